@@ -17,13 +17,13 @@ impl RootContainer {
     const CORNER_RADIUS: u32 = 8;
     const BORDER_COLOR: Pixel = Pixel::from_rgba(0xFD, 0xB0, 0xC0, 0xFF);
     const BG_COLOR: Pixel = Pixel::from_rgba(0, 0, 0, 0x80);
-    const TITLE_HEIGHT: u32 = 20;
 
     pub fn new(width: u32, height: u32) -> Self {
         let real_width = width + Self::CORNER_RADIUS;
-        let real_height = height + Self::TITLE_HEIGHT;
+        // The title bar is drawn by the WM
+        let real_height = height;
         let window_x = Self::CORNER_RADIUS / 2;
-        let window_y = Self::TITLE_HEIGHT;
+        let window_y = 0;
 
         let mut win = Window::create(0, 0, real_width, real_height);
 
@@ -33,15 +33,11 @@ impl RootContainer {
             real_width,
             real_height,
             Self::CORNER_RADIUS,
-            |is_border, line_num| {
+            |is_border, _| {
                 if is_border {
                     Self::BORDER_COLOR
                 } else {
-                    if line_num < Self::TITLE_HEIGHT {
-                        Self::BORDER_COLOR
-                    } else {
-                        Self::BG_COLOR
-                    }
+                    Self::BG_COLOR
                 }
             },
         );
@@ -0,0 +1,24 @@
+pub use opal_abi::background::{Background, GradientDirection, ImageFit};
+use opal_abi::com::{
+    request::{RequestKind, SetBackground},
+    response::{OkResponse, Response, error::ResponseError},
+};
+
+use crate::{privilege_key, send_request};
+
+/// Changes the background drawn behind every window, this is a privileged request,
+/// it fails with [`ResponseError::PermissionDenied`] unless the WM spawned the current process,
+/// or with [`ResponseError::MissingCapability`] if the WM didn't agree to [`opal_abi::com::packet::Capabilities::SET_BACKGROUND`].
+pub fn set_background(background: Background) -> Result<(), ResponseError> {
+    let key = privilege_key().ok_or(ResponseError::PermissionDenied)?;
+
+    let resp = send_request(RequestKind::SetBackground(SetBackground::new(
+        key, background,
+    )))
+    .expect("Failed to send SetBackground request");
+    match resp {
+        Response::Ok(OkResponse::Success, _) => Ok(()),
+        Response::Err(e, _) => Err(e),
+        _ => panic!("Unexpected response, {:#?}", resp),
+    }
+}
@@ -0,0 +1,62 @@
+use opal_abi::com::{
+    request::{CaptureScreen, RequestKind},
+    response::{OkResponse, Response, error::ResponseError},
+};
+
+use crate::{privilege_key, send_request, window::Window};
+use opal_abi::fb::Pixel;
+
+/// Pixels captured by the WM, see [`capture_screen`] and [`Window::capture`]
+pub struct Capture {
+    width: u32,
+    height: u32,
+    /// Row by row, with straight alpha
+    pixels: Vec<Pixel>,
+}
+
+impl Capture {
+    /// Sends a capture request and copies the captured pixels out of the shared memory the WM responds with
+    pub(crate) fn request(request: RequestKind) -> Result<Self, ResponseError> {
+        let resp = send_request(request).expect("Failed to send Capture request");
+        let captured = match resp {
+            Response::Ok(OkResponse::Captured(captured), _) => captured,
+            Response::Err(e, _) => return Err(e),
+            _ => panic!("Unexpected response, {:#?}", resp),
+        };
+
+        let (pixels, mmap_ri) =
+            Window::map_pixels(captured.shm_key(), captured.width(), captured.height());
+        let pixels = unsafe { pixels.as_ref() }.to_vec();
+        safa_api::syscalls::resources::destroy_resource(mmap_ri)
+            .expect("Failed to destroy the captured pixels' mapping");
+
+        Ok(Self {
+            width: captured.width(),
+            height: captured.height(),
+            pixels,
+        })
+    }
+
+    #[inline]
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[inline]
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns the captured pixels row by row, with straight alpha.
+    pub fn pixels(&self) -> &[Pixel] {
+        &self.pixels
+    }
+}
+
+/// Captures the composited pixels of the whole screen, this is a privileged request,
+/// it fails with [`ResponseError::PermissionDenied`] unless the WM spawned the current process,
+/// or with [`ResponseError::MissingCapability`] if the WM didn't agree to [`opal_abi::com::packet::Capabilities::CAPTURE`].
+pub fn capture_screen() -> Result<Capture, ResponseError> {
+    let key = privilege_key().ok_or(ResponseError::PermissionDenied)?;
+    Capture::request(RequestKind::CaptureScreen(CaptureScreen::new(key)))
+}
@@ -0,0 +1,81 @@
+use std::{
+    io,
+    ops::ControlFlow,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use opal_abi::com::response::event::EventEnvelope;
+use safa_api::syscalls::types::Ri;
+
+/// Whether or not an [`EventLoop`] exists
+static EVENT_LOOP_EXISTS: AtomicBool = AtomicBool::new(false);
+
+/// Panics if an [`EventLoop`] exists, called by everything that takes events other than the event loop itself
+pub(crate) fn assert_no_event_loop() {
+    assert!(
+        !EVENT_LOOP_EXISTS.load(Ordering::Acquire),
+        "Events can't be taken while an EventLoop exists, it dispatches every event"
+    );
+}
+
+/// Dispatches the events the WM sends to a handler.
+///
+/// Only one event loop can exist at a time, while it exists nothing else can take events
+/// ([`crate::poll_event`], [`crate::window::Window::poll_event`] and the blocking variants panic),
+/// requests can still be sent from any thread without waiting for the event loop.
+pub struct EventLoop {
+    _private: (),
+}
+
+impl EventLoop {
+    /// Creates the event loop, returns `None` if another event loop exists.
+    pub fn new() -> Option<Self> {
+        EVENT_LOOP_EXISTS
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| Self { _private: () })
+    }
+
+    /// Returns the raw resource of the connection with the WM, see [`crate::raw_resource`].
+    ///
+    /// When it becomes readable call [`Self::dispatch_pending`].
+    pub fn raw_resource(&self) -> Ri {
+        crate::raw_resource()
+    }
+
+    /// Passes every event the WM sent so far to `handler`, in order, without blocking,
+    /// returns the amount of events dispatched.
+    ///
+    /// Each event comes with the ID of the window it is for, see [`crate::window::Window::id`].
+    pub fn dispatch_pending(
+        &mut self,
+        mut handler: impl FnMut(EventEnvelope),
+    ) -> io::Result<usize> {
+        let mut dispatched = 0;
+        while let Some(event) = crate::next_event(None)? {
+            handler(event);
+            dispatched += 1;
+        }
+        Ok(dispatched)
+    }
+
+    /// Passes every event the WM sends to `handler`, waiting for more events until `handler` breaks with a value,
+    /// which is then returned.
+    pub fn run<T>(
+        &mut self,
+        mut handler: impl FnMut(EventEnvelope) -> ControlFlow<T>,
+    ) -> io::Result<T> {
+        loop {
+            let event = crate::wait_for_next_event(None)?;
+            if let ControlFlow::Break(value) = handler(event) {
+                return Ok(value);
+            }
+        }
+    }
+}
+
+impl Drop for EventLoop {
+    fn drop(&mut self) {
+        EVENT_LOOP_EXISTS.store(false, Ordering::Release);
+    }
+}
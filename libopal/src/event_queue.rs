@@ -0,0 +1,130 @@
+use std::collections::VecDeque;
+
+use opal_abi::com::{packet::WinID, response::event::EventEnvelope};
+
+/// The events received from the WM that weren't taken yet, in the order the WM sent them.
+///
+/// Events are taken either in order regardless of their window (see [`crate::poll_event`]),
+/// or in order for a single window (see [`crate::window::Window::poll_event`]) skipping over the events of other windows.
+pub(crate) struct EventQueue {
+    events: VecDeque<EventEnvelope>,
+}
+
+impl EventQueue {
+    pub const fn new() -> Self {
+        Self {
+            events: VecDeque::new(),
+        }
+    }
+
+    /// Queues an event after every event received before it
+    pub fn push(&mut self, event: EventEnvelope) {
+        self.events.push_back(event);
+    }
+
+    /// Takes the oldest event, of the window `win_id` if given or of any window otherwise
+    pub fn pop(&mut self, win_id: Option<WinID>) -> Option<EventEnvelope> {
+        match win_id {
+            None => self.events.pop_front(),
+            Some(win_id) => {
+                let index = self
+                    .events
+                    .iter()
+                    .position(|event| event.win_id() == win_id)?;
+                self.events.remove(index)
+            }
+        }
+    }
+
+    /// Drops every event of the window `win_id`, called once the window is destroyed
+    pub fn remove_window(&mut self, win_id: WinID) {
+        self.events.retain(|event| event.win_id() != win_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opal_abi::com::response::{OkResponse, Response, event::Event};
+
+    use super::*;
+
+    fn event(win_id: WinID, timestamp: u64) -> Response {
+        Response::Event(EventEnvelope::new(Event::WindowFocused, win_id, timestamp))
+    }
+
+    /// Encodes and decodes `responses` like they were sent by the WM, queuing the events among them in order
+    fn replay(responses: &[Response]) -> EventQueue {
+        let mut queue = EventQueue::new();
+        for response in responses {
+            let (bytes, len) = response.encode();
+            if let Response::Event(event) = Response::decode(&bytes[..len]).unwrap() {
+                queue.push(event);
+            }
+        }
+        queue
+    }
+
+    /// Takes every event left with `pop(win_id)`, returns their timestamps
+    fn drain(queue: &mut EventQueue, win_id: Option<WinID>) -> Vec<u64> {
+        std::iter::from_fn(|| queue.pop(win_id))
+            .map(|event| event.timestamp())
+            .collect()
+    }
+
+    #[test]
+    fn pop_any_window_in_fifo_order() {
+        let mut queue = replay(&[
+            event(1, 10),
+            Response::Ok(OkResponse::Success, 1),
+            event(2, 20),
+            event(1, 30),
+            Response::Ok(OkResponse::Success, 2),
+            event(3, 40),
+        ]);
+
+        assert_eq!(drain(&mut queue, None), [10, 20, 30, 40]);
+    }
+
+    #[test]
+    fn pop_single_window_skips_other_windows() {
+        let mut queue = replay(&[
+            event(1, 10),
+            event(2, 20),
+            event(1, 30),
+            event(3, 40),
+            event(2, 50),
+        ]);
+
+        assert_eq!(drain(&mut queue, Some(2)), [20, 50]);
+        assert_eq!(queue.pop(Some(4)), None);
+        // The skipped events keep their order
+        assert_eq!(drain(&mut queue, None), [10, 30, 40]);
+    }
+
+    #[test]
+    fn pop_keeps_the_envelope() {
+        let mut queue = replay(&[Response::Event(EventEnvelope::new(
+            Event::CloseRequested,
+            7,
+            1234,
+        ))]);
+
+        let event = queue.pop(Some(7)).unwrap();
+        assert_eq!(event.event(), Event::CloseRequested);
+        assert_eq!(event.win_id(), 7);
+        assert_eq!(event.timestamp(), 1234);
+    }
+
+    #[test]
+    fn remove_window_drops_only_its_events() {
+        let mut queue = replay(&[event(1, 10), event(2, 20), event(1, 30), event(3, 40)]);
+
+        queue.remove_window(1);
+        assert_eq!(queue.pop(Some(1)), None);
+        assert_eq!(drain(&mut queue, None), [20, 40]);
+
+        // Events received after the removal are queued again
+        queue.push(EventEnvelope::new(Event::WindowFocused, 1, 50));
+        assert_eq!(drain(&mut queue, Some(1)), [50]);
+    }
+}
@@ -1,18 +1,46 @@
 use std::{
     io::{self, Read, Write},
-    sync::{LazyLock, Mutex},
+    sync::{
+        Condvar, LazyLock, Mutex, MutexGuard, OnceLock,
+        atomic::{AtomicU32, Ordering},
+    },
 };
 
 use opal_abi::com::{
-    packet::MAX_PACKET_SIZE,
-    request::{Request, RequestKind},
-    response::{OkResponse, Response, event::Event},
+    packet::{Capabilities, MAX_PACKET_SIZE, NO_SERIAL, PROTOCOL_VERSION, Serial, WinID},
+    request::{Hello, Request, RequestKind},
+    response::{OkResponse, Response, error::ResponseError, event::EventEnvelope},
 };
-use safa_api::sockets::UnixSockConnection;
+use safa_api::{
+    abi::io::{PollEntry, PollEvents},
+    sockets::UnixSockConnection,
+    syscalls::types::Ri,
+};
+
+use crate::event_queue::EventQueue;
 
+pub mod background;
+pub mod capture;
+pub mod event_loop;
+mod event_queue;
 pub mod window;
 
-static EVENTS_QUEUE: Mutex<Vec<Event>> = Mutex::new(Vec::new());
+/// The events the WM may send, see [`poll_event`] and [`event_loop::EventLoop`]
+pub use opal_abi::com::response::event;
+/// Layout independent key symbols carried by key events
+pub use opal_abi::keysym;
+
+static EVENTS_QUEUE: Mutex<EventQueue> = Mutex::new(EventQueue::new());
+/// The responses read by a thread while waiting for something else, kept until the thread that sent the request takes them
+static PENDING_RESPONSES: Mutex<Vec<Response>> = Mutex::new(Vec::new());
+/// The serial of the next request, see [`Serial`]
+static NEXT_SERIAL: AtomicU32 = AtomicU32::new(NO_SERIAL + 1);
+/// Whether or not a thread is waiting for the connection with the WM to become readable, see [`take_packet`]
+static READING: Mutex<bool> = Mutex::new(false);
+/// Notified once the thread waiting for the connection read what the WM sent, see [`take_packet`]
+static PACKETS_READ: Condvar = Condvar::new();
+/// The optional features the WM agreed to in [`init`]
+static CAPABILITIES: OnceLock<Capabilities> = OnceLock::new();
 
 static WM_CONNECTION: LazyLock<Mutex<UnixSockConnection>> = LazyLock::new(|| {
     use safa_api::sockets::{SockKind, UnixSockConnectionBuilder};
@@ -21,73 +49,268 @@ static WM_CONNECTION: LazyLock<Mutex<UnixSockConnection>> = LazyLock::new(|| {
     let mut builder = UnixSockConnectionBuilder::from_abstract_path(addr).unwrap();
 
     builder.set_type(SockKind::SeqPacket);
-    builder
+    let connection = builder
         .connect()
-        .map(|k| Mutex::new(k))
-        .unwrap_or_else(|_| panic!("Failed to establish connection with the Opal WM at {addr}"))
+        .unwrap_or_else(|_| panic!("Failed to establish connection with the Opal WM at {addr}"));
+    Mutex::new(connection)
 });
 
-pub(crate) fn send_request(req: RequestKind) -> io::Result<Response> {
-    let request = Request::new(req);
-    let (bytes, len) = request.encode();
-    let mut events = EVENTS_QUEUE
-        .lock()
-        .expect("Failed to acquire lock on events queue");
-    let mut wm = WM_CONNECTION.lock().expect("Failed to lock WM connection");
+fn wm_connection() -> MutexGuard<'static, UnixSockConnection> {
+    WM_CONNECTION.lock().expect("Failed to lock WM connection")
+}
 
-    Write::write(&mut *wm, &bytes[..len])?;
+/// Returns a serial no other request in flight uses
+fn next_serial() -> Serial {
+    loop {
+        let serial = NEXT_SERIAL.fetch_add(1, Ordering::Relaxed);
+        // Skipped once the serials wrap around
+        if serial != NO_SERIAL {
+            return serial;
+        }
+    }
+}
+
+/// Reads a single packet from the WM without blocking, returns `None` if the WM didn't send anything yet
+fn try_read_response(wm: &mut UnixSockConnection) -> io::Result<Option<Response>> {
+    // The connection blocks, so it is only read from once the WM sent something
+    if !poll_connection(resource_of(wm), PollEvents::IN, Some(0))? {
+        return Ok(None);
+    }
 
     let mut packet: [u8; MAX_PACKET_SIZE] = [0u8; MAX_PACKET_SIZE];
-    let response = loop {
-        let read = Read::read(&mut *wm, &mut packet)?;
+    let read = Read::read(wm, &mut packet)?;
 
-        let msg = &packet[..read];
+    let msg = &packet[..read];
+    Ok(Some(
+        Response::decode(msg).expect("Couldn't Parse WM's response"),
+    ))
+}
 
-        let response = Response::decode(msg).expect("Couldn't Parse WM's response");
-        match response {
-            Response::Event(event) => {
-                events.push(event);
-            }
-            other => break other,
-        }
-    };
-    Ok(response)
+/// Queues a response that wasn't what the reading thread was waiting for, so that whoever waits for it can take it
+fn queue_response(response: Response) {
+    match response {
+        Response::Event(event) => EVENTS_QUEUE
+            .lock()
+            .expect("Failed to acquire lock on events queue")
+            .push(event),
+        // No request carries it so nobody would ever take it, see `Request::decode_serial`
+        Response::Err(err, NO_SERIAL) => panic!("The WM couldn't decode a request: {err:?}"),
+        response => PENDING_RESPONSES
+            .lock()
+            .expect("Failed to acquire lock on pending responses")
+            .push(response),
+    }
 }
 
-/// Blockingly wait for an event from the window manager.
-pub fn wait_for_event_blocking() -> io::Result<Event> {
-    {
-        let mut events = EVENTS_QUEUE
+/// Waits until the connection with the WM is ready for `events` or failed, or until `timeout_ms` passes if given,
+/// returns whether or not it is ready.
+///
+/// The connection isn't locked while waiting, so that other threads can keep using it.
+fn wait_for_connection(events: PollEvents, timeout_ms: Option<u64>) -> io::Result<bool> {
+    poll_connection(raw_resource(), events, timeout_ms)
+}
+
+/// Same as [`wait_for_connection`] but for the raw resource `ri` of the connection, see [`resource_of`]
+fn poll_connection(ri: Ri, events: PollEvents, timeout_ms: Option<u64>) -> io::Result<bool> {
+    let mut entries = [PollEntry::new(ri, events)];
+    safa_api::syscalls::io::poll(&mut entries, timeout_ms).map_err(|err| {
+        io::Error::other(format!(
+            "Failed to poll the connection with the WM: {err:?}"
+        ))
+    })?;
+
+    Ok(entries[0]
+        .returned_events()
+        .intersects(events | PollEvents::HUP | PollEvents::ERR))
+}
+
+/// Reads every packet the WM sent so far into the queues without blocking.
+///
+/// Only called with [`READING`] locked, so that a thread can check the queues and go to sleep (see [`take_packet`])
+/// without missing the packets read in the meantime.
+fn read_pending_packets(_reading: &MutexGuard<'static, bool>) -> io::Result<()> {
+    let mut wm = wm_connection();
+    while let Some(response) = try_read_response(&mut wm)? {
+        queue_response(response);
+    }
+    Ok(())
+}
+
+/// Takes a packet the WM sent out of the queues with `take`, reading what the WM sent so far if needed,
+/// waits for the WM to send it if `block` is true or returns `None` otherwise.
+///
+/// A single thread at a time waits for the connection to become readable and reads every packet into the queues,
+/// while the other blocked threads sleep until it did, so that no thread sleeps on the connection after another thread
+/// already read the packet it waits for.
+fn take_packet<T>(block: bool, mut take: impl FnMut() -> Option<T>) -> io::Result<Option<T>> {
+    loop {
+        let mut reading = READING
             .lock()
-            .expect("Failed to acquire lock on events queue");
+            .expect("Failed to acquire lock on the reading state");
+        if let Some(value) = take() {
+            return Ok(Some(value));
+        }
+
+        if *reading {
+            // The waiting thread reads the packets the connection has soon and then wakes this thread up
+            if block || wait_for_connection(PollEvents::IN, Some(0))? {
+                drop(
+                    PACKETS_READ
+                        .wait(reading)
+                        .expect("Failed to acquire lock on the reading state"),
+                );
+                continue;
+            }
+            return Ok(None);
+        }
 
-        if let Some(event) = events.pop() {
-            return Ok(event);
+        if !block {
+            read_pending_packets(&reading)?;
+            return Ok(take());
         }
+
+        *reading = true;
+        drop(reading);
+        let ready = wait_for_connection(PollEvents::IN, None);
+
+        let mut reading = READING
+            .lock()
+            .expect("Failed to acquire lock on the reading state");
+        let result = ready.and_then(|_| read_pending_packets(&reading));
+        *reading = false;
+        PACKETS_READ.notify_all();
+        result?;
     }
+}
 
-    let mut wm = WM_CONNECTION.lock().expect("Failed to lock WM connection");
-    let mut packet: [u8; MAX_PACKET_SIZE] = [0u8; MAX_PACKET_SIZE];
-    let read = Read::read(&mut *wm, &mut packet)?;
+/// Sends a request to the WM and waits for the response to it.
+///
+/// Safe to call from several threads at once, each thread gets the response to it's own request (see [`Serial`]),
+/// the connection is only locked while writing the request and while reading what the WM sent.
+///
+/// Requests for optional features the WM didn't agree to (see [`capabilities`]) aren't sent,
+/// they are responded to with [`ResponseError::MissingCapability`] right away like the WM would.
+pub(crate) fn send_request(req: RequestKind) -> io::Result<Response> {
+    let serial = next_serial();
+    if !capabilities().contains(req.required_capabilities()) {
+        return Ok(Response::Err(ResponseError::MissingCapability, serial));
+    }
+    let (bytes, len) = Request::new(serial, req).encode();
 
-    let msg = &packet[..read];
+    // The connection blocks, wait for the WM to make room for the request without holding the connection
+    wait_for_connection(PollEvents::OUT, None)?;
+    Write::write_all(&mut *wm_connection(), &bytes[..len])?;
 
-    let response = Response::decode(msg).expect("Couldn't Parse WM's response");
-    match response {
-        Response::Event(event) => {
-            return Ok(event);
+    take_packet(true, || {
+        let mut pending = PENDING_RESPONSES
+            .lock()
+            .expect("Failed to acquire lock on pending responses");
+        pending
+            .iter()
+            .position(|response| response.serial() == Some(serial))
+            .map(|index| pending.swap_remove(index))
+    })
+    .map(|response| response.expect("Blocking takes always take something"))
+}
+
+/// Takes the oldest event of the window `win_id` if given or of any window otherwise, see [`take_packet`]
+fn take_event(block: bool, win_id: Option<WinID>) -> io::Result<Option<EventEnvelope>> {
+    take_packet(block, || {
+        EVENTS_QUEUE
+            .lock()
+            .expect("Failed to acquire lock on events queue")
+            .pop(win_id)
+    })
+}
+
+/// Returns the oldest event of the window `win_id` if given or of any window otherwise, without blocking,
+/// or `None` if the WM didn't send any such events yet.
+pub(crate) fn next_event(win_id: Option<WinID>) -> io::Result<Option<EventEnvelope>> {
+    take_event(false, win_id)
+}
+
+/// Blockingly waits for the oldest event of the window `win_id` if given or of any window otherwise, see [`next_event`]
+pub(crate) fn wait_for_next_event(win_id: Option<WinID>) -> io::Result<EventEnvelope> {
+    take_event(true, win_id).map(|event| event.expect("Blocking takes always take something"))
+}
+
+/// Returns the next event from the window manager along with the ID of the window it is for and the time it was sent at,
+/// without blocking, or `None` if the WM didn't send any events yet.
+///
+/// Events come in the order the WM sent them, mouse events are sent to the window under the cursor,
+/// while key events are sent to the focused window, see [`window::Window::poll_event`] to only take the events of a single window.
+///
+/// Panics while an [`event_loop::EventLoop`] exists.
+pub fn poll_event() -> io::Result<Option<EventEnvelope>> {
+    event_loop::assert_no_event_loop();
+    next_event(None)
+}
+
+/// Blockingly wait for an event from the window manager, see [`poll_event`].
+///
+/// Other threads can keep sending requests while this thread waits.
+pub fn wait_for_event_blocking() -> io::Result<EventEnvelope> {
+    event_loop::assert_no_event_loop();
+    wait_for_next_event(None)
+}
+
+/// Drops the events of the window `win_id` that weren't taken yet, called once the window is destroyed
+pub(crate) fn discard_events(win_id: WinID) {
+    EVENTS_QUEUE
+        .lock()
+        .expect("Failed to acquire lock on events queue")
+        .remove_window(win_id);
+}
+
+/// Returns the raw resource of the connection with the WM, it becomes readable whenever the WM sends something,
+/// so applications can wait for it in their own poll loop and then call [`poll_event`] until it returns `None`.
+///
+/// The resource must only be waited on, reading from or writing to it directly corrupts the connection.
+pub fn raw_resource() -> Ri {
+    resource_of(&wm_connection())
+}
+
+#[cfg(target_os = "safaos")]
+fn resource_of(connection: &UnixSockConnection) -> Ri {
+    use std::os::safaos::AsRawResource;
+    connection.as_raw_resource()
+}
+
+/// Only SafaOS resources can be waited for, so there are none outside of it
+#[cfg(not(target_os = "safaos"))]
+fn resource_of(_connection: &UnixSockConnection) -> Ri {
+    unimplemented!("Only SafaOS resources can be polled")
+}
+
+/// Initializes the client that is going to communicate with the WM, negotiating the protocol version
+/// Panicks on failure, including if the WM doesn't speak this client's protocol version
+pub fn init() {
+    let hello = Hello::new(PROTOCOL_VERSION, Capabilities::all());
+    let resp = send_request(RequestKind::Hello(hello)).expect("Failed to send Hello request");
+    match resp {
+        Response::Ok(OkResponse::Welcome(welcome), _) => {
+            _ = CAPABILITIES.set(welcome.capabilities());
         }
-        other => unreachable!(
-            "Shouldn't get any other kind of responses while waiting for events, got: {other:#?}"
+        Response::Err(ResponseError::UnsupportedVersion { min, max }, _) => panic!(
+            "The WM only speaks protocol versions {min} to {max}, but this client speaks version {PROTOCOL_VERSION}"
         ),
+        // A WM that predates the negotiation takes the Hello for a Ping
+        Response::Ok(OkResponse::Success, _) => panic!(
+            "The WM predates protocol version negotiation, but this client speaks version {PROTOCOL_VERSION}"
+        ),
+        _ => panic!("Hello request, unexpected response {:#?}", resp),
     }
 }
 
-/// Initializes the client that is going to communicate with the WM
-/// Panicks on failure
-pub fn init() {
-    assert!(
-        send_request(RequestKind::Ping).is_ok_and(|o| o == Response::Ok(OkResponse::Success)),
-        "Ping request, responded with an error"
-    )
+/// Returns the privilege key the WM passed to the current process, `None` unless the WM spawned the current process,
+/// see [`opal_abi::PRIVILEGE_KEY_ENV`]
+pub(crate) fn privilege_key() -> Option<u64> {
+    std::env::var(opal_abi::PRIVILEGE_KEY_ENV)
+        .ok()
+        .and_then(|key| key.parse().ok())
+}
+
+/// Returns the optional features of the protocol the WM agreed to, empty before [`init`]
+pub fn capabilities() -> Capabilities {
+    CAPABILITIES.get().copied().unwrap_or(Capabilities::empty())
 }
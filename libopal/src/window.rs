@@ -1,19 +1,29 @@
-use std::ptr::NonNull;
+use std::{io, ptr::NonNull};
 
 use opal_abi::com::{
-    request::{CreateWindow, DamageWindow, RequestKind},
-    response::{OkResponse, Response},
+    request::{
+        AckConfigure, CaptureWindow, CreateWindow, DamageWindow, DestroyWindow, MoveWindow,
+        RequestFrameCallback, RequestKind, ResizeWindow, SetCursor, SetOpaqueRegion,
+        WindowTextChunk,
+    },
+    response::{OkResponse, ResizeWindowResp, Response, event::EventEnvelope},
 };
 use safa_api::{
     abi::mem::{MemMapFlags, ShmFlags},
     syscalls::types::Ri,
 };
 
-use crate::send_request;
-pub use opal_abi::fb::Pixel;
+use crate::{
+    capture::Capture, discard_events, event_loop::assert_no_event_loop, next_event, send_request,
+    wait_for_next_event,
+};
+pub use opal_abi::com::packet::WinID;
+pub use opal_abi::com::request::WindowFlags;
+pub use opal_abi::cursor::CursorShape;
+pub use opal_abi::fb::{AlphaFormat, Pixel};
 
 pub struct Window {
-    win_id: u16,
+    win_id: WinID,
     width: u32,
     height: u32,
     pixels: NonNull<[Pixel]>,
@@ -22,6 +32,10 @@ pub struct Window {
 
 impl Drop for Window {
     fn drop(&mut self) {
+        // It is ok if the WM is gone by now, the window is gone with it
+        _ = send_request(RequestKind::DestroyWindow(DestroyWindow::new(self.win_id)));
+        // Including the events received while waiting for the window to be destroyed
+        discard_events(self.win_id);
         safa_api::syscalls::resources::destroy_resource(self.pixels_mmap_ri)
             .expect("Window's pixels Dropped too early");
     }
@@ -31,6 +45,28 @@ unsafe impl Send for Window {}
 unsafe impl Sync for Window {}
 
 impl Window {
+    /// The ID the WM gave the window, events are for the window with the ID they come with
+    #[inline]
+    pub const fn id(&self) -> WinID {
+        self.win_id
+    }
+
+    /// Returns the oldest event for this window without blocking, or `None` if the WM didn't send any yet.
+    ///
+    /// The events of other windows read meanwhile stay queued for their own windows, see [`crate::poll_event`].
+    ///
+    /// Panics while an [`crate::event_loop::EventLoop`] exists.
+    pub fn poll_event(&self) -> io::Result<Option<EventEnvelope>> {
+        assert_no_event_loop();
+        next_event(Some(self.win_id))
+    }
+
+    /// Blockingly waits for the next event for this window, see [`Self::poll_event`]
+    pub fn wait_for_event(&self) -> io::Result<EventEnvelope> {
+        assert_no_event_loop();
+        wait_for_next_event(Some(self.win_id))
+    }
+
     #[inline]
     pub const fn height(&self) -> u32 {
         self.height
@@ -43,27 +79,135 @@ impl Window {
 
     /// Redraws the window's pixels as a rectangle starting at (from_x, from_y) with the given width and height.
     pub fn redraw(&self, from_x: u32, from_y: u32, width: u32, height: u32) {
-        assert_eq!(
-            send_request(RequestKind::DamageWindow(DamageWindow::new(
-                self.win_id,
-                from_x,
-                from_y,
-                width,
-                height,
-            )))
-            .expect("Failed to send Damage Window request"),
-            Response::Ok(OkResponse::Success),
+        assert!(
+            matches!(
+                send_request(RequestKind::DamageWindow(DamageWindow::new(
+                    self.win_id,
+                    from_x,
+                    from_y,
+                    width,
+                    height,
+                )))
+                .expect("Failed to send Damage Window request"),
+                Response::Ok(OkResponse::Success, _)
+            ),
             "Damage Window request returned an unexpected response"
         );
     }
 
+    /// Request the WM to move the window to the position (x, y) on the screen,
+    /// the WM may clamp the position so the window stays within the screen.
+    pub fn move_to(&self, x: u32, y: u32) {
+        assert!(
+            matches!(
+                send_request(RequestKind::MoveWindow(MoveWindow::new(self.win_id, x, y)))
+                    .expect("Failed to send Move Window request"),
+                Response::Ok(OkResponse::Success, _)
+            ),
+            "Move Window request returned an unexpected response"
+        );
+    }
+
+    /// Sets the title of the window, displayed by the WM in the window's title bar.
+    pub fn set_title(&self, title: &str) {
+        for chunk in WindowTextChunk::chunks(self.win_id, title) {
+            assert!(
+                matches!(
+                    send_request(RequestKind::SetWindowTitle(chunk))
+                        .expect("Failed to send Set Window Title request"),
+                    Response::Ok(OkResponse::Success, _)
+                ),
+                "Set Window Title request returned an unexpected response"
+            );
+        }
+    }
+
+    /// Sets the application ID of the window, which identifies the application that created it (for example `org.safaos.hello`).
+    pub fn set_app_id(&self, app_id: &str) {
+        for chunk in WindowTextChunk::chunks(self.win_id, app_id) {
+            assert!(
+                matches!(
+                    send_request(RequestKind::SetAppId(chunk))
+                        .expect("Failed to send Set App ID request"),
+                    Response::Ok(OkResponse::Success, _)
+                ),
+                "Set App ID request returned an unexpected response"
+            );
+        }
+    }
+
+    /// Sets the shape of the mouse cursor while it is over the window's pixels.
+    ///
+    /// Panics if the WM didn't agree to [`opal_abi::com::packet::Capabilities::CURSOR_SHAPES`], see [`crate::capabilities`].
+    pub fn set_cursor(&self, shape: CursorShape) {
+        assert!(
+            matches!(
+                send_request(RequestKind::SetCursor(SetCursor::new(self.win_id, shape)))
+                    .expect("Failed to send Set Cursor request"),
+                Response::Ok(OkResponse::Success, _)
+            ),
+            "Set Cursor request returned an unexpected response"
+        );
+    }
+
+    /// Tells the WM that the rectangle starting at (x, y) with the given width and height is fully opaque
+    /// (every pixel in it has an alpha of 0xFF), the WM then skips drawing whatever is behind it.
+    ///
+    /// An empty rectangle removes the hint, the WM then checks the window's pixels instead.
+    pub fn set_opaque_region(&self, x: u32, y: u32, width: u32, height: u32) {
+        assert!(
+            matches!(
+                send_request(RequestKind::SetOpaqueRegion(SetOpaqueRegion::new(
+                    self.win_id,
+                    x,
+                    y,
+                    width,
+                    height,
+                )))
+                .expect("Failed to send Set Opaque Region request"),
+                Response::Ok(OkResponse::Success, _)
+            ),
+            "Set Opaque Region request returned an unexpected response"
+        );
+    }
+
+    /// Captures the window's pixels including the decorations around it, as if nothing else was on the screen.
+    ///
+    /// Panics if the WM didn't agree to [`opal_abi::com::packet::Capabilities::CAPTURE`], see [`crate::capabilities`].
+    pub fn capture(&self) -> Capture {
+        // The client owns the window so the privilege key isn't needed
+        Capture::request(RequestKind::CaptureWindow(CaptureWindow::new(
+            0,
+            self.win_id,
+        )))
+        .unwrap_or_else(|e| panic!("Failed to capture: {:?}", e))
+    }
+
+    /// Asks the WM to send a [`opal_abi::com::response::event::Event::FrameDone`] event once the next frame containing the window is presented,
+    /// draw the next frame of an animation when the event is received to pace the animation to the WM's refresh rate.
+    ///
+    /// Panics if the WM didn't agree to [`opal_abi::com::packet::Capabilities::FRAME_CALLBACKS`], see [`crate::capabilities`].
+    pub fn request_frame_callback(&self) {
+        assert!(
+            matches!(
+                send_request(RequestKind::RequestFrameCallback(
+                    RequestFrameCallback::new(self.win_id)
+                ))
+                .expect("Failed to send Request Frame Callback request"),
+                Response::Ok(OkResponse::Success, _)
+            ),
+            "Request Frame Callback request returned an unexpected response"
+        );
+    }
+
     #[inline(always)]
     /// Returns a mutable reference to the window's pixels.
     pub const fn pixels_mut(&mut self) -> &mut [Pixel] {
         unsafe { self.pixels.as_mut() }
     }
 
-    fn new_inner(win_id: u16, shm_key: usize, width: u32, height: u32) -> Self {
+    /// Maps the pixels of a window with the size `width`*`height` from the shared memory key `shm_key`
+    pub(crate) fn map_pixels(shm_key: usize, width: u32, height: u32) -> (NonNull<[Pixel]>, Ri) {
         let pixels_required = width as usize * height as usize;
         let bytes_required = pixels_required * size_of::<Pixel>();
         let pages_required = bytes_required.div_ceil(4096);
@@ -86,6 +230,11 @@ impl Window {
             .expect("Failed to destroy SHM Resource");
 
         let pixels = NonNull::slice_from_raw_parts(pixels_bytes.cast::<Pixel>(), pixels_required);
+        (pixels, pixels_mmap_ri)
+    }
+
+    fn new_inner(win_id: u16, shm_key: usize, width: u32, height: u32) -> Self {
+        let (pixels, pixels_mmap_ri) = Self::map_pixels(shm_key, width, height);
         Self {
             win_id,
             pixels,
@@ -95,16 +244,81 @@ impl Window {
         }
     }
 
+    /// Request the WM to resize the window to `width`*`height` pixels, and remaps the window's pixels.
+    ///
+    /// The WM keeps the old pixels that still fit in the new size, the rest are transparent,
+    /// the WM may also clamp the size to the screen's size, use [`Self::width`] and [`Self::height`] to get the actual size.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        let resp = send_request(RequestKind::ResizeWindow(ResizeWindow::new(
+            self.win_id,
+            width,
+            height,
+        )))
+        .expect("Failed to send Resize Window Request");
+
+        let resized = match resp {
+            Response::Ok(OkResponse::WindowResized(r), _) => r,
+            Response::Err(e, _) => panic!("Failed to resize window: {:?}", e),
+            _ => panic!("Unexpected response, {:#?}", resp),
+        };
+
+        self.remap(resized);
+    }
+
+    /// Acknowledges a [`crate::event::ConfigureEvent`] with the serial `serial`, the client should be ready to draw at the configured size before acknowledging it.
+    ///
+    /// If it is the most recent configure event the WM resizes the window and the window's pixels are remapped,
+    /// returns true in that case.
+    pub fn ack_configure(&mut self, serial: u32) -> bool {
+        let resp = send_request(RequestKind::AckConfigure(AckConfigure::new(
+            self.win_id,
+            serial,
+        )))
+        .expect("Failed to send Ack Configure Request");
+
+        match resp {
+            Response::Ok(OkResponse::WindowResized(resized), _) => {
+                self.remap(resized);
+                true
+            }
+            Response::Ok(OkResponse::Success, _) => false,
+            Response::Err(e, _) => panic!("Failed to acknowledge configure event: {:?}", e),
+            _ => panic!("Unexpected response, {:#?}", resp),
+        }
+    }
+
+    /// Remaps the window's pixels after the WM resized the window
+    fn remap(&mut self, resized: ResizeWindowResp) {
+        let (pixels, pixels_mmap_ri) =
+            Self::map_pixels(resized.shm_key(), resized.width(), resized.height());
+
+        safa_api::syscalls::resources::destroy_resource(self.pixels_mmap_ri)
+            .expect("Window's pixels Dropped too early");
+
+        self.pixels = pixels;
+        self.pixels_mmap_ri = pixels_mmap_ri;
+        self.width = resized.width();
+        self.height = resized.height();
+    }
+
     /// Request the creation of a new window from the WM.
+    ///
+    /// The WM draws it's own decorations (title bar and border) around the window,
+    /// `width` and `height` are the size of the window's pixels excluding the decorations.
     pub fn create(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self::create_with_flags(x, y, width, height, WindowFlags::empty())
+    }
+
+    /// Same as [`Self::create`] but with the given `flags`, for example [`WindowFlags::NO_DECORATIONS`]
+    pub fn create_with_flags(x: u32, y: u32, width: u32, height: u32, flags: WindowFlags) -> Self {
         let resp = send_request(RequestKind::CreateWindow(CreateWindow::new(
-            0, width, height, x, y,
+            flags, width, height, x, y,
         )))
         .expect("Failed to send Create Window Request");
 
         let window = match resp {
-            Response::Ok(OkResponse::WindowCreated(w)) => w,
-            Response::Err(e) => panic!("Failed to create window: {:?}", e),
+            Response::Ok(OkResponse::WindowCreated(w), _) => w,
+            Response::Err(e, _) => panic!("Failed to create window: {:?}", e),
             _ => panic!("Unexpected response, {:#?}", resp),
         };
 
@@ -116,7 +330,10 @@ impl Window {
             id, 0, 0, width, height,
         )))
         .expect("Failed to send clear window request");
-        assert!(matches!(results, Response::Ok(_)), "Failed to clear window");
+        assert!(
+            matches!(results, Response::Ok(..)),
+            "Failed to clear window"
+        );
         window
     }
 }
@@ -0,0 +1,115 @@
+//! The background drawn behind every window, changed using [`crate::com::request::SetBackground`]
+
+use bincode::{Decode, Encode};
+
+/// The maximum length in bytes of the path of a background image
+pub const MAX_IMAGE_PATH_LEN: usize = 192;
+
+/// How a background image is fit to the screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Encode, Decode)]
+#[repr(u32)]
+pub enum ImageFit {
+    /// Centered at it's original size, cropped if it is larger than the screen
+    Center,
+    /// Repeated at it's original size starting at the top-left corner of the screen
+    Tile,
+    /// Stretched to the size of the screen, ignoring it's aspect ratio
+    Stretch,
+    /// Scaled keeping it's aspect ratio until it covers the whole screen, cropped at the edges that don't fit
+    Fill,
+}
+
+impl ImageFit {
+    pub const ALL: [ImageFit; 4] = [Self::Center, Self::Tile, Self::Stretch, Self::Fill];
+
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Center => "center",
+            Self::Tile => "tile",
+            Self::Stretch => "stretch",
+            Self::Fill => "fill",
+        }
+    }
+
+    /// Returns the image fit with the name `name` if any, see [`Self::name`]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|fit| fit.name() == name)
+    }
+}
+
+/// The direction of a background gradient
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Encode, Decode)]
+#[repr(u32)]
+pub enum GradientDirection {
+    /// From the top edge of the screen to the bottom edge
+    Vertical,
+    /// From the left edge of the screen to the right edge
+    Horizontal,
+}
+
+impl GradientDirection {
+    pub const ALL: [GradientDirection; 2] = [Self::Vertical, Self::Horizontal];
+
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Vertical => "vertical",
+            Self::Horizontal => "horizontal",
+        }
+    }
+
+    /// Returns the gradient direction with the name `name` if any, see [`Self::name`]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|direction| direction.name() == name)
+    }
+}
+
+/// A description of the background drawn behind every window, colors are in the format `0xRRGGBB`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub enum Background {
+    /// A single color
+    Solid(u32),
+    /// A gradient from the color `from` to the color `to`
+    Gradient {
+        from: u32,
+        to: u32,
+        direction: GradientDirection,
+    },
+    /// An image the WM loads from a path, see [`Background::image`]
+    Image {
+        fit: ImageFit,
+        path_len: u16,
+        path: [u8; MAX_IMAGE_PATH_LEN],
+    },
+}
+
+impl Background {
+    /// Returns an image background loaded from the path `path`,
+    /// or `None` if the path is longer than [`MAX_IMAGE_PATH_LEN`] bytes.
+    pub fn image(path: &str, fit: ImageFit) -> Option<Self> {
+        let bytes = path.as_bytes();
+        if bytes.len() > MAX_IMAGE_PATH_LEN {
+            return None;
+        }
+
+        let mut path = [0u8; MAX_IMAGE_PATH_LEN];
+        path[..bytes.len()].copy_from_slice(bytes);
+        Some(Self::Image {
+            fit,
+            path_len: bytes.len() as u16,
+            path,
+        })
+    }
+
+    /// Returns the path of an image background, or `None` if the background isn't an image or the path isn't valid UTF-8
+    pub fn image_path(&self) -> Option<&str> {
+        match self {
+            Self::Image { path_len, path, .. } => {
+                let path = path.get(..*path_len as usize)?;
+                str::from_utf8(path).ok()
+            }
+            _ => None,
+        }
+    }
+}
@@ -7,12 +7,59 @@ use bincode::{
     config::{Fixint, Limit, LittleEndian},
     error::DecodeError,
 };
+use bitflags::bitflags;
 
 use crate::com::{request::ReqMagicNumInner, response::Response};
 
 /// The max size of a packet that can be transferred to and from the WM
 pub const MAX_PACKET_SIZE: usize = 256;
 
+/// The version of the protocol defined by this crate, bumped on every change to the layout of the packets,
+/// negotiated with [`crate::com::request::Hello`].
+///
+/// Version 0 is any client that doesn't start with a [`crate::com::request::Hello`], which predates the negotiation.
+pub const PROTOCOL_VERSION: u32 = 4;
+/// The oldest protocol version the WM still speaks
+pub const MIN_PROTOCOL_VERSION: u32 = 4;
+
+/// The ID the WM gives a window when it is created, see [`crate::com::response::CreateWindowResp`]
+pub type WinID = u16;
+
+/// A number chosen by the client for each request, echoed back in the response to that request,
+/// so that responses can be matched with their requests no matter which order they arrive in.
+///
+/// Responses to requests the WM couldn't even decode the serial of carry [`NO_SERIAL`] instead
+/// (see [`crate::com::request::Request::decode_serial`]), so clients should never use it.
+pub type Serial = u32;
+/// The serial of responses to requests whose serial couldn't be decoded, see [`Serial`]
+pub const NO_SERIAL: Serial = 0;
+
+bitflags! {
+    /// Optional features of the protocol, a client asks for the features it uses in [`crate::com::request::Hello`]
+    /// and the WM answers with the ones it agrees to in [`crate::com::response::WelcomeResp`],
+    /// requests for the features it didn't agree to are rejected, see [`crate::com::request::RequestKind::required_capabilities`].
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Capabilities: u32 {
+        /// [`crate::com::request::RequestFrameCallback`] and the frame done events
+        const FRAME_CALLBACKS = 1 << 0;
+        /// [`crate::com::request::CaptureScreen`] and [`crate::com::request::CaptureWindow`]
+        const CAPTURE = 1 << 1;
+        /// [`crate::com::request::SetCursor`]
+        const CURSOR_SHAPES = 1 << 2;
+        /// [`crate::com::request::SetBackground`], which also requires the privilege key
+        const SET_BACKGROUND = 1 << 3;
+    }
+}
+
+/// Returns an error if the WM doesn't speak the protocol version `version`
+pub const fn check_version(version: u32) -> Result<(), PacketParseErr> {
+    if version >= MIN_PROTOCOL_VERSION && version <= PROTOCOL_VERSION {
+        Ok(())
+    } else {
+        Err(PacketParseErr::UnsupportedVersion(version))
+    }
+}
+
 pub(crate) const BINCODE_CONFIG: bincode::config::Configuration<
     LittleEndian,
     Fixint,
@@ -28,6 +75,8 @@ pub enum PacketParseErr {
     InvalidPacketKind,
     InvalidPacketSize,
     InvalidPacketData,
+    /// The packet was sent by a peer that speaks the protocol version inside, which is not supported, see [`check_version`]
+    UnsupportedVersion(u32),
 }
 
 impl Display for PacketParseErr {
@@ -1,6 +1,57 @@
 use bincode::{Decode, Encode};
+use bitflags::bitflags;
 
-use crate::com::packet::{BINCODE_CONFIG, MAX_PACKET_SIZE, PacketParseErr};
+use crate::{
+    background::Background,
+    com::packet::{
+        BINCODE_CONFIG, Capabilities, MAX_PACKET_SIZE, NO_SERIAL, PacketParseErr, Serial,
+    },
+    cursor::CursorShape,
+    fb::AlphaFormat,
+};
+
+bitflags! {
+    /// Flags that control how a Window is created, see [`CreateWindow`]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct WindowFlags: u32 {
+        /// Don't draw the WM's decorations (title bar, buttons and border) around the Window,
+        /// the Window can no longer be dragged by the user.
+        const NO_DECORATIONS = 1 << 0;
+        /// The Window's pixels use premultiplied alpha instead of straight alpha, see [`AlphaFormat`]
+        const PREMULTIPLIED_ALPHA = 1 << 1;
+    }
+}
+
+/// The first Request a client sends, negotiates the protocol version and the optional features the client uses,
+/// responded to with [`crate::com::response::OkResponse::Welcome`].
+///
+/// The WM refuses any other request before this one, and refuses clients that speak a protocol version it doesn't support
+/// with [`crate::com::response::error::ResponseError::UnsupportedVersion`].
+#[derive(Debug, Clone, Copy, Encode, Decode)]
+#[repr(C)]
+pub struct Hello {
+    protocol_version: u32,
+    capabilities: u32,
+}
+
+impl Hello {
+    /// Constructs a new [`Hello`] Request, `protocol_version` is usually [`crate::com::packet::PROTOCOL_VERSION`]
+    pub const fn new(protocol_version: u32, capabilities: Capabilities) -> Self {
+        Self {
+            protocol_version,
+            capabilities: capabilities.bits(),
+        }
+    }
+
+    pub const fn protocol_version(&self) -> u32 {
+        self.protocol_version
+    }
+
+    /// The optional features the client asks for
+    pub const fn capabilities(&self) -> Capabilities {
+        Capabilities::from_bits_retain(self.capabilities)
+    }
+}
 
 /// A Request to ask the WM to Create a new Window
 #[derive(Debug, Clone, Copy, Encode, Decode)]
@@ -15,9 +66,9 @@ pub struct CreateWindow {
 
 impl CreateWindow {
     /// Constructs a new [`CreateWindow`] Request
-    pub const fn new(flags: u32, width: u32, height: u32, x: u32, y: u32) -> Self {
+    pub const fn new(flags: WindowFlags, width: u32, height: u32, x: u32, y: u32) -> Self {
         Self {
-            flags,
+            flags: flags.bits(),
             width,
             height,
             x,
@@ -25,6 +76,19 @@ impl CreateWindow {
         }
     }
 
+    pub const fn flags(&self) -> WindowFlags {
+        WindowFlags::from_bits_retain(self.flags)
+    }
+
+    /// Returns the alpha format of the Window's pixels, see [`WindowFlags::PREMULTIPLIED_ALPHA`]
+    pub const fn alpha_format(&self) -> AlphaFormat {
+        if self.flags().contains(WindowFlags::PREMULTIPLIED_ALPHA) {
+            AlphaFormat::Premultiplied
+        } else {
+            AlphaFormat::Straight
+        }
+    }
+
     pub const fn x(&self) -> u32 {
         self.x
     }
@@ -92,16 +156,429 @@ impl DamageWindow {
     }
 }
 
+/// A Request to ask the WM to resize a Window, this reallocates the Window's pixels,
+/// the new pixels can be accessed using the shared memory key in the response.
+#[derive(Debug, Clone, Copy, Encode, Decode)]
+#[repr(C)]
+pub struct ResizeWindow {
+    /// The new width of the Window
+    width: u32,
+    /// The new height of the Window
+    height: u32,
+    /// The ID of the target Window
+    win_id: u16,
+    __0: u16,
+}
+
+impl ResizeWindow {
+    pub const fn new(win_id: u16, width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            win_id,
+            __0: 0,
+        }
+    }
+
+    pub const fn win_id(&self) -> u16 {
+        self.win_id
+    }
+
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+}
+
+/// A Request to ask the WM to move a Window to a new position on the screen.
+#[derive(Debug, Clone, Copy, Encode, Decode)]
+#[repr(C)]
+pub struct MoveWindow {
+    /// The new X Position of the Window
+    x: u32,
+    /// The new Y Position of the Window
+    y: u32,
+    /// The ID of the target Window
+    win_id: u16,
+    __0: u16,
+}
+
+impl MoveWindow {
+    pub const fn new(win_id: u16, x: u32, y: u32) -> Self {
+        Self {
+            x,
+            y,
+            win_id,
+            __0: 0,
+        }
+    }
+
+    pub const fn win_id(&self) -> u16 {
+        self.win_id
+    }
+
+    pub const fn x(&self) -> u32 {
+        self.x
+    }
+
+    pub const fn y(&self) -> u32 {
+        self.y
+    }
+}
+
+/// A Request to ask the WM to destroy a Window, the Window's ID is no longer valid afterwards.
+#[derive(Debug, Clone, Copy, Encode, Decode)]
+#[repr(C)]
+pub struct DestroyWindow {
+    /// The ID of the target Window
+    win_id: u16,
+}
+
+impl DestroyWindow {
+    pub const fn new(win_id: u16) -> Self {
+        Self { win_id }
+    }
+
+    pub const fn win_id(&self) -> u16 {
+        self.win_id
+    }
+}
+
+/// A Request to acknowledge a [`crate::com::response::event::ConfigureEvent`], telling the WM the client is ready for the configured size.
+///
+/// The WM only resizes the Window once the most recent configure event is acknowledged,
+/// and responds with [`crate::com::response::OkResponse::WindowResized`] in that case,
+/// acknowledging an older configure event does nothing and is responded to with [`crate::com::response::OkResponse::Success`].
+#[derive(Debug, Clone, Copy, Encode, Decode)]
+#[repr(C)]
+pub struct AckConfigure {
+    /// The serial of the acknowledged configure event
+    serial: u32,
+    /// The ID of the target Window
+    win_id: u16,
+    __0: u16,
+}
+
+impl AckConfigure {
+    pub const fn new(win_id: u16, serial: u32) -> Self {
+        Self {
+            serial,
+            win_id,
+            __0: 0,
+        }
+    }
+
+    pub const fn win_id(&self) -> u16 {
+        self.win_id
+    }
+
+    pub const fn serial(&self) -> u32 {
+        self.serial
+    }
+}
+
+/// A Request to set the shape of the mouse cursor while it is over a Window's pixels.
+#[derive(Debug, Clone, Copy, Encode, Decode)]
+#[repr(C)]
+pub struct SetCursor {
+    shape: CursorShape,
+    /// The ID of the target Window
+    win_id: u16,
+    __0: u16,
+}
+
+impl SetCursor {
+    pub const fn new(win_id: u16, shape: CursorShape) -> Self {
+        Self {
+            shape,
+            win_id,
+            __0: 0,
+        }
+    }
+
+    pub const fn win_id(&self) -> u16 {
+        self.win_id
+    }
+
+    pub const fn shape(&self) -> CursorShape {
+        self.shape
+    }
+}
+
+/// A Request to tell the WM which part of a Window's pixels is fully opaque (every pixel has an alpha of 0xFF),
+/// the WM doesn't draw what is behind that part, the client must keep the promise or whatever was drawn there before will show through.
+///
+/// An empty rectangle (a width or a height of 0) removes the hint, the WM then checks the Window's pixels instead
+/// and only treats the Window as opaque if every one of it's pixels is.
+#[derive(Debug, Clone, Copy, Encode, Decode)]
+#[repr(C)]
+pub struct SetOpaqueRegion {
+    /// X Position of the opaque rectangle within the Window
+    x: u32,
+    /// Y Position of the opaque rectangle within the Window
+    y: u32,
+    /// Width of the opaque rectangle
+    width: u32,
+    /// Height of the opaque rectangle
+    height: u32,
+    /// The ID of the target Window
+    win_id: u16,
+    __0: u16,
+}
+
+impl SetOpaqueRegion {
+    pub const fn new(win_id: u16, x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+            win_id,
+            __0: 0,
+        }
+    }
+
+    pub const fn win_id(&self) -> u16 {
+        self.win_id
+    }
+
+    pub const fn x(&self) -> u32 {
+        self.x
+    }
+
+    pub const fn y(&self) -> u32 {
+        self.y
+    }
+
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+}
+
+/// A Request to be notified with a [`crate::com::response::event::Event::FrameDone`] event
+/// once the next frame containing the Window is presented, the callback is only fired once.
+///
+/// Clients can use this to pace their animations to the WM's refresh rate instead of drawing as fast as possible.
+#[derive(Debug, Clone, Copy, Encode, Decode)]
+#[repr(C)]
+pub struct RequestFrameCallback {
+    /// The ID of the target Window
+    win_id: u16,
+}
+
+impl RequestFrameCallback {
+    pub const fn new(win_id: u16) -> Self {
+        Self { win_id }
+    }
+
+    pub const fn win_id(&self) -> u16 {
+        self.win_id
+    }
+}
+
+/// A privileged Request to capture the composited pixels of the whole screen,
+/// responded to with [`crate::com::response::OkResponse::Captured`] holding a new shared memory buffer with the pixels.
+///
+/// `key` must be the privilege key the WM passed to the client's process in the [`crate::PRIVILEGE_KEY_ENV`] environment variable,
+/// otherwise the request fails with [`crate::com::response::error::ResponseError::PermissionDenied`].
+#[derive(Debug, Clone, Copy, Encode, Decode)]
+#[repr(C)]
+pub struct CaptureScreen {
+    key: u64,
+}
+
+impl CaptureScreen {
+    pub const fn new(key: u64) -> Self {
+        Self { key }
+    }
+
+    pub const fn key(&self) -> u64 {
+        self.key
+    }
+}
+
+/// A Request to capture the pixels of a Window (including the decorations around it),
+/// as if nothing else was on the screen, see [`CaptureScreen`].
+///
+/// Capturing a Window owned by another client is privileged, `key` must then be the privilege key
+/// the WM passed to the client's process in the [`crate::PRIVILEGE_KEY_ENV`] environment variable,
+/// otherwise the request fails with [`crate::com::response::error::ResponseError::PermissionDenied`].
+#[derive(Debug, Clone, Copy, Encode, Decode)]
+#[repr(C)]
+pub struct CaptureWindow {
+    key: u64,
+    /// The ID of the target Window
+    win_id: u16,
+}
+
+impl CaptureWindow {
+    pub const fn new(key: u64, win_id: u16) -> Self {
+        Self { key, win_id }
+    }
+
+    pub const fn key(&self) -> u64 {
+        self.key
+    }
+
+    pub const fn win_id(&self) -> u16 {
+        self.win_id
+    }
+}
+
+/// A privileged Request to change the background drawn behind every window, see [`Background`].
+///
+/// `key` must be the privilege key the WM passed to the client's process in the [`crate::PRIVILEGE_KEY_ENV`] environment variable,
+/// otherwise the request fails with [`crate::com::response::error::ResponseError::PermissionDenied`].
+/// Fails with [`crate::com::response::error::ResponseError::InvalidData`] if the background image couldn't be loaded.
+#[derive(Debug, Clone, Copy, Encode, Decode)]
+#[repr(C)]
+pub struct SetBackground {
+    key: u64,
+    background: Background,
+}
+
+impl SetBackground {
+    pub const fn new(key: u64, background: Background) -> Self {
+        Self { key, background }
+    }
+
+    pub const fn key(&self) -> u64 {
+        self.key
+    }
+
+    pub const fn background(&self) -> &Background {
+        &self.background
+    }
+}
+
+/// The maximum amount of bytes a single [`WindowTextChunk`] can carry.
+pub const TEXT_CHUNK_SIZE: usize = 192;
+
+/// The maximum length in bytes of a text set using [`WindowTextChunk`]s.
+pub const MAX_WINDOW_TEXT_LEN: usize = 4096;
+
+/// A chunk of a UTF-8 text property of a Window (such as the title), sent with [`RequestKind::SetWindowTitle`] or [`RequestKind::SetAppId`].
+///
+/// Texts don't fit in a single packet, so they are sent in chunks of at most [`TEXT_CHUNK_SIZE`] bytes in order,
+/// the WM applies the text once the last chunk is received, use [`WindowTextChunk::chunks`] to split a text into chunks.
+#[derive(Debug, Clone, Copy, Encode, Decode)]
+#[repr(C)]
+pub struct WindowTextChunk {
+    /// The length in bytes of the whole text
+    total_len: u32,
+    /// The offset in bytes of this chunk within the whole text
+    offset: u32,
+    /// The amount of bytes in `bytes` that are a part of the text
+    len: u16,
+    /// The ID of the target Window
+    win_id: u16,
+    bytes: [u8; TEXT_CHUNK_SIZE],
+}
+
+impl WindowTextChunk {
+    /// Splits `text` into chunks to send to the WM in order, there is always at least a single chunk even if the text is empty.
+    pub fn chunks(win_id: u16, text: &str) -> impl Iterator<Item = WindowTextChunk> {
+        let bytes = text.as_bytes();
+        let total_len = bytes.len() as u32;
+        let chunks_count = bytes.len().div_ceil(TEXT_CHUNK_SIZE).max(1);
+
+        (0..chunks_count).map(move |i| {
+            let offset = i * TEXT_CHUNK_SIZE;
+            let chunk = &bytes[offset..(offset + TEXT_CHUNK_SIZE).min(bytes.len())];
+
+            let mut chunk_bytes = [0u8; TEXT_CHUNK_SIZE];
+            chunk_bytes[..chunk.len()].copy_from_slice(chunk);
+
+            WindowTextChunk {
+                total_len,
+                offset: offset as u32,
+                len: chunk.len() as u16,
+                win_id,
+                bytes: chunk_bytes,
+            }
+        })
+    }
+
+    pub const fn win_id(&self) -> u16 {
+        self.win_id
+    }
+
+    pub const fn total_len(&self) -> u32 {
+        self.total_len
+    }
+
+    pub const fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    /// Returns the bytes of the text this chunk carries
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes[..(self.len as usize).min(TEXT_CHUNK_SIZE)]
+    }
+
+    /// Returns true if this is the last chunk of the text
+    pub const fn is_last(&self) -> bool {
+        self.offset as usize + self.len as usize >= self.total_len as usize
+    }
+}
+
 /// The kind of request sent to the WM from a client
 #[derive(Debug, Encode, Decode)]
 #[repr(u32)]
 pub enum RequestKind {
+    /// See [`Hello`], must stay the first variant in every protocol version so that the WM can always decode it
+    Hello(Hello),
     /// A request to ping the WM (ensures the connection is alive)
     Ping,
     /// See [`CreateWindow`]
     CreateWindow(CreateWindow),
     /// See [`DamageWindow`]
     DamageWindow(DamageWindow),
+    /// See [`ResizeWindow`]
+    ResizeWindow(ResizeWindow),
+    /// See [`MoveWindow`]
+    MoveWindow(MoveWindow),
+    /// See [`DestroyWindow`]
+    DestroyWindow(DestroyWindow),
+    /// Sets the title of a Window, displayed in the title bar, see [`WindowTextChunk`]
+    SetWindowTitle(WindowTextChunk),
+    /// Sets the application ID of a Window, which identifies the application that created it (for example `org.safaos.hello`), see [`WindowTextChunk`]
+    SetAppId(WindowTextChunk),
+    /// See [`AckConfigure`]
+    AckConfigure(AckConfigure),
+    /// See [`SetCursor`]
+    SetCursor(SetCursor),
+    /// See [`RequestFrameCallback`]
+    RequestFrameCallback(RequestFrameCallback),
+    /// See [`SetOpaqueRegion`]
+    SetOpaqueRegion(SetOpaqueRegion),
+    /// See [`CaptureScreen`]
+    CaptureScreen(CaptureScreen),
+    /// See [`CaptureWindow`], responded to like [`CaptureScreen`]
+    CaptureWindow(CaptureWindow),
+    /// See [`SetBackground`]
+    SetBackground(SetBackground),
+}
+
+impl RequestKind {
+    /// Returns the capabilities the WM must have agreed to for it to serve this request,
+    /// the WM responds to the request with [`crate::com::response::error::ResponseError::MissingCapability`] otherwise.
+    pub const fn required_capabilities(&self) -> Capabilities {
+        match self {
+            Self::RequestFrameCallback(_) => Capabilities::FRAME_CALLBACKS,
+            Self::CaptureScreen(_) | Self::CaptureWindow(_) => Capabilities::CAPTURE,
+            Self::SetCursor(_) => Capabilities::CURSOR_SHAPES,
+            Self::SetBackground(_) => Capabilities::SET_BACKGROUND,
+            _ => Capabilities::empty(),
+        }
+    }
 }
 
 #[derive(Encode, Decode, Clone, Copy, Debug)]
@@ -116,14 +593,18 @@ pub(crate) enum ReqMagicNumInner {
 pub struct Request {
     magic: ReqMagicNumInner,
     kind: RequestKind,
+    /// Comes last so that the layout of a [`Hello`] request starts the same in every protocol version,
+    /// and so that it can be read from the end of a packet whose kind can't be decoded, see [`Request::decode_serial`]
+    serial: Serial,
 }
 
 impl Request {
-    /// Constructs a new Request with the given kind.
-    pub const fn new(kind: RequestKind) -> Self {
+    /// Constructs a new Request with the given kind, the response to it carries `serial`, see [`Serial`]
+    pub const fn new(serial: Serial, kind: RequestKind) -> Self {
         Self {
             magic: ReqMagicNumInner::RequestMagic,
             kind,
+            serial,
         }
     }
 
@@ -131,6 +612,10 @@ impl Request {
         &self.kind
     }
 
+    pub const fn serial(&self) -> Serial {
+        self.serial
+    }
+
     /// Encodes the Request into a byte array and returns the length of the encoded data.
     pub fn encode(self) -> ([u8; MAX_PACKET_SIZE], usize) {
         let mut dst = [0u8; MAX_PACKET_SIZE];
@@ -143,4 +628,62 @@ impl Request {
     pub fn decode(data: &[u8]) -> Result<Self, PacketParseErr> {
         Ok((bincode::decode_from_slice(data, BINCODE_CONFIG)?).0)
     }
+
+    /// Returns the serial of the encoded request `data` without decoding its kind,
+    /// so that a request that fails to [`Self::decode`] can still be responded to with its serial.
+    ///
+    /// Returns [`NO_SERIAL`] if `data` doesn't start with the request magic or is too short to hold a kind and a serial.
+    pub fn decode_serial(data: &[u8]) -> Serial {
+        // The magic, the kind's discriminant and the serial
+        const MIN_LEN: usize = size_of::<u32>() * 2 + size_of::<Serial>();
+
+        if data.len() < MIN_LEN
+            || bincode::decode_from_slice::<ReqMagicNumInner, _>(data, BINCODE_CONFIG).is_err()
+        {
+            return NO_SERIAL;
+        }
+
+        data.last_chunk()
+            .copied()
+            .map_or(NO_SERIAL, Serial::from_le_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_serial_matches_decode() {
+        let (bytes, len) =
+            Request::new(42, RequestKind::MoveWindow(MoveWindow::new(3, 10, 20))).encode();
+
+        assert_eq!(Request::decode(&bytes[..len]).unwrap().serial(), 42);
+        assert_eq!(Request::decode_serial(&bytes[..len]), 42);
+    }
+
+    #[test]
+    fn decode_serial_of_an_unknown_kind() {
+        let (mut bytes, len) = Request::new(7, RequestKind::Ping).encode();
+        // The kind's discriminant follows the magic
+        bytes[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        assert!(matches!(
+            Request::decode(&bytes[..len]),
+            Err(PacketParseErr::InvalidPacketKind)
+        ));
+        assert_eq!(Request::decode_serial(&bytes[..len]), 7);
+    }
+
+    #[test]
+    fn decode_serial_without_a_header() {
+        let (mut bytes, len) = Request::new(7, RequestKind::Ping).encode();
+
+        assert_eq!(
+            Request::decode_serial(&bytes[..len - 1 - size_of::<u32>()]),
+            NO_SERIAL
+        );
+        bytes[..4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(Request::decode_serial(&bytes[..len]), NO_SERIAL);
+    }
 }
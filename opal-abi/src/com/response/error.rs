@@ -1,6 +1,6 @@
 use bincode::{Decode, Encode};
 
-use crate::com::packet::PacketParseErr;
+use crate::com::packet::{MIN_PROTOCOL_VERSION, PROTOCOL_VERSION, PacketParseErr};
 
 /// A Response Error
 #[derive(Debug, Clone, Copy, Encode, Decode, PartialEq, Eq)]
@@ -12,6 +12,17 @@ pub enum ResponseError {
     InvalidData,
     UnknownFatalError,
     UnknownWindow,
+    /// The client tried to operate on a window it doesn't own, or sent a privileged request without the privilege key
+    PermissionDenied,
+    /// The client speaks a protocol version the WM doesn't support, the WM only speaks the versions from `min` to `max` (inclusive),
+    /// the WM disconnects the client after sending this error.
+    UnsupportedVersion {
+        min: u32,
+        max: u32,
+    },
+    /// The client sent a request for an optional feature the WM didn't agree to when greeting it,
+    /// see [`crate::com::request::RequestKind::required_capabilities`]
+    MissingCapability,
 }
 
 impl From<PacketParseErr> for ResponseError {
@@ -21,6 +32,10 @@ impl From<PacketParseErr> for ResponseError {
             PacketParseErr::InvalidPacketSize => ResponseError::PacketTooShort,
             PacketParseErr::InvalidPacketKind => ResponseError::InvalidRequestKind,
             PacketParseErr::InvalidPacketData => ResponseError::InvalidData,
+            PacketParseErr::UnsupportedVersion(_) => ResponseError::UnsupportedVersion {
+                min: MIN_PROTOCOL_VERSION,
+                max: PROTOCOL_VERSION,
+            },
         }
     }
 }
@@ -1,6 +1,8 @@
 use bincode::{Decode, Encode};
 use bitflags::bitflags;
 
+use crate::com::packet::WinID;
+
 /// When the mouse cursor enters a window.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
 #[repr(C)]
@@ -46,6 +48,14 @@ bitflags! {
         const LEFT = 1 << 0;
         const MIDDLE = 1 << 1;
         const RIGHT = 1 << 2;
+        /// The back side button, usually navigates back.
+        ///
+        /// Not reported yet, the mouse driver doesn't tell the side buttons apart.
+        const BACK = 1 << 3;
+        /// The forward side button, usually navigates forward.
+        ///
+        /// Not reported yet, the mouse driver doesn't tell the side buttons apart.
+        const FORWARD = 1 << 4;
     }
 }
 
@@ -130,6 +140,233 @@ impl MouseChangeEvent {
     }
 }
 
+/// When the mouse wheel is scrolled while the cursor is over a window.
+///
+/// Not sent yet, the mouse driver doesn't report the wheel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+#[repr(C)]
+pub struct MouseScrollEvent {
+    /// The amount of notches scrolled horizontally, positive values scroll to the right.
+    delta_x: i32,
+    /// The amount of notches scrolled vertically, positive values scroll down.
+    delta_y: i32,
+    /// The x-coordinate of the mouse cursor, relative to the window.
+    pos_x: u32,
+    /// The y-coordinate of the mouse cursor, relative to the window.
+    pos_y: u32,
+}
+
+impl MouseScrollEvent {
+    /// Creates a new `MouseScrollEvent`.
+    pub fn new(delta_x: i32, delta_y: i32, pos_x: u32, pos_y: u32) -> Self {
+        Self {
+            delta_x,
+            delta_y,
+            pos_x,
+            pos_y,
+        }
+    }
+
+    /// Returns the amount of notches scrolled horizontally, positive values scroll to the right.
+    pub const fn delta_x(&self) -> i32 {
+        self.delta_x
+    }
+
+    /// Returns the amount of notches scrolled vertically, positive values scroll down.
+    pub const fn delta_y(&self) -> i32 {
+        self.delta_y
+    }
+
+    /// Returns the x-coordinate of the mouse cursor, relative to the window.
+    pub const fn x(&self) -> u32 {
+        self.pos_x
+    }
+
+    /// Returns the y-coordinate of the mouse cursor, relative to the window.
+    pub const fn y(&self) -> u32 {
+        self.pos_y
+    }
+}
+
+bitflags! {
+    /// The modifier keys that are held (or toggled on in case of locks) while a key event occurred.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct KeyModifiers: u8 {
+        const SHIFT = 1 << 0;
+        const CTRL = 1 << 1;
+        const ALT = 1 << 2;
+        const SUPER = 1 << 3;
+        const CAPS_LOCK = 1 << 4;
+        const NUM_LOCK = 1 << 5;
+    }
+}
+
+impl Encode for KeyModifiers {
+    fn encode<E: bincode::enc::Encoder>(
+        &self,
+        encoder: &mut E,
+    ) -> Result<(), bincode::error::EncodeError> {
+        u8::encode(&self.bits(), encoder)
+    }
+}
+
+impl<Context> Decode<Context> for KeyModifiers {
+    fn decode<D: bincode::de::Decoder<Context = Context>>(
+        decoder: &mut D,
+    ) -> Result<Self, bincode::error::DecodeError> {
+        u8::decode(decoder).map(KeyModifiers::from_bits_retain)
+    }
+}
+
+bincode::impl_borrow_decode!(KeyModifiers);
+
+/// When a key is pressed or released while the window is focused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+#[repr(C)]
+pub struct KeyEvent {
+    /// The raw scancode of the key as reported by the keyboard device.
+    scancode: u32,
+    /// The layout independent symbol of the key, see [`crate::keysym`].
+    keysym: u32,
+    /// The modifiers held while the event occurred.
+    modifiers: KeyModifiers,
+    /// The length of the UTF-8 encoded text in `text`, 0 if the key produces no text.
+    text_len: u8,
+    __: u16,
+    /// The UTF-8 encoded text produced by the key press.
+    text: [u8; 4],
+}
+
+impl KeyEvent {
+    /// Creates a new `KeyEvent`, `text` is the character the key produces (if any).
+    pub fn new(scancode: u32, keysym: u32, modifiers: KeyModifiers, text: Option<char>) -> Self {
+        let mut text_bytes = [0u8; 4];
+        let text_len = text.map_or(0, |c| c.encode_utf8(&mut text_bytes).len()) as u8;
+
+        Self {
+            scancode,
+            keysym,
+            modifiers,
+            text_len,
+            __: 0,
+            text: text_bytes,
+        }
+    }
+
+    /// Returns the raw scancode of the key.
+    pub const fn scancode(&self) -> u32 {
+        self.scancode
+    }
+
+    /// Returns the layout independent symbol of the key, see [`crate::keysym`].
+    pub const fn keysym(&self) -> u32 {
+        self.keysym
+    }
+
+    /// Returns the modifiers held while the event occurred.
+    pub const fn modifiers(&self) -> KeyModifiers {
+        self.modifiers
+    }
+
+    /// Returns the text the key produced if any.
+    pub fn text(&self) -> Option<&str> {
+        let len = (self.text_len as usize).min(self.text.len());
+        if len == 0 {
+            return None;
+        }
+
+        str::from_utf8(&self.text[..len]).ok()
+    }
+}
+
+/// When the window is resized, sent after the WM has reallocated the window's pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+#[repr(C)]
+pub struct WindowResizedEvent {
+    /// The new width of the window.
+    width: u32,
+    /// The new height of the window.
+    height: u32,
+}
+
+impl WindowResizedEvent {
+    /// Creates a new `WindowResizedEvent`.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { width, height }
+    }
+
+    /// Returns the new width of the window.
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Returns the new height of the window.
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+}
+
+/// When the WM wants the window to be resized (for example while the user is dragging the window's border).
+///
+/// The window is not resized until the client acknowledges the event using [`crate::com::request::AckConfigure`],
+/// so the client can get ready to draw at the new size first, only the most recent configure event needs to be acknowledged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+#[repr(C)]
+pub struct ConfigureEvent {
+    /// Identifies this configure event, sent back when acknowledging it.
+    serial: u32,
+    /// The suggested width of the window.
+    width: u32,
+    /// The suggested height of the window.
+    height: u32,
+}
+
+impl ConfigureEvent {
+    /// Creates a new `ConfigureEvent`.
+    pub fn new(serial: u32, width: u32, height: u32) -> Self {
+        Self {
+            serial,
+            width,
+            height,
+        }
+    }
+
+    /// Returns the serial to acknowledge this event with.
+    pub const fn serial(&self) -> u32 {
+        self.serial
+    }
+
+    /// Returns the suggested width of the window.
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Returns the suggested height of the window.
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+}
+
+/// When a frame containing the window was presented, sent once for each [`crate::com::request::RequestFrameCallback`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+#[repr(C)]
+pub struct FrameDoneEvent {
+    /// The time the frame was presented at, in milliseconds since the WM started.
+    timestamp: u64,
+}
+
+impl FrameDoneEvent {
+    /// Creates a new `FrameDoneEvent`.
+    pub fn new(timestamp: u64) -> Self {
+        Self { timestamp }
+    }
+
+    /// Returns the time the frame was presented at, in milliseconds since the WM started.
+    pub const fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+}
+
 /// Represents an event that occurred on a window.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
 #[repr(u32)]
@@ -139,4 +376,57 @@ pub enum Event {
     MouseEnter(MouseEnterEvent),
     WindowFocused,
     WindowUnfocused,
+    KeyPress(KeyEvent),
+    KeyRelease(KeyEvent),
+    WindowResized(WindowResizedEvent),
+    /// The user asked to close the window using the close button, the window is not closed by the WM.
+    CloseRequested,
+    /// The window was minimized by the user, it is no longer displayed until it is restored.
+    WindowMinimized,
+    /// The window was restored after being minimized.
+    WindowRestored,
+    /// The WM wants the window to be resized, see [`ConfigureEvent`].
+    Configure(ConfigureEvent),
+    /// Sent to the window under the cursor, not the focused window.
+    MouseScroll(MouseScrollEvent),
+    /// A frame containing the window was presented, see [`FrameDoneEvent`].
+    FrameDone(FrameDoneEvent),
+}
+
+/// An event along with the window it is for and the time it was sent at, see [`crate::com::response::Response::Event`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+#[repr(C)]
+pub struct EventEnvelope {
+    /// The time the WM sent the event at, in milliseconds since the WM started.
+    timestamp: u64,
+    win_id: WinID,
+    __: u16,
+    event: Event,
+}
+
+impl EventEnvelope {
+    /// Creates a new `EventEnvelope`.
+    pub const fn new(event: Event, win_id: WinID, timestamp: u64) -> Self {
+        Self {
+            timestamp,
+            win_id,
+            __: 0,
+            event,
+        }
+    }
+
+    pub const fn event(&self) -> Event {
+        self.event
+    }
+
+    /// Returns the ID of the window the event is for, see [`crate::com::response::CreateWindowResp::window_id`].
+    pub const fn win_id(&self) -> WinID {
+        self.win_id
+    }
+
+    /// Returns the time the WM sent the event at, in milliseconds since the WM started,
+    /// the same clock as [`FrameDoneEvent::timestamp`].
+    pub const fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
 }
@@ -1,7 +1,7 @@
 use bincode::{Decode, Encode};
 
 use crate::com::{
-    packet::{BINCODE_CONFIG, MAX_PACKET_SIZE, PacketParseErr},
+    packet::{BINCODE_CONFIG, Capabilities, MAX_PACKET_SIZE, PacketParseErr, Serial},
     response::error::ResponseError,
 };
 /// Possible response errors.
@@ -10,6 +10,33 @@ pub mod error;
 /// The layout of the events the WM can send to the client, an event is a kind of [response](self)
 pub mod event;
 
+#[derive(Debug, Encode, Decode, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+/// Response of [`super::request::Hello`]
+pub struct WelcomeResp {
+    /// The protocol version the WM is going to speak with the client
+    protocol_version: u32,
+    capabilities: u32,
+}
+
+impl WelcomeResp {
+    pub const fn protocol_version(&self) -> u32 {
+        self.protocol_version
+    }
+
+    /// The optional features the WM agreed to, out of the ones the client asked for
+    pub const fn capabilities(&self) -> Capabilities {
+        Capabilities::from_bits_retain(self.capabilities)
+    }
+
+    pub const fn new(protocol_version: u32, capabilities: Capabilities) -> Self {
+        Self {
+            protocol_version,
+            capabilities: capabilities.bits(),
+        }
+    }
+}
+
 #[derive(Debug, Encode, Decode, Clone, Copy, PartialEq, Eq)]
 #[repr(C)]
 /// Response of [`super::request::CreateWindow`]
@@ -41,23 +68,109 @@ impl CreateWindowResp {
     }
 }
 
+#[derive(Debug, Encode, Decode, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+/// Response of [`super::request::ResizeWindow`]
+pub struct ResizeWindowResp {
+    /// The resized window's new shared memory key, the old key is no longer used by the WM.
+    shm_key: usize,
+    /// The actual new width, may be smaller than the requested width
+    width: u32,
+    /// The actual new height, may be smaller than the requested height
+    height: u32,
+}
+
+impl ResizeWindowResp {
+    pub const fn shm_key(&self) -> usize {
+        self.shm_key
+    }
+
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub const fn new(shm_key: usize, width: u32, height: u32) -> Self {
+        Self {
+            shm_key,
+            width,
+            height,
+        }
+    }
+}
+
+#[derive(Debug, Encode, Decode, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+/// Response of [`super::request::CaptureScreen`] and [`super::request::CaptureWindow`]
+pub struct CaptureResp {
+    /// The shared memory key of the captured pixels, the pixels use straight alpha and are stored row by row.
+    ///
+    /// The WM keeps the buffer alive until the client captures again or disconnects, so it should be mapped right away.
+    shm_key: usize,
+    /// The width of the captured pixels
+    width: u32,
+    /// The height of the captured pixels
+    height: u32,
+}
+
+impl CaptureResp {
+    pub const fn shm_key(&self) -> usize {
+        self.shm_key
+    }
+
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub const fn new(shm_key: usize, width: u32, height: u32) -> Self {
+        Self {
+            shm_key,
+            width,
+            height,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Encode, Decode)]
 #[repr(u32)]
 /// Represents an Ok response sent by the WM as a reply to a Request
 pub enum OkResponse {
     Success,
     WindowCreated(CreateWindowResp),
+    WindowResized(ResizeWindowResp),
+    Captured(CaptureResp),
+    /// See [`WelcomeResp`], must stay at this position in every protocol version so that clients can always decode it,
+    /// a WM that predates the negotiation responds to [`super::request::Hello`] with [`OkResponse::Success`] instead.
+    Welcome(WelcomeResp),
 }
 
 #[derive(Debug, Encode, Decode, PartialEq, Eq)]
 #[repr(u32)]
 pub enum Response {
-    Ok(OkResponse) = 0xA1E_F00D_D,
-    Err(ResponseError) = 0xBAD_F00D_D,
-    Event(event::Event) = 0x100_F00D_D,
+    /// The successful response to the request with the serial inside, see [`Serial`]
+    Ok(OkResponse, Serial) = 0xA1E_F00D_D,
+    /// The error response to the request with the serial inside, see [`Serial`]
+    Err(ResponseError, Serial) = 0xBAD_F00D_D,
+    /// An event for a window, events aren't responses to any request
+    Event(event::EventEnvelope) = 0x100_F00D_D,
 }
 
 impl Response {
+    /// Returns the serial of the request this is the response to, or `None` if this is an event
+    pub const fn serial(&self) -> Option<Serial> {
+        match self {
+            Self::Ok(_, serial) | Self::Err(_, serial) => Some(*serial),
+            Self::Event(_) => None,
+        }
+    }
+
     /// Encodes the response into a byte array, also returns the length of the encoded data.
     pub fn encode(&self) -> ([u8; MAX_PACKET_SIZE], usize) {
         let mut dst = [0u8; MAX_PACKET_SIZE];
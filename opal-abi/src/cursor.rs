@@ -0,0 +1,58 @@
+//! The shapes the mouse cursor can take, the WM draws each shape using the image of the cursor theme in use.
+
+use bincode::{Decode, Encode};
+
+/// A shape of the mouse cursor, a client can choose the shape displayed over it's window using [`crate::com::request::SetCursor`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Encode, Decode)]
+#[repr(u32)]
+pub enum CursorShape {
+    /// The default pointer
+    Arrow,
+    /// An I-beam, displayed over text that can be selected or edited
+    Text,
+    /// A pointing hand, displayed over links and buttons
+    Hand,
+    /// Displayed while the application is busy
+    Busy,
+    /// Resizing from the left or the right edge
+    ResizeHorizontal,
+    /// Resizing from the top or the bottom edge
+    ResizeVertical,
+    /// Resizing from the top-left or the bottom-right corner
+    ResizeNwSe,
+    /// Resizing from the top-right or the bottom-left corner
+    ResizeNeSw,
+}
+
+impl CursorShape {
+    /// All the cursor shapes, ordered by their discriminant
+    pub const ALL: [CursorShape; 8] = [
+        Self::Arrow,
+        Self::Text,
+        Self::Hand,
+        Self::Busy,
+        Self::ResizeHorizontal,
+        Self::ResizeVertical,
+        Self::ResizeNwSe,
+        Self::ResizeNeSw,
+    ];
+
+    /// Returns the name of the cursor shape, used to look up the shape's image in a cursor theme
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Arrow => "arrow",
+            Self::Text => "text",
+            Self::Hand => "hand",
+            Self::Busy => "busy",
+            Self::ResizeHorizontal => "resize-horizontal",
+            Self::ResizeVertical => "resize-vertical",
+            Self::ResizeNwSe => "resize-nwse",
+            Self::ResizeNeSw => "resize-nesw",
+        }
+    }
+
+    /// Returns the cursor shape with the name `name` if any, see [`Self::name`]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|shape| shape.name() == name)
+    }
+}
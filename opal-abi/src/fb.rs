@@ -1,3 +1,22 @@
+/// Returns `a * b / 255` rounded to the nearest integer, without a division.
+///
+/// Used for every alpha multiplication so that the clients and the WM always agree on the results.
+#[inline(always)]
+pub const fn mul_div_255(a: u8, b: u8) -> u8 {
+    let t = a as u16 * b as u16 + 128;
+    ((t + (t >> 8)) >> 8) as u8
+}
+
+/// How the alpha value of a pixel applies to it's color channels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AlphaFormat {
+    /// The color channels are not affected by the alpha value, a half-transparent red is `(0xFF, 0, 0, 0x7F)`
+    #[default]
+    Straight,
+    /// The color channels are already multiplied by the alpha value, a half-transparent red is `(0x7F, 0, 0, 0x7F)`
+    Premultiplied,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 /// Represents a single pixel
 #[repr(C)]
@@ -22,4 +41,14 @@ impl Pixel {
     pub const fn from_hex(argb: u32) -> Self {
         unsafe { core::mem::transmute(argb) }
     }
+
+    /// Converts a pixel with straight alpha to a pixel with premultiplied alpha, see [`AlphaFormat`]
+    pub const fn premultiplied(&self) -> Self {
+        Self {
+            blue: mul_div_255(self.blue, self.alpha),
+            green: mul_div_255(self.green, self.alpha),
+            red: mul_div_255(self.red, self.alpha),
+            alpha: self.alpha,
+        }
+    }
 }
@@ -0,0 +1,98 @@
+//! Layout independent key symbols, sent along with key events.
+//!
+//! A printable key's symbol is the unicode code point of the character it produces without any modifiers held,
+//! (for example `'a'` and not `'A'`), the rest of the keys use one of the constants defined here,
+//! which are all placed outside of the unicode range.
+
+/// A key symbol, see the [module documentation](self)
+pub type Keysym = u32;
+
+const SPECIAL_BASE: Keysym = 0x0100_0000;
+
+/// An unknown key that has no symbol
+pub const NONE: Keysym = 0;
+
+pub const ESCAPE: Keysym = SPECIAL_BASE + 1;
+pub const BACKSPACE: Keysym = SPECIAL_BASE + 2;
+pub const TAB: Keysym = SPECIAL_BASE + 3;
+pub const ENTER: Keysym = SPECIAL_BASE + 4;
+pub const DELETE: Keysym = SPECIAL_BASE + 5;
+pub const INSERT: Keysym = SPECIAL_BASE + 6;
+pub const HOME: Keysym = SPECIAL_BASE + 7;
+pub const END: Keysym = SPECIAL_BASE + 8;
+pub const PAGE_UP: Keysym = SPECIAL_BASE + 9;
+pub const PAGE_DOWN: Keysym = SPECIAL_BASE + 10;
+pub const PRINT_SCREEN: Keysym = SPECIAL_BASE + 11;
+
+pub const UP: Keysym = SPECIAL_BASE + 0x10;
+pub const DOWN: Keysym = SPECIAL_BASE + 0x11;
+pub const LEFT: Keysym = SPECIAL_BASE + 0x12;
+pub const RIGHT: Keysym = SPECIAL_BASE + 0x13;
+
+pub const LEFT_SHIFT: Keysym = SPECIAL_BASE + 0x20;
+pub const RIGHT_SHIFT: Keysym = SPECIAL_BASE + 0x21;
+pub const LEFT_CTRL: Keysym = SPECIAL_BASE + 0x22;
+pub const RIGHT_CTRL: Keysym = SPECIAL_BASE + 0x23;
+pub const LEFT_ALT: Keysym = SPECIAL_BASE + 0x24;
+pub const RIGHT_ALT: Keysym = SPECIAL_BASE + 0x25;
+pub const LEFT_SUPER: Keysym = SPECIAL_BASE + 0x26;
+pub const RIGHT_SUPER: Keysym = SPECIAL_BASE + 0x27;
+pub const CAPS_LOCK: Keysym = SPECIAL_BASE + 0x28;
+pub const NUM_LOCK: Keysym = SPECIAL_BASE + 0x29;
+pub const SCROLL_LOCK: Keysym = SPECIAL_BASE + 0x2A;
+
+/// Function keys are sequential, use `F1 + (n - 1)` to get the symbol of the function key `n`
+pub const F1: Keysym = SPECIAL_BASE + 0x30;
+pub const F12: Keysym = F1 + 11;
+
+/// Returns true if the given key symbol is a unicode code point (a printable key)
+pub const fn is_printable(keysym: Keysym) -> bool {
+    keysym != NONE && keysym < SPECIAL_BASE
+}
+
+/// The names of the non-printable keys that have one, see [`from_name`]
+const NAMES: [(&str, Keysym); 15] = [
+    ("escape", ESCAPE),
+    ("backspace", BACKSPACE),
+    ("tab", TAB),
+    ("enter", ENTER),
+    ("delete", DELETE),
+    ("insert", INSERT),
+    ("home", HOME),
+    ("end", END),
+    ("page_up", PAGE_UP),
+    ("page_down", PAGE_DOWN),
+    ("print_screen", PRINT_SCREEN),
+    ("up", UP),
+    ("down", DOWN),
+    ("left", LEFT),
+    ("right", RIGHT),
+];
+
+/// Returns the key symbol of the key named `name`, used to describe keys in text (such as key bindings in a configuration file).
+///
+/// A name is either a single printable character (the symbol of that character, for example `a` or `/`, letters are case insensitive), `space`,
+/// a function key from `f1` to `f12`, or the lowercase name of a non-printable key with words separated by `_` (for example `print_screen`).
+pub fn from_name(name: &str) -> Option<Keysym> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(c.to_ascii_lowercase() as Keysym);
+    }
+
+    if name == "space" {
+        return Some(' ' as Keysym);
+    }
+
+    if let Some(n) = name
+        .strip_prefix('f')
+        .and_then(|n| n.parse::<Keysym>().ok())
+        && (1..=12).contains(&n)
+    {
+        return Some(F1 + (n - 1));
+    }
+
+    NAMES
+        .iter()
+        .find(|(key_name, _)| *key_name == name)
+        .map(|(_, keysym)| *keysym)
+}
@@ -3,8 +3,18 @@
 /// The abstract socket address to use to connect with the OpalWM
 pub const CONNECT_ABSTRACT_ADDR: &str = "opal_wm::connect";
 
+/// The environment variable the WM passes the privilege key in to the processes it spawns,
+/// privileged requests (such as [`com::request::SetBackground`]) must carry that key.
+pub const PRIVILEGE_KEY_ENV: &str = "OPAL_PRIVILEGE_KEY";
+
 /// The communication protocol, contains he layout of packets
 /// that can be sent to and from the WM
 pub mod com;
 
+pub mod background;
+
+pub mod cursor;
+
 pub mod fb;
+
+pub mod keysym;
@@ -0,0 +1,92 @@
+use std::{
+    fs::OpenOptions,
+    io::{self, ErrorKind, Write},
+    path::{Path, PathBuf},
+    thread,
+};
+
+use crate::{
+    elog,
+    framebuffer::{self, Pixel},
+    image::bmp,
+    log,
+    shm::SharedPixels,
+};
+
+/// The directory screenshots taken using the Print Screen key are saved to
+pub const SCREENSHOTS_DIR: &str = "sys:/tmp";
+
+/// Captured pixels in shared memory, handed to a client as a response to a capture request.
+///
+/// The client maps the pixels using the shared memory key, the WM keeps them alive until the client captures again or disconnects.
+pub struct Capture {
+    width: usize,
+    height: usize,
+    pixels: SharedPixels,
+}
+
+impl Capture {
+    /// Copies the `width`*`height` `pixels` into a new shared memory buffer
+    pub fn new(width: usize, height: usize, pixels: &[Pixel]) -> Self {
+        let mut shared_pixels = SharedPixels::new(width * height, Pixel::from_hex(0));
+        shared_pixels.as_mut_slice().copy_from_slice(pixels);
+
+        Self {
+            width,
+            height,
+            pixels: shared_pixels,
+        }
+    }
+
+    pub const fn shm_key(&self) -> usize {
+        self.pixels.key()
+    }
+
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+
+    pub const fn height(&self) -> usize {
+        self.height
+    }
+}
+
+/// Returns the width and the height of the screen, and the composited pixels of the whole screen with straight alpha
+pub fn screen_pixels() -> (usize, usize, Vec<Pixel>) {
+    let fb = framebuffer::framebuffer();
+    let pixels = fb.pixels().iter().map(Pixel::unpremultiplied).collect();
+    (fb.width(), fb.height(), pixels)
+}
+
+/// Saves a screenshot of the whole screen as a BMP image in [`SCREENSHOTS_DIR`],
+/// the image is encoded and written on another thread so that the WM doesn't stall meanwhile.
+pub fn save_screenshot() {
+    let (width, height, pixels) = screen_pixels();
+
+    thread::spawn(move || {
+        let bytes = bmp::encode(width, height, &pixels);
+        match write_screenshot(&bytes) {
+            Ok(path) => log!("Saved a screenshot to {}", path.display()),
+            Err(err) => elog!("Failed to save a screenshot: {err}"),
+        }
+    });
+}
+
+/// Writes the encoded screenshot `bytes` to the first unused screenshot name in [`SCREENSHOTS_DIR`], returns the path it was written to
+fn write_screenshot(bytes: &[u8]) -> io::Result<PathBuf> {
+    let dir = Path::new(SCREENSHOTS_DIR);
+    for i in 0u32.. {
+        let path = dir.join(format!("screenshot-{i}.bmp"));
+        // Fails if the name is taken, so that two screenshots taken at once never get the same name
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                file.write_all(bytes)?;
+                return Ok(path);
+            }
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+    }
+
+    Err(io::Error::other("Ran out of screenshot names"))
+}
@@ -0,0 +1,54 @@
+use std::{
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+/// The refresh rate used when none is configured, in frames per second
+pub const DEFAULT_REFRESH_RATE: u32 = 60;
+
+/// The time the WM started at, every timestamp sent to clients is relative to it
+static STARTED: LazyLock<Instant> = LazyLock::new(Instant::now);
+
+/// Starts the clock [`timestamp`] is relative to, called as soon as the WM starts
+pub fn start() {
+    LazyLock::force(&STARTED);
+}
+
+/// Returns the current time in milliseconds since the WM started, see [`start`]
+pub fn timestamp() -> u64 {
+    STARTED.elapsed().as_millis() as u64
+}
+
+/// The compositor clock, paces redraws to a fixed refresh rate
+pub struct FrameClock {
+    frame_interval: Duration,
+    /// The time the next frame should be presented at
+    next_frame: Instant,
+}
+
+impl FrameClock {
+    /// Creates a new clock that ticks `refresh_rate` times per second, starting now
+    pub fn new(refresh_rate: u32) -> Self {
+        Self {
+            frame_interval: Duration::from_secs(1) / refresh_rate.max(1),
+            next_frame: Instant::now(),
+        }
+    }
+
+    /// Returns how long until it is time to present the next frame, zero if it is already time
+    pub fn time_until_next_frame(&self) -> Duration {
+        self.next_frame.saturating_duration_since(Instant::now())
+    }
+
+    /// Schedules the next frame after a frame was presented,
+    /// if we are running behind (or nothing was presented for a while) the missed frames are skipped instead of presenting them all at once.
+    pub fn frame_presented(&mut self) {
+        self.next_frame += self.frame_interval;
+
+        let now = Instant::now();
+        if self.next_frame <= now {
+            let behind = (now - self.next_frame).as_nanos() / self.frame_interval.as_nanos();
+            self.next_frame += self.frame_interval * (behind as u32 + 1);
+        }
+    }
+}
@@ -1,142 +1,566 @@
 use std::{
+    collections::VecDeque,
     io::ErrorKind,
     process::{Command, Stdio},
     sync::Arc,
+    thread::{self, JoinHandle},
 };
 
 use opal_abi::com::{
-    request::RequestKind,
-    response::{CreateWindowResp, OkResponse, Response, error::ResponseError},
+    packet::{self, Capabilities, PacketParseErr, Serial},
+    request::{MAX_WINDOW_TEXT_LEN, Request, RequestKind, WindowFlags, WindowTextChunk},
+    response::{
+        CaptureResp, CreateWindowResp, OkResponse, ResizeWindowResp, Response, WelcomeResp,
+        error::ResponseError,
+    },
 };
-use safa_api::sockets::{SockKind, UnixListenerBuilder, UnixSockConnection};
+use rustc_hash::FxHashMap;
+use safa_api::sockets::{SockKind, UnixListener, UnixListenerBuilder, UnixSockConnection};
 
 use crate::{
-    com::{ClientComPipe, ReadError},
+    capture::{self, Capture},
+    com::{ClientComPipe, ClientID, PRIVILEGE_KEY, ReadError},
     dlog, elog,
-    framebuffer::Pixel,
+    framebuffer::{self, Pixel},
     log, logging,
-    window::{self, WINDOWS, Window, WindowKind},
+    poll::{self, Poller, Token},
+    region::Rect,
+    wallpaper::{Wallpaper, WallpaperError},
+    window::{self, WINDOWS, WinID, Window, WindowKind},
     wlog,
 };
 
-fn spawn_hello() {
-    if let Err(err) = Command::new("sys:/bin/hello_world")
+/// Spawns a program with the console as it's standard input and output, and the privilege key in it's environment (see [`PRIVILEGE_KEY`]).
+///
+/// `command_line` is the path of the program followed by it's arguments separated by whitespace.
+pub fn spawn(command_line: &str) {
+    let mut parts = command_line.split_whitespace();
+    let Some(program) = parts.next() else {
+        return;
+    };
+
+    if let Err(err) = Command::new(program)
+        .args(parts)
+        .env(opal_abi::PRIVILEGE_KEY_ENV, PRIVILEGE_KEY.to_string())
         .stdout(Stdio::from(logging::console_clone()))
         .stderr(Stdio::from(logging::console_clone()))
         .stdin(Stdio::from(logging::console_clone()))
         .spawn()
     {
-        elog!("Failed to spawn hello_world process: {}", err);
+        elog!("Failed to spawn {command_line:?}: {}", err);
+    }
+}
+
+/// A text property of a window that can be sent in chunks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum TextProperty {
+    Title,
+    AppId,
+}
+
+/// Assembles the window texts a single client sends in chunks, see [`WindowTextChunk`]
+#[derive(Default)]
+struct PendingTexts {
+    /// The total length and the received bytes of each text
+    texts: FxHashMap<(WinID, TextProperty), (usize, Vec<u8>)>,
+}
+
+impl PendingTexts {
+    /// Receives a chunk of the text `property` of a window owned by the client `client`,
+    /// returns the whole text if this was the last chunk.
+    fn receive(
+        &mut self,
+        client: ClientID,
+        property: TextProperty,
+        chunk: &WindowTextChunk,
+    ) -> Result<Option<String>, ResponseError> {
+        window::check_owner(client, chunk.win_id())?;
+
+        let total_len = chunk.total_len() as usize;
+        if total_len > MAX_WINDOW_TEXT_LEN {
+            return Err(ResponseError::InvalidData);
+        }
+
+        let key = (chunk.win_id(), property);
+        if chunk.offset() == 0 {
+            self.texts
+                .insert(key, (total_len, Vec::with_capacity(total_len)));
+        }
+
+        let Some((expected_len, text)) = self.texts.get_mut(&key) else {
+            return Err(ResponseError::InvalidData);
+        };
+
+        // Chunks must be sent in order and agree on the total length
+        if *expected_len != total_len
+            || text.len() != chunk.offset() as usize
+            || text.len() + chunk.bytes().len() > total_len
+        {
+            self.texts.remove(&key);
+            return Err(ResponseError::InvalidData);
+        }
+
+        text.extend_from_slice(chunk.bytes());
+        if text.len() != total_len {
+            return Ok(None);
+        }
+
+        let (_, text) = self.texts.remove(&key).expect("The text was just received");
+        String::from_utf8(text)
+            .map(Some)
+            .map_err(|_| ResponseError::InvalidData)
     }
 }
 
-fn handle_connect(connection: UnixSockConnection) {
-    dlog!("Handling a new connection");
-
-    let mut window_ids = Vec::with_capacity(1);
-
-    let pipe = Arc::new(ClientComPipe::new(connection));
-    // No one else is going to be receiving requests and therefore we can take ownership of the receiver
-    let mut receiver = pipe.receiver();
-
-    loop {
-        dlog!("Waiting for a Request");
-
-        let response = match receiver.receive_request() {
-            Ok(req) => match req.kind() {
-                RequestKind::CreateWindow(request) => {
-                    let height = request.height() as usize;
-                    let width = request.width() as usize;
-                    let pos_x = request.x() as usize;
-                    let pos_y = request.y() as usize;
-
-                    let window = Window::new_filled_with(
-                        pos_x,
-                        pos_y,
-                        width,
-                        height,
-                        Pixel::from_rgba(0, 0, 0, 0xFF),
-                    )
-                    .with_com_pipe(pipe.clone());
-
-                    let shm_key = *window.shm_key();
-                    window::add_window(window, WindowKind::Normal)
-                        .map(|id| {
-                            dlog!("Added Window {id}, with the SHM Key {shm_key} for a client");
-                            window_ids.push(id);
-                            CreateWindowResp::new(id, shm_key)
-                        })
-                        .map(OkResponse::WindowCreated)
-                        .ok_or(ResponseError::UnknownFatalError)
+/// A background loaded on another thread for a [`RequestKind::SetBackground`] request,
+/// reading and decoding an image may take a while and would otherwise stall every client and the input.
+struct BackgroundLoad {
+    /// The serial of the request, which is responded to once the background is loaded
+    serial: Serial,
+    thread: JoinHandle<Result<Wallpaper, WallpaperError>>,
+}
+
+/// A connected client
+struct Client {
+    pipe: Arc<ClientComPipe>,
+    /// The windows created by the client, removed once the client disconnects
+    window_ids: Vec<WinID>,
+    pending_texts: PendingTexts,
+    /// The pixels of the last capture the client requested, kept alive until the client maps them
+    capture: Option<Capture>,
+    /// The protocol version negotiated with the client, `None` until the client sends [`RequestKind::Hello`]
+    protocol_version: Option<u32>,
+    /// The token of the client's connection in the last [`Poller`] it was registered with, see [`Listener::register`]
+    token: Option<Token>,
+    /// The optional features agreed to when greeting the client, see [`RequestKind::required_capabilities`]
+    capabilities: Capabilities,
+    /// The backgrounds the client requested that are still loading, in the order they were requested in
+    background_loads: VecDeque<BackgroundLoad>,
+}
+
+impl Client {
+    fn new(connection: UnixSockConnection) -> Self {
+        Self {
+            pipe: Arc::new(ClientComPipe::new(connection)),
+            window_ids: Vec::with_capacity(1),
+            pending_texts: PendingTexts::default(),
+            capture: None,
+            protocol_version: None,
+            token: None,
+            capabilities: Capabilities::empty(),
+            background_loads: VecDeque::new(),
+        }
+    }
+
+    /// Moves the `width`*`height` captured `pixels` to shared memory replacing the client's previous capture,
+    /// returns the response to send to the client.
+    fn capture(&mut self, width: usize, height: usize, pixels: &[Pixel]) -> OkResponse {
+        let capture = self.capture.insert(Capture::new(width, height, pixels));
+        OkResponse::Captured(CaptureResp::new(
+            capture.shm_key(),
+            capture.width() as u32,
+            capture.height() as u32,
+        ))
+    }
+
+    /// Handles a single request sent by the client
+    fn handle_request(&mut self, request: &Request) -> Result<OkResponse, ResponseError> {
+        let client = self.pipe.id();
+
+        match request.kind() {
+            RequestKind::Hello(_) if self.protocol_version.is_some() => {
+                Err(ResponseError::InvalidData)
+            }
+            RequestKind::Hello(hello) => {
+                let version = hello.protocol_version();
+                packet::check_version(version)?;
+                // Every optional feature is supported so far
+                let capabilities = hello.capabilities() & Capabilities::all();
+
+                self.protocol_version = Some(version);
+                self.capabilities = capabilities;
+                dlog!("Greeted a client speaking protocol version {version} with {capabilities:?}");
+                Ok(OkResponse::Welcome(WelcomeResp::new(version, capabilities)))
+            }
+            // Clients that predate the negotiation don't start with a Hello
+            _ if self.protocol_version.is_none() => {
+                Err(PacketParseErr::UnsupportedVersion(0).into())
+            }
+            kind if !self.capabilities.contains(kind.required_capabilities()) => {
+                Err(ResponseError::MissingCapability)
+            }
+            RequestKind::CreateWindow(request) => {
+                let height = request.height() as usize;
+                let width = request.width() as usize;
+                let pos_x = request.x() as usize;
+                let pos_y = request.y() as usize;
+
+                let mut window = Window::new_filled_with(
+                    pos_x,
+                    pos_y,
+                    width,
+                    height,
+                    Pixel::from_rgba(0, 0, 0, 0xFF),
+                )
+                .with_com_pipe(self.pipe.clone())
+                .with_alpha_format(request.alpha_format());
+
+                if !request.flags().contains(WindowFlags::NO_DECORATIONS) {
+                    window = window.with_decorations();
                 }
-                RequestKind::DamageWindow(damage) => window::damage_window(
-                    damage.win_id(),
-                    damage.x() as usize,
-                    damage.y() as usize,
-                    damage.width() as usize,
-                    damage.height() as usize,
+
+                let shm_key = window.shm_key();
+                window::add_window(window, WindowKind::Normal)
+                    .map(|id| {
+                        dlog!("Added Window {id}, with the SHM Key {shm_key} for a client");
+                        self.window_ids.push(id);
+                        CreateWindowResp::new(id, shm_key)
+                    })
+                    .map(OkResponse::WindowCreated)
+                    .ok_or(ResponseError::UnknownFatalError)
+            }
+            RequestKind::DamageWindow(damage) => window::damage_window(
+                client,
+                damage.win_id(),
+                damage.x() as usize,
+                damage.y() as usize,
+                damage.width() as usize,
+                damage.height() as usize,
+            )
+            .map(|()| OkResponse::Success),
+            RequestKind::ResizeWindow(resize) if resize.width() == 0 || resize.height() == 0 => {
+                Err(ResponseError::InvalidData)
+            }
+            RequestKind::ResizeWindow(resize) => window::resize_window(
+                client,
+                resize.win_id(),
+                resize.width() as usize,
+                resize.height() as usize,
+            )
+            .map(|(shm_key, width, height)| {
+                dlog!("Resized Window {} to {width}x{height}", resize.win_id());
+                OkResponse::WindowResized(ResizeWindowResp::new(
+                    shm_key,
+                    width as u32,
+                    height as u32,
+                ))
+            }),
+            RequestKind::MoveWindow(move_req) => window::move_window(
+                client,
+                move_req.win_id(),
+                move_req.x() as usize,
+                move_req.y() as usize,
+            )
+            .map(|_| OkResponse::Success),
+            RequestKind::DestroyWindow(destroy) => {
+                let win_id = destroy.win_id();
+                window::remove_window(client, win_id).map(|()| {
+                    dlog!("Destroyed Window {win_id} for a client");
+                    self.window_ids.retain(|id| *id != win_id);
+                    OkResponse::Success
+                })
+            }
+            RequestKind::SetWindowTitle(chunk) => self
+                .pending_texts
+                .receive(client, TextProperty::Title, chunk)
+                .and_then(|title| match title {
+                    Some(title) => window::set_window_title(client, chunk.win_id(), title),
+                    None => Ok(()),
+                })
+                .map(|()| OkResponse::Success),
+            RequestKind::SetAppId(chunk) => self
+                .pending_texts
+                .receive(client, TextProperty::AppId, chunk)
+                .and_then(|app_id| match app_id {
+                    Some(app_id) => window::set_app_id(client, chunk.win_id(), app_id),
+                    None => Ok(()),
+                })
+                .map(|()| OkResponse::Success),
+            RequestKind::AckConfigure(ack) => {
+                window::ack_configure(client, ack.win_id(), ack.serial()).map(|resized| {
+                    match resized {
+                        Some((shm_key, width, height)) => {
+                            dlog!("Resized Window {} to {width}x{height}", ack.win_id());
+                            OkResponse::WindowResized(ResizeWindowResp::new(
+                                shm_key,
+                                width as u32,
+                                height as u32,
+                            ))
+                        }
+                        None => OkResponse::Success,
+                    }
+                })
+            }
+            RequestKind::SetCursor(set_cursor) => {
+                window::set_window_cursor(client, set_cursor.win_id(), set_cursor.shape())
+                    .map(|()| OkResponse::Success)
+            }
+            RequestKind::SetOpaqueRegion(set_opaque) => {
+                let hint = Rect::new(
+                    set_opaque.x() as usize,
+                    set_opaque.y() as usize,
+                    set_opaque.width() as usize,
+                    set_opaque.height() as usize,
+                );
+                window::set_opaque_region(
+                    client,
+                    set_opaque.win_id(),
+                    (!hint.is_empty()).then_some(hint),
                 )
                 .map(|()| OkResponse::Success)
-                .map_err(|()| ResponseError::UnknownWindow),
-                RequestKind::Ping => Ok(OkResponse::Success),
-            },
-            Err(read_error) => match read_error {
-                ReadError::ParseErr(e) => Err(ResponseError::from(e)),
-                ReadError::IOError(e) if e.kind() == ErrorKind::ConnectionAborted => {
-                    dlog!("One client disconnected successfully");
-                    break;
+            }
+            RequestKind::CaptureScreen(request) if request.key() != *PRIVILEGE_KEY => {
+                Err(ResponseError::PermissionDenied)
+            }
+            RequestKind::CaptureScreen(_) => {
+                let (width, height, pixels) = capture::screen_pixels();
+                Ok(self.capture(width, height, &pixels))
+            }
+            // Only the owner of a window can capture it without the privilege key
+            RequestKind::CaptureWindow(request)
+                if request.key() != *PRIVILEGE_KEY
+                    && window::check_owner(client, request.win_id())
+                        == Err(ResponseError::PermissionDenied) =>
+            {
+                Err(ResponseError::PermissionDenied)
+            }
+            RequestKind::CaptureWindow(request) => window::capture_window(request.win_id())
+                .map(|(width, height, pixels)| self.capture(width, height, &pixels)),
+            // Requests with the privilege key are loaded on another thread instead, see `Self::start_background_load`
+            RequestKind::SetBackground(_) => Err(ResponseError::PermissionDenied),
+            RequestKind::RequestFrameCallback(request) => {
+                window::request_frame_callback(client, request.win_id())
+                    .map(|()| OkResponse::Success)
+            }
+            RequestKind::Ping => Ok(OkResponse::Success),
+        }
+    }
+
+    /// Starts loading the background of a privileged [`RequestKind::SetBackground`] request on another thread,
+    /// returns false if `request` isn't one, it should be handled by [`Self::handle_request`] then.
+    fn start_background_load(&mut self, request: &Request) -> bool {
+        let RequestKind::SetBackground(set_background) = request.kind() else {
+            return false;
+        };
+        if self.protocol_version.is_none()
+            || !self.capabilities.contains(Capabilities::SET_BACKGROUND)
+            || set_background.key() != *PRIVILEGE_KEY
+        {
+            return false;
+        }
+
+        let background = *set_background.background();
+        let (width, height) = framebuffer::screen_size();
+        self.background_loads.push_back(BackgroundLoad {
+            serial: request.serial(),
+            thread: thread::spawn(move || Wallpaper::new(background, width, height)),
+        });
+        true
+    }
+
+    /// Returns true if some of the backgrounds the client requested are still loading, see [`Self::finish_background_loads`]
+    fn is_loading(&self) -> bool {
+        !self.background_loads.is_empty()
+    }
+
+    /// Sets the backgrounds that finished loading in the order they were requested in and responds to their requests,
+    /// never blocks, returns false if the client should be disconnected.
+    fn finish_background_loads(&mut self) -> bool {
+        while let Some(load) = self
+            .background_loads
+            .pop_front_if(|load| load.thread.is_finished())
+        {
+            let result = match load.thread.join() {
+                Ok(Ok(wallpaper)) => {
+                    window::set_wallpaper(wallpaper);
+                    Response::Ok(OkResponse::Success, load.serial)
                 }
-                ReadError::IOError(e) => {
-                    elog!("Error reading from socket '{e}', disconnecting...");
-                    break;
+                Ok(Err(err)) => {
+                    elog!("Failed to set the background: {err}");
+                    Response::Err(ResponseError::InvalidData, load.serial)
                 }
-            },
-        };
+                Err(_) => {
+                    elog!("Failed to set the background: the loading thread panicked");
+                    Response::Err(ResponseError::InvalidData, load.serial)
+                }
+            };
 
-        let response = match response {
-            Err(e) => Response::Err(e),
-            Ok(k) => Response::Ok(k),
-        };
+            if let Err(e) = self.pipe.sender().send_response(&result) {
+                elog!("Error writing to socket '{e}', disconnecting...");
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Handles every request the client sent so far without blocking,
+    /// returns false if the client disconnected (or should be disconnected).
+    fn handle_pending_requests(&mut self) -> bool {
+        loop {
+            let (response, serial) = match self.pipe.try_receive_request() {
+                // Responded to once the background is loaded
+                Ok(Some(request)) if self.start_background_load(&request) => continue,
+                Ok(Some(request)) => (self.handle_request(&request), request.serial()),
+                Ok(None) => return true,
+                Err(read_error) => match read_error {
+                    // The first request is always a Hello, a client whose Hello can't be decoded speaks another protocol version
+                    ReadError::ParseErr(_, serial) if self.protocol_version.is_none() => {
+                        (Err(PacketParseErr::UnsupportedVersion(0).into()), serial)
+                    }
+                    ReadError::ParseErr(e, serial) => (Err(ResponseError::from(e)), serial),
+                    ReadError::IOError(e) if e.kind() == ErrorKind::ConnectionAborted => {
+                        dlog!("One client disconnected successfully");
+                        return false;
+                    }
+                    ReadError::IOError(e) => {
+                        elog!("Error reading from socket '{e}', disconnecting...");
+                        return false;
+                    }
+                },
+            };
+
+            let response = match response {
+                Err(e) => Response::Err(e, serial),
+                Ok(k) => Response::Ok(k, serial),
+            };
 
-        dlog!("Writing a Response");
-        if let Err(e) = pipe.sender().send_response(response) {
-            elog!("Error writing to socket '{e}', disconnecting...");
-            break;
+            dlog!("Writing a Response");
+            if let Err(e) = self.pipe.sender().send_response(&response) {
+                elog!("Error writing to socket '{e}', disconnecting...");
+                return false;
+            }
+
+            // Refused during the negotiation, see `RequestKind::Hello`
+            if self.protocol_version.is_none() {
+                wlog!(
+                    "Refused a client speaking an unsupported protocol version, disconnecting..."
+                );
+                return false;
+            }
         }
     }
+}
 
-    // cleanup windows
-    {
+impl Drop for Client {
+    fn drop(&mut self) {
+        // cleanup windows
         let mut windows = WINDOWS
             .lock()
             .expect("Failed to acquire lock on Windows when cleaning up after disconnecting");
-        for id in window_ids {
-            if let Err(()) = windows.remove_window(id) {
+        for id in &self.window_ids {
+            if let Err(()) = windows.remove_window(*id) {
                 wlog!("Failed to remove window {id}");
             }
         }
     }
 }
 
-/// Listens for incoming connections and handles them
-pub fn listen() -> ! {
-    let addr = opal_abi::CONNECT_ABSTRACT_ADDR;
-    let mut listener_builder = UnixListenerBuilder::from_abstract_path(addr).unwrap();
-    listener_builder
-        .set_type(SockKind::SeqPacket)
-        .set_backlog(usize::MAX);
+/// Accepts incoming connections and handles the requests of every connected client, all from a single thread
+pub struct Listener {
+    listener: UnixListener,
+    clients: Vec<Client>,
+    /// The token of the listener in the last [`Poller`] it was registered with, see [`Listener::register`]
+    token: Option<Token>,
+}
 
-    let listener = listener_builder.bind().expect("Failed to bind a listener");
-    log!("WM Listening at {}", addr);
+impl Listener {
+    /// Binds the WM's socket so clients can start connecting
+    pub fn bind() -> Self {
+        let addr = opal_abi::CONNECT_ABSTRACT_ADDR;
+        let mut listener_builder = UnixListenerBuilder::from_abstract_path(addr).unwrap();
+        listener_builder
+            .set_type(SockKind::SeqPacket)
+            .set_backlog(usize::MAX);
 
-    spawn_hello();
+        let listener = listener_builder.bind().expect("Failed to bind a listener");
+        log!("WM Listening at {}", addr);
 
-    loop {
-        let connection = listener
-            .accept()
-            .expect("Failed to Accept a pending connection");
+        Self {
+            listener,
+            clients: Vec::new(),
+            token: None,
+        }
+    }
 
-        // TODO: Implement something similar to poll
-        std::thread::spawn(|| handle_connect(connection));
+    /// Registers the listener and every client's connection with `poller`,
+    /// a connection is waited on to become writable only while some responses are queued for it.
+    pub fn register(&mut self, poller: &mut Poller) {
+        self.token = Some(poller.register(poll::raw_resource(&self.listener), false));
+        for client in &mut self.clients {
+            let pipe = &client.pipe;
+            client.token = Some(poller.register(pipe.raw_resource(), pipe.has_queued()));
+        }
+    }
+
+    /// Accepts pending connections, sends the queued responses and handles the pending requests
+    /// of every client that is ready after the last wait on `poller`, never blocks.
+    ///
+    /// Clients that stopped receiving responses are disconnected.
+    pub fn poll(&mut self, poller: &Poller) {
+        if self.token.is_some_and(|token| poller.is_readable(token)) {
+            self.accept_pending();
+        }
+
+        // Disconnected clients are dropped, which removes their windows
+        self.clients.retain_mut(|client| {
+            // Clients accepted after the wait haven't been registered yet, but may have already sent requests
+            let (readable, writable) = match client.token {
+                Some(token) => (poller.is_readable(token), poller.is_writable(token)),
+                None => (true, false),
+            };
+
+            if writable && let Err(e) = client.pipe.flush() {
+                elog!("Error writing to socket '{e}', disconnecting...");
+                return false;
+            }
+
+            if readable && !client.handle_pending_requests() {
+                return false;
+            }
+
+            if !client.finish_background_loads() {
+                return false;
+            }
+
+            // Events are queued for the client outside of request handling too, see `Window::send_event`
+            if client.pipe.overflowed() {
+                wlog!("A client stopped receiving responses, disconnecting...");
+                return false;
+            }
+            true
+        });
+    }
+
+    /// Returns true if some backgrounds are still loading on other threads,
+    /// [`Self::poll`] should be called every now and then to finish them since they can't wake up a wait on a [`Poller`].
+    pub fn is_loading(&self) -> bool {
+        self.clients.iter().any(Client::is_loading)
+    }
+
+    /// Accepts every pending connection, never blocks
+    fn accept_pending(&mut self) {
+        let ri = poll::raw_resource(&self.listener);
+        loop {
+            // The listener blocks, so only accept while a connection is pending
+            match poll::is_readable_now(ri) {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(e) => {
+                    elog!("Failed to check for pending connections '{e}'");
+                    break;
+                }
+            }
+
+            match self.listener.accept() {
+                Ok(connection) => {
+                    dlog!("Handling a new connection");
+                    self.clients.push(Client::new(connection));
+                }
+                Err(e) => {
+                    elog!("Failed to accept a pending connection '{e}'");
+                    break;
+                }
+            }
+        }
     }
 }
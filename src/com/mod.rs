@@ -1,115 +1,180 @@
 use std::{
-    cell::UnsafeCell,
-    io::{self, Read, Write},
-    sync::{Mutex, MutexGuard},
+    collections::VecDeque,
+    hash::{BuildHasher, RandomState},
+    io::{self, ErrorKind, Read, Write},
+    sync::{
+        LazyLock, Mutex, MutexGuard,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Instant,
 };
 
 use opal_abi::com::{
-    packet::{MAX_PACKET_SIZE, PacketParseErr},
+    packet::{MAX_PACKET_SIZE, PacketParseErr, Serial},
     request::Request,
     response::Response,
 };
-use safa_api::sockets::UnixSockConnection;
+use safa_api::{sockets::UnixSockConnection, syscalls::types::Ri};
 use thiserror::Error;
 
+use crate::poll;
+
 pub mod listener;
 
-/// A lock guard for the Sender part of the [`ClientComPipe`]
-pub struct ClientComSender<'a> {
-    _guard: MutexGuard<'a, ()>,
-    pipe: &'a ClientComPipe,
+/// The key that makes a request privileged, only the processes the WM spawns know it, see [`opal_abi::PRIVILEGE_KEY_ENV`]
+pub static PRIVILEGE_KEY: LazyLock<u64> =
+    LazyLock::new(|| RandomState::new().hash_one(Instant::now()));
+
+/// The most packets that can wait for a client to receive them, a client that falls further behind is disconnected
+const MAX_QUEUED_PACKETS: usize = 512;
+
+/// The connection with a client along with the packets that are waiting for the client to make room for them
+struct Connection {
+    socket: UnixSockConnection,
+    /// Encoded packets in the order they were sent in, see [`Response::encode`]
+    queued: VecDeque<([u8; MAX_PACKET_SIZE], usize)>,
+    /// Set once more than [`MAX_QUEUED_PACKETS`] packets are waiting, nothing is sent to the client anymore
+    overflowed: bool,
 }
 
-impl ClientComSender<'_> {
-    /// Sends a response to the client, blocks until the response is sent.
-    pub fn send_response(&mut self, response: Response) -> Result<(), io::Error> {
-        let (bytes, len) = response.encode();
-        let bytes = &bytes[..len];
+impl Connection {
+    /// Sends the queued packets until the client stops receiving, only fails if the connection itself failed
+    fn flush(&mut self) -> io::Result<()> {
+        let ri = poll::raw_resource(&self.socket);
+        while let Some((bytes, len)) = self.queued.front() {
+            // The socket blocks, so nothing is written unless the client made room for it
+            if !poll::is_writable_now(ri)? {
+                break;
+            }
 
-        let len = self.write(bytes)?;
-        debug_assert_eq!(len, bytes.len());
+            self.socket.write_all(&bytes[..*len])?;
+            self.queued.pop_front();
+        }
         Ok(())
     }
 }
 
-/// A lock guard for the Receiver part of the [`ClientComPipe`].
-pub struct ClientComReceiver<'a> {
-    _guard: MutexGuard<'a, ()>,
-    pipe: &'a ClientComPipe,
+/// A lock guard for the Sender part of the [`ClientComPipe`]
+pub struct ClientComSender<'a> {
+    connection: MutexGuard<'a, Connection>,
 }
 
-impl ClientComReceiver<'_> {
-    /// Receives a request from the client, blocks until the request is received.
-    pub fn receive_request(&mut self) -> Result<Request, ReadError> {
-        let mut buf = [0u8; MAX_PACKET_SIZE];
-        let len = self.read(&mut buf)?;
-        let request = &buf[..len];
-        Ok(Request::decode(request)?)
+impl ClientComSender<'_> {
+    /// Sends a response to the client without blocking, if the client isn't receiving fast enough the response is queued
+    /// and sent after every response sent before it, once the client makes room for it (see [`ClientComPipe::flush`]).
+    ///
+    /// Fails with [`ErrorKind::WouldBlock`] once too many responses are queued (see [`MAX_QUEUED_PACKETS`]),
+    /// the client should then be disconnected.
+    pub fn send_response(&mut self, response: &Response) -> Result<(), io::Error> {
+        let connection = &mut *self.connection;
+        if connection.overflowed {
+            return Err(ErrorKind::WouldBlock.into());
+        }
+
+        connection.queued.push_back(response.encode());
+        connection.flush()?;
+
+        if connection.queued.len() > MAX_QUEUED_PACKETS {
+            connection.overflowed = true;
+            connection.queued.clear();
+            return Err(io::Error::new(
+                ErrorKind::WouldBlock,
+                "The client stopped receiving",
+            ));
+        }
+        Ok(())
     }
 }
 
 /// A Wrapper over a bi-directonal communication pipe, that can send data to and from the client.
 ///
-/// This structure allows you to separate read and write operations on the client giving different locks for send and receive operations,
-/// obviously this means that there is no guarantee that the client will receive the response in the same order as the request was sent, but allows to send events to the client.
+/// Neither receiving a request nor sending a response ever waits for the client,
+/// the connection is only read from or written to once it is ready,
+/// this allows events to be sent to the client while requests are being received (in no particular order).
 pub struct ClientComPipe {
-    sender_lock: Mutex<()>,
-    receiver_lock: Mutex<()>,
-    connection: UnsafeCell<UnixSockConnection>,
+    id: ClientID,
+    connection: Mutex<Connection>,
 }
 
 /// An Error that happened during reading a request from a Client
 #[derive(Error, Debug)]
 pub enum ReadError {
+    /// The request couldn't be decoded, along with it's serial if that could be decoded or [`opal_abi::com::packet::NO_SERIAL`] otherwise
     #[error("Failed to parse Client's Request {0}")]
-    ParseErr(#[from] PacketParseErr),
+    ParseErr(PacketParseErr, Serial),
     #[error("Error while reading from a socket: {0}")]
     IOError(#[from] io::Error),
 }
 
+/// A unique identifier given to each connected client
+pub type ClientID = usize;
+
+static NEXT_CLIENT_ID: AtomicUsize = AtomicUsize::new(0);
+
 impl ClientComPipe {
-    pub const fn new(inner: UnixSockConnection) -> Self {
+    pub fn new(inner: UnixSockConnection) -> Self {
         Self {
-            sender_lock: Mutex::new(()),
-            receiver_lock: Mutex::new(()),
-            connection: UnsafeCell::new(inner),
+            id: NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed),
+            connection: Mutex::new(Connection {
+                socket: inner,
+                queued: VecDeque::new(),
+                overflowed: false,
+            }),
         }
     }
 
+    /// Returns the ID of the client on the other side of this pipe
+    pub const fn id(&self) -> ClientID {
+        self.id
+    }
+
+    fn connection(&self) -> MutexGuard<'_, Connection> {
+        self.connection
+            .lock()
+            .expect("Failed to acquire lock on a communication pipe")
+    }
+
+    /// Returns the raw resource of the connection, it becomes readable once the client sends a request
+    /// and writable once the client makes room for the queued responses.
+    pub fn raw_resource(&self) -> Ri {
+        poll::raw_resource(&self.connection().socket)
+    }
+
+    /// Returns whether or not some responses are waiting for the client to make room for them
+    pub fn has_queued(&self) -> bool {
+        !self.connection().queued.is_empty()
+    }
+
+    /// Returns whether or not the client fell too far behind on receiving responses and should be disconnected
+    pub fn overflowed(&self) -> bool {
+        self.connection().overflowed
+    }
+
+    /// Sends the responses that are waiting for the client to make room for them, never blocks
+    pub fn flush(&self) -> io::Result<()> {
+        self.connection().flush()
+    }
+
     /// Acquires lock on a sender that can be used to send responses to the client.
     pub fn sender<'a>(&'a self) -> ClientComSender<'a> {
         ClientComSender {
-            _guard: self
-                .sender_lock
-                .lock()
-                .expect("Failed to acquire lock on the sending side of a communication pipe"),
-            pipe: self,
+            connection: self.connection(),
         }
     }
 
-    /// Acquires lock on a receiver that can be used to receive requests from the client.
-    pub fn receiver<'a>(&'a self) -> ClientComReceiver<'a> {
-        ClientComReceiver {
-            _guard: self
-                .receiver_lock
-                .lock()
-                .expect("Failed to acquire lock on the receiving side of a communication pipe"),
-            pipe: self,
+    /// Receives a request from the client if the client sent any, never blocks.
+    pub fn try_receive_request(&self) -> Result<Option<Request>, ReadError> {
+        let mut connection = self.connection();
+        if !poll::is_readable_now(poll::raw_resource(&connection.socket))? {
+            return Ok(None);
         }
-    }
-}
 
-impl<'a> Read for ClientComReceiver<'a> {
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        unsafe { Read::read(&mut *self.pipe.connection.get(), buf) }
-    }
-}
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+        let len = connection.socket.read(&mut buf)?;
 
-impl<'a> Write for ClientComSender<'a> {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        unsafe { Write::write(&mut *self.pipe.connection.get(), buf) }
-    }
-    fn flush(&mut self) -> io::Result<()> {
-        unsafe { &mut *self.pipe.connection.get() }.flush()
+        let request = &buf[..len];
+        Request::decode(request)
+            .map(Some)
+            .map_err(|err| ReadError::ParseErr(err, Request::decode_serial(request)))
     }
 }
@@ -0,0 +1,527 @@
+//! The configuration of the WM, loaded once at startup from [`CONFIG_PATH`].
+//!
+//! The configuration file is made of `[section]` headers each followed by `key = value` lines,
+//! lines starting with `#` or `;` are comments and blank lines are ignored.
+//! Every setting is optional, a missing setting (or a missing file) keeps the default behavior.
+//!
+//! ```ini
+//! [general]
+//! # One of `error`, `warn`, `info`, `debug` or `verbose`
+//! log_level = debug
+//! # `click` focuses a window when it is clicked, `hover` focuses (and raises) a window as soon as the cursor enters it
+//! focus_policy = click
+//! # The maximum amount of windows that can exist at once, from 1 to 65536
+//! max_windows = 1024
+//! # Whether or not to show the two demo windows at startup
+//! demo_windows = true
+//! # A background description, see `wallpaper::parse_background`
+//! background = solid #282828
+//! # The maximum amount of frames drawn per second
+//! refresh_rate = 60
+//! # Draws to an in-memory screen of `WIDTHxHEIGHT` pixels instead of the framebuffer device, unset by default
+//! # headless = 1024x768
+//!
+//! [cursor]
+//! # The directory the cursor theme is loaded from, see `cursor::CursorTheme::load`
+//! theme = sys:/share/cursors
+//!
+//! # The programs spawned once the WM is ready to accept clients, in order,
+//! # this section replaces the default list so an empty section spawns nothing
+//! [autostart]
+//! run = sys:/bin/hello_world
+//!
+//! # Key combinations in the format `<modifier>+...+<key>` mapped to an action, see `KeyBinding` and `Action`,
+//! # this section replaces the default bindings so an empty section disables every binding
+//! [bindings]
+//! super+m = restore-minimized
+//! print_screen = screenshot
+//! ```
+
+use std::{fs, io::ErrorKind, path::PathBuf};
+
+use opal_abi::{
+    com::response::event::KeyModifiers,
+    keysym::{self, Keysym},
+};
+use thiserror::Error;
+
+use crate::{
+    clock::DEFAULT_REFRESH_RATE,
+    cursor::CURSOR_THEME_DIR,
+    elog, log,
+    logging::LogLevel,
+    wallpaper::{self, Background, DEFAULT_BACKGROUND},
+    window::{DEFAULT_MAX_WINDOWS, MAX_WINDOWS_LIMIT},
+};
+
+/// The path the configuration is loaded from
+pub const CONFIG_PATH: &str = "sys:/etc/opal.conf";
+
+/// An error in the configuration file, each error is reported and the line it is in is ignored
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Line {0} is not in the format `[section]` or `key = value`")]
+    InvalidLine(usize),
+    #[error("Line {0} is outside of any section")]
+    NoSection(usize),
+    #[error("Line {0} has an unknown section {1:?}")]
+    UnknownSection(usize, String),
+    #[error("Line {0} has an unknown key {1:?}")]
+    UnknownKey(usize, String),
+    #[error("Line {0} has an invalid value for {1:?}: {2:?}")]
+    InvalidValue(usize, String, String),
+}
+
+/// When a window gets focused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FocusPolicy {
+    /// When the window is clicked
+    Click,
+    /// As soon as the cursor enters the window, the focus stays on the last window even if the cursor leaves it
+    Hover,
+}
+
+impl FocusPolicy {
+    pub const ALL: [FocusPolicy; 2] = [Self::Click, Self::Hover];
+
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Click => "click",
+            Self::Hover => "hover",
+        }
+    }
+
+    /// Returns the focus policy with the name `name` if any, see [`Self::name`]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|policy| policy.name() == name)
+    }
+}
+
+/// Something the WM does when a key binding is pressed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Restores the most recently minimized window
+    RestoreMinimized,
+    /// Saves a screenshot of the whole screen, see [`crate::capture::save_screenshot`]
+    Screenshot,
+    /// Asks the focused window to close
+    CloseWindow,
+    MinimizeWindow,
+    /// Maximizes the focused window, or restores it if it is already maximized
+    MaximizeWindow,
+    /// Spawns a program, the command line is the path of the program followed by it's arguments separated by whitespace
+    Spawn(String),
+}
+
+impl Action {
+    /// Parses an action, either `restore-minimized`, `screenshot`, `close-window`, `minimize-window`,
+    /// `maximize-window` or `spawn <command line>`.
+    pub fn parse(action: &str) -> Option<Self> {
+        if let Some(command_line) = action.strip_prefix("spawn ") {
+            let command_line = command_line.trim();
+            return (!command_line.is_empty()).then(|| Self::Spawn(command_line.to_string()));
+        }
+
+        match action {
+            "restore-minimized" => Some(Self::RestoreMinimized),
+            "screenshot" => Some(Self::Screenshot),
+            "close-window" => Some(Self::CloseWindow),
+            "minimize-window" => Some(Self::MinimizeWindow),
+            "maximize-window" => Some(Self::MaximizeWindow),
+            _ => None,
+        }
+    }
+}
+
+/// A key combination that runs an action instead of being sent to the focused window
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyBinding {
+    /// The modifiers that must be held, exactly, the lock modifiers are ignored
+    modifiers: KeyModifiers,
+    keysym: Keysym,
+    action: Action,
+}
+
+impl KeyBinding {
+    /// Parses a key combination in the format `<modifier>+...+<key>`,
+    /// the modifiers are `shift`, `ctrl`, `alt` and `super`, the key is a name accepted by [`keysym::from_name`].
+    pub fn parse(combination: &str, action: Action) -> Option<Self> {
+        let combination = combination.to_ascii_lowercase();
+        let (modifier_names, key) = combination
+            .rsplit_once('+')
+            .unwrap_or(("", combination.as_str()));
+
+        let mut modifiers = KeyModifiers::empty();
+        for name in modifier_names.split('+').filter(|name| !name.is_empty()) {
+            modifiers |= match name {
+                "shift" => KeyModifiers::SHIFT,
+                "ctrl" => KeyModifiers::CTRL,
+                "alt" => KeyModifiers::ALT,
+                "super" => KeyModifiers::SUPER,
+                _ => return None,
+            };
+        }
+
+        Some(Self {
+            modifiers,
+            keysym: keysym::from_name(key)?,
+            action,
+        })
+    }
+
+    /// Returns whether or not pressing the key `keysym` with `modifiers` active triggers this binding
+    pub fn matches(&self, keysym: Keysym, modifiers: KeyModifiers) -> bool {
+        self.keysym == keysym
+            && modifiers.difference(KeyModifiers::CAPS_LOCK | KeyModifiers::NUM_LOCK)
+                == self.modifiers
+    }
+
+    pub const fn action(&self) -> &Action {
+        &self.action
+    }
+}
+
+/// A section of the configuration file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Section {
+    General,
+    Cursor,
+    Autostart,
+    Bindings,
+}
+
+/// The configuration of the WM, see the [module documentation](self) for the format
+#[derive(Debug)]
+pub struct Config {
+    pub log_level: LogLevel,
+    pub focus_policy: FocusPolicy,
+    pub max_windows: usize,
+    pub demo_windows: bool,
+    pub background: Background,
+    /// The maximum amount of frames drawn per second, never 0
+    pub refresh_rate: u32,
+    /// The size of the in-memory screen to draw to instead of the framebuffer device, if any
+    pub headless: Option<(usize, usize)>,
+    pub cursor_theme: PathBuf,
+    /// The command lines of the programs spawned at startup, see [`Action::Spawn`]
+    pub autostart: Vec<String>,
+    pub bindings: Vec<KeyBinding>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let binding = |combination, action| {
+            KeyBinding::parse(combination, action).expect("The default key bindings are valid")
+        };
+
+        Self {
+            log_level: LogLevel::Debug,
+            focus_policy: FocusPolicy::Click,
+            max_windows: DEFAULT_MAX_WINDOWS,
+            demo_windows: true,
+            background: DEFAULT_BACKGROUND,
+            refresh_rate: DEFAULT_REFRESH_RATE,
+            headless: None,
+            cursor_theme: PathBuf::from(CURSOR_THEME_DIR),
+            autostart: vec!["sys:/bin/hello_world".to_string()],
+            bindings: vec![
+                binding("super+m", Action::RestoreMinimized),
+                binding("print_screen", Action::Screenshot),
+            ],
+        }
+    }
+}
+
+impl Config {
+    /// Loads the configuration at [`CONFIG_PATH`], every error is reported through [`elog!`] and the defaults are used instead
+    pub fn load() -> Self {
+        let text = match fs::read_to_string(CONFIG_PATH) {
+            Ok(text) => text,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                log!("No configuration at {CONFIG_PATH}, using the defaults");
+                return Self::default();
+            }
+            Err(err) => {
+                elog!("Failed to read {CONFIG_PATH}, using the defaults: {err}");
+                return Self::default();
+            }
+        };
+
+        let (config, errors) = Self::parse(&text);
+        for err in &errors {
+            elog!("Invalid configuration at {CONFIG_PATH}: {err}");
+        }
+
+        log!("Loaded the configuration at {CONFIG_PATH}");
+        config
+    }
+
+    /// Parses a configuration, returns the parsed configuration along with the errors of the lines that were ignored
+    pub fn parse(text: &str) -> (Self, Vec<ConfigError>) {
+        let mut config = Self::default();
+        let mut errors = Vec::new();
+        let mut section = None;
+
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            if let Some(name) = line
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                section = match name.trim() {
+                    "general" => Some(Section::General),
+                    "cursor" => Some(Section::Cursor),
+                    "autostart" => {
+                        config.autostart.clear();
+                        Some(Section::Autostart)
+                    }
+                    "bindings" => {
+                        config.bindings.clear();
+                        Some(Section::Bindings)
+                    }
+                    name => {
+                        errors.push(ConfigError::UnknownSection(line_number, name.to_string()));
+                        None
+                    }
+                };
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                errors.push(ConfigError::InvalidLine(line_number));
+                continue;
+            };
+            let (key, value) = (key.trim(), value.trim());
+
+            let Some(section) = section else {
+                errors.push(ConfigError::NoSection(line_number));
+                continue;
+            };
+
+            if let Err(err) = config.set(section, key, value, line_number) {
+                errors.push(err);
+            }
+        }
+
+        (config, errors)
+    }
+
+    /// Sets the setting `key` of the section `section` to `value`, found at the line `line_number`
+    fn set(
+        &mut self,
+        section: Section,
+        key: &str,
+        value: &str,
+        line_number: usize,
+    ) -> Result<(), ConfigError> {
+        let invalid_value =
+            || ConfigError::InvalidValue(line_number, key.to_string(), value.to_string());
+
+        match (section, key) {
+            (Section::General, "log_level") => {
+                self.log_level = LogLevel::from_name(value).ok_or_else(invalid_value)?
+            }
+            (Section::General, "focus_policy") => {
+                self.focus_policy = FocusPolicy::from_name(value).ok_or_else(invalid_value)?
+            }
+            (Section::General, "max_windows") => {
+                self.max_windows = value
+                    .parse()
+                    .ok()
+                    .filter(|max| (1..=MAX_WINDOWS_LIMIT).contains(max))
+                    .ok_or_else(invalid_value)?
+            }
+            (Section::General, "demo_windows") => {
+                self.demo_windows = value.parse().map_err(|_| invalid_value())?
+            }
+            (Section::General, "background") => {
+                self.background = wallpaper::parse_background(value).ok_or_else(invalid_value)?
+            }
+            (Section::General, "refresh_rate") => {
+                self.refresh_rate = value
+                    .parse()
+                    .ok()
+                    .filter(|rate| *rate > 0)
+                    .ok_or_else(invalid_value)?
+            }
+            (Section::General, "headless") => {
+                self.headless = value
+                    .split_once('x')
+                    .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+                    .filter(|&(width, height)| width > 0 && height > 0)
+                    .map(Some)
+                    .ok_or_else(invalid_value)?
+            }
+            (Section::Cursor, "theme") if !value.is_empty() => {
+                self.cursor_theme = PathBuf::from(value)
+            }
+            (Section::Cursor, "theme") => return Err(invalid_value()),
+            (Section::Autostart, "run") if !value.is_empty() => {
+                self.autostart.push(value.to_string())
+            }
+            (Section::Autostart, "run") => return Err(invalid_value()),
+            (Section::Bindings, combination) => {
+                let binding = Action::parse(value)
+                    .and_then(|action| KeyBinding::parse(combination, action))
+                    .ok_or_else(invalid_value)?;
+                // A later binding of the same combination replaces the earlier one
+                self.bindings.retain(|other| {
+                    other.keysym != binding.keysym || other.modifiers != binding.modifiers
+                });
+                self.bindings.push(binding);
+            }
+            _ => return Err(ConfigError::UnknownKey(line_number, key.to_string())),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binding(combination: &str, action: Action) -> KeyBinding {
+        KeyBinding::parse(combination, action).unwrap()
+    }
+
+    #[test]
+    fn parse_empty_keeps_the_defaults() {
+        let (config, errors) = Config::parse("\n  # a comment\n; another comment\n\n");
+        let default = Config::default();
+
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(config.log_level, default.log_level);
+        assert_eq!(config.focus_policy, default.focus_policy);
+        assert_eq!(config.max_windows, default.max_windows);
+        assert_eq!(config.demo_windows, default.demo_windows);
+        assert_eq!(config.background, default.background);
+        assert_eq!(config.refresh_rate, DEFAULT_REFRESH_RATE);
+        assert_eq!(config.headless, None);
+        assert_eq!(config.cursor_theme, default.cursor_theme);
+        assert_eq!(config.autostart, default.autostart);
+        assert_eq!(config.bindings, default.bindings);
+    }
+
+    #[test]
+    fn parse_every_setting() {
+        let (config, errors) = Config::parse(
+            "[general]
+            log_level = warn
+            focus_policy = hover
+            max_windows = 16
+            demo_windows = false
+            background = gradient vertical #000000 #FFFFFF
+            refresh_rate = 144
+            headless = 640x480
+
+            [cursor]
+            theme = sys:/share/other-cursors
+
+            [autostart]
+            run = sys:/bin/a
+            run = sys:/bin/b --flag
+
+            [bindings]
+            ctrl+alt+t = spawn sys:/bin/term
+            super+q = close-window",
+        );
+
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(config.log_level, LogLevel::Warn);
+        assert_eq!(config.focus_policy, FocusPolicy::Hover);
+        assert_eq!(config.max_windows, 16);
+        assert!(!config.demo_windows);
+        assert_eq!(
+            config.background,
+            wallpaper::parse_background("gradient vertical #000000 #FFFFFF").unwrap()
+        );
+        assert_eq!(config.refresh_rate, 144);
+        assert_eq!(config.headless, Some((640, 480)));
+        assert_eq!(
+            config.cursor_theme,
+            PathBuf::from("sys:/share/other-cursors")
+        );
+        assert_eq!(config.autostart, ["sys:/bin/a", "sys:/bin/b --flag"]);
+        assert_eq!(
+            config.bindings,
+            [
+                binding("ctrl+alt+t", Action::Spawn("sys:/bin/term".to_string())),
+                binding("super+q", Action::CloseWindow),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_reports_and_skips_invalid_lines() {
+        let (config, errors) = Config::parse(
+            "log_level = info
+            [general]
+            not a setting
+            unknown = 1
+            max_windows = 0
+            refresh_rate = 0
+            headless = 640
+            headless = 0x480
+            focus_policy = click
+            [unknown]
+            key = value",
+        );
+
+        assert!(
+            matches!(
+                errors.as_slice(),
+                [
+                    ConfigError::NoSection(1),
+                    ConfigError::InvalidLine(3),
+                    ConfigError::UnknownKey(4, _),
+                    ConfigError::InvalidValue(5, _, _),
+                    ConfigError::InvalidValue(6, _, _),
+                    ConfigError::InvalidValue(7, _, _),
+                    ConfigError::InvalidValue(8, _, _),
+                    ConfigError::UnknownSection(10, _),
+                    ConfigError::NoSection(11),
+                ]
+            ),
+            "{errors:?}"
+        );
+        // The valid lines still apply, the invalid ones keep the defaults
+        assert_eq!(config.focus_policy, FocusPolicy::Click);
+        assert_eq!(config.max_windows, DEFAULT_MAX_WINDOWS);
+        assert_eq!(config.refresh_rate, DEFAULT_REFRESH_RATE);
+        assert_eq!(config.headless, None);
+    }
+
+    #[test]
+    fn parse_empty_sections_replace_the_default_lists() {
+        let (config, errors) = Config::parse("[autostart]\n[bindings]\n");
+
+        assert!(errors.is_empty(), "{errors:?}");
+        assert!(config.autostart.is_empty());
+        assert!(config.bindings.is_empty());
+    }
+
+    #[test]
+    fn parse_later_binding_replaces_the_same_combination() {
+        let (config, errors) = Config::parse(
+            "[bindings]
+            super+m = screenshot
+            SUPER+M = restore-minimized
+            print_screen = spawn",
+        );
+
+        assert!(
+            matches!(errors.as_slice(), [ConfigError::InvalidValue(4, _, _)]),
+            "{errors:?}"
+        );
+        assert_eq!(
+            config.bindings,
+            [binding("super+m", Action::RestoreMinimized)]
+        );
+    }
+}
@@ -0,0 +1,276 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+pub use opal_abi::cursor::CursorShape;
+use thiserror::Error;
+
+use crate::{
+    framebuffer::Pixel,
+    image::{Image, ImageError},
+};
+
+const DEFAULT_CURSOR_BYTES: &[u8] = include_bytes!("../assets/epic-cursor.bmp");
+
+/// The directory the cursor theme is loaded from
+pub const CURSOR_THEME_DIR: &str = "sys:/share/cursors";
+/// The file in a cursor theme directory that lists the image and the hotspot of each cursor shape
+const THEME_INDEX_FILE: &str = "index.theme";
+
+/// The size of the generated arrow cursors
+const ARROW_SIZE: usize = 17;
+const BUILTIN_FILL: Pixel = Pixel::from_rgba(0xFF, 0xFF, 0xFF, 0xFF);
+const BUILTIN_OUTLINE: Pixel = Pixel::from_rgba(0, 0, 0, 0xFF);
+
+/// An error that happened while loading a cursor theme
+#[derive(Debug, Error)]
+pub enum CursorThemeError {
+    #[error("Failed to read {0}: {1}")]
+    Io(PathBuf, io::Error),
+    #[error("Line {0} of the index is not in the format `<shape> <image> <hotspot x> <hotspot y>`")]
+    InvalidLine(usize),
+    #[error("Line {0} of the index has an unknown cursor shape {1:?}")]
+    UnknownShape(usize, String),
+    #[error("Failed to parse the image {0}: {1}")]
+    InvalidImage(PathBuf, ImageError),
+    #[error("The hotspot of {0} is outside of it's image")]
+    InvalidHotspot(PathBuf),
+}
+
+/// A set of images, one for each cursor shape
+pub struct CursorTheme {
+    /// Indexed by the shape
+    images: [CursorImage; CursorShape::ALL.len()],
+}
+
+impl CursorTheme {
+    /// Returns the builtin cursor theme, used when no other theme is available
+    pub fn builtin() -> Self {
+        Self {
+            images: CursorShape::ALL.map(CursorImage::builtin),
+        }
+    }
+
+    /// Loads the cursor theme in the directory `dir`, shapes the theme has no image for use the builtin image.
+    ///
+    /// The directory must contain an index file named `index.theme`, each line of the index is in the format
+    /// `<shape> <image> <hotspot x> <hotspot y>` where `<shape>` is the name of the shape (see [`CursorShape::name`])
+    /// and `<image>` is the path of a BMP, PNG or QOI image relative to the directory, empty lines and lines starting with `#` are ignored.
+    pub fn load(dir: &Path) -> Result<Self, CursorThemeError> {
+        let index_path = dir.join(THEME_INDEX_FILE);
+        let index =
+            fs::read_to_string(&index_path).map_err(|err| CursorThemeError::Io(index_path, err))?;
+
+        let mut theme = Self::builtin();
+
+        for (i, line) in index.lines().enumerate() {
+            let line_num = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut parts = line.split_whitespace();
+            let (Some(name), Some(image), Some(hotspot_x), Some(hotspot_y), None) = (
+                parts.next(),
+                parts.next(),
+                parts.next().and_then(|x| x.parse().ok()),
+                parts.next().and_then(|y| y.parse().ok()),
+                parts.next(),
+            ) else {
+                return Err(CursorThemeError::InvalidLine(line_num));
+            };
+
+            let shape = CursorShape::from_name(name)
+                .ok_or_else(|| CursorThemeError::UnknownShape(line_num, name.to_string()))?;
+
+            let image_path = dir.join(image);
+            let bytes = fs::read(&image_path)
+                .map_err(|err| CursorThemeError::Io(image_path.clone(), err))?;
+            let image = Image::from_slice(&bytes)
+                .map_err(|err| CursorThemeError::InvalidImage(image_path.clone(), err))?;
+
+            if hotspot_x >= image.width() || hotspot_y >= image.height() {
+                return Err(CursorThemeError::InvalidHotspot(image_path));
+            }
+
+            theme.images[shape as usize] = CursorImage::from_image(&image, hotspot_x, hotspot_y);
+        }
+
+        Ok(theme)
+    }
+
+    /// Returns the image of the cursor shape `shape`
+    pub fn get(&self, shape: CursorShape) -> &CursorImage {
+        &self.images[shape as usize]
+    }
+}
+
+/// The image of a cursor shape
+pub struct CursorImage {
+    width: usize,
+    height: usize,
+    /// The point within the image that is placed at the cursor's position
+    hotspot: (usize, usize),
+    pixels: Vec<Pixel>,
+}
+
+impl CursorImage {
+    /// Creates a cursor image from an image, with the hotspot at (`hotspot_x`, `hotspot_y`)
+    pub fn from_image(image: &Image, hotspot_x: usize, hotspot_y: usize) -> Self {
+        Self {
+            width: image.width(),
+            height: image.height(),
+            hotspot: (hotspot_x, hotspot_y),
+            pixels: image.pixels().collect(),
+        }
+    }
+
+    /// Creates the builtin image of the cursor shape `shape`
+    pub fn builtin(shape: CursorShape) -> Self {
+        match shape {
+            CursorShape::Arrow => {
+                let image = Image::from_slice(DEFAULT_CURSOR_BYTES)
+                    .expect("Failed to parse the default cursor");
+                Self::from_image(&image, 0, 0)
+            }
+            CursorShape::Hand => Self::hand(),
+            CursorShape::Text => Self::i_beam(),
+            CursorShape::Busy => Self::hourglass(),
+            CursorShape::ResizeHorizontal => Self::double_arrow(1.0, 0.0),
+            CursorShape::ResizeVertical => Self::double_arrow(0.0, 1.0),
+            CursorShape::ResizeNwSe => Self::double_arrow(1.0, 1.0),
+            CursorShape::ResizeNeSw => Self::double_arrow(1.0, -1.0),
+        }
+    }
+
+    /// Creates an image of the size `width`*`height` with the hotspot at it's center,
+    /// the pixels `inside` returns true for are filled and the pixels around them are outlined.
+    fn outlined(width: usize, height: usize, inside: impl Fn(isize, isize) -> bool) -> Self {
+        let inside = |x: isize, y: isize| {
+            x >= 0 && y >= 0 && x < width as isize && y < height as isize && inside(x, y)
+        };
+
+        let mut pixels = vec![Pixel::from_rgba(0, 0, 0, 0); width * height];
+        for y in 0..height as isize {
+            for x in 0..width as isize {
+                let pixel = &mut pixels[y as usize * width + x as usize];
+
+                if inside(x, y) {
+                    *pixel = BUILTIN_FILL;
+                } else if (-1..=1)
+                    .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+                    .any(|(dx, dy)| inside(x + dx, y + dy))
+                {
+                    *pixel = BUILTIN_OUTLINE;
+                }
+            }
+        }
+
+        Self {
+            width,
+            height,
+            hotspot: (width / 2, height / 2),
+            pixels,
+        }
+    }
+
+    /// Draws a double headed arrow pointing in the direction (`dir_x`, `dir_y`)
+    fn double_arrow(dir_x: f32, dir_y: f32) -> Self {
+        const CENTER: f32 = (ARROW_SIZE / 2) as f32;
+        // The distance from the center to each head's tip
+        const TIP: f32 = CENTER - 1.0;
+        // The distance from the center to each head's base
+        const HEAD_BASE: f32 = TIP - 4.0;
+
+        let len = (dir_x * dir_x + dir_y * dir_y).sqrt();
+        let (dir_x, dir_y) = (dir_x / len, dir_y / len);
+
+        Self::outlined(ARROW_SIZE, ARROW_SIZE, |x, y| {
+            let rel_x = x as f32 - CENTER;
+            let rel_y = y as f32 - CENTER;
+            // The distance along the arrow and away from it
+            let along = (rel_x * dir_x + rel_y * dir_y).abs();
+            let across = (rel_x * dir_y - rel_y * dir_x).abs();
+
+            let shaft = along <= TIP && across <= 1.0;
+            let head = along >= HEAD_BASE && across <= TIP - along;
+            shaft || head
+        })
+    }
+
+    /// Draws a hand pointing up with it's index finger, with the hotspot at the tip of the finger
+    fn hand() -> Self {
+        #[rustfmt::skip]
+        const HAND: [&str; 19] = [
+            "..................",
+            "......##..........",
+            "......##..........",
+            "......##..........",
+            "......##..........",
+            "......##.##.##....",
+            "......##.##.##.##.",
+            "......##.##.##.##.",
+            "..##..##.##.##.##.",
+            "..###.##.##.##.##.",
+            "...##############.",
+            "...##############.",
+            "....#############.",
+            "....#############.",
+            ".....###########..",
+            ".....###########..",
+            "......#########...",
+            "......#########...",
+            "..................",
+        ];
+
+        let image = Self::outlined(HAND[0].len(), HAND.len(), |x, y| {
+            HAND[y as usize].as_bytes()[x as usize] == b'#'
+        });
+        Self {
+            hotspot: (6, 1),
+            ..image
+        }
+    }
+
+    /// Draws a text I-beam
+    fn i_beam() -> Self {
+        Self::outlined(9, 19, |x, y| {
+            let serif = (y == 1 || y == 17) && (1..=7).contains(&x);
+            let stem = x == 4 && (1..=17).contains(&y);
+            serif || stem
+        })
+    }
+
+    /// Draws an hourglass
+    fn hourglass() -> Self {
+        Self::outlined(15, 19, |x, y| {
+            let from_center = (y - 9).abs();
+            // The glass narrows towards the center, with a flat cap at the top and the bottom
+            let half_width = if from_center == 8 {
+                6
+            } else {
+                (from_center * 6 / 8).max(1)
+            };
+            from_center <= 8 && (x - 7).abs() <= half_width
+        })
+    }
+
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+
+    pub const fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the point within the image that is placed at the cursor's position
+    pub const fn hotspot(&self) -> (usize, usize) {
+        self.hotspot
+    }
+
+    pub fn pixels(&self) -> &[Pixel] {
+        &self.pixels
+    }
+}
@@ -0,0 +1,413 @@
+use opal_abi::cursor::CursorShape;
+
+use crate::{
+    font::{self, GLYPH_HEIGHT, GLYPH_WIDTH},
+    framebuffer::Pixel,
+};
+
+/// The width of the border around the window
+pub const BORDER_WIDTH: usize = 2;
+/// The height of the title bar, not including the border
+pub const TITLE_BAR_HEIGHT: usize = 22;
+
+const BUTTON_SIZE: usize = 14;
+const BUTTON_SPACING: usize = 6;
+const TITLE_PADDING: usize = 8;
+/// How close to the edge of the window the cursor has to be to resize it
+const RESIZE_MARGIN: usize = 4;
+/// How far from a corner along an edge the cursor can be to resize the window from that corner
+const RESIZE_CORNER_SIZE: usize = 12;
+
+// The whole border is used to resize the window
+const _: () = assert!(BORDER_WIDTH <= RESIZE_MARGIN);
+
+const BORDER_COLOR: Pixel = Pixel::from_rgba(0x3C, 0x38, 0x36, 0xFF);
+const TITLE_COLOR_FOCUSED: Pixel = Pixel::from_rgba(0xFD, 0xB0, 0xC0, 0xFF);
+const TITLE_COLOR_UNFOCUSED: Pixel = Pixel::from_rgba(0x50, 0x49, 0x45, 0xFF);
+const TEXT_COLOR_FOCUSED: Pixel = Pixel::from_rgba(0x28, 0x28, 0x28, 0xFF);
+const TEXT_COLOR_UNFOCUSED: Pixel = Pixel::from_rgba(0xD5, 0xC4, 0xA1, 0xFF);
+const CLOSE_COLOR: Pixel = Pixel::from_rgba(0xFB, 0x49, 0x34, 0xFF);
+const MAXIMIZE_COLOR: Pixel = Pixel::from_rgba(0xB8, 0xBB, 0x26, 0xFF);
+const MINIMIZE_COLOR: Pixel = Pixel::from_rgba(0xFA, 0xBD, 0x2F, 0xFF);
+const BUTTON_GLYPH_COLOR: Pixel = Pixel::from_rgba(0x28, 0x28, 0x28, 0xFF);
+
+/// The part of a decorated window a point is in, see [`Decorations::hit_test`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecorationHit {
+    /// The title bar excluding the buttons, dragging it moves the window
+    TitleBar,
+    CloseButton,
+    MaximizeButton,
+    MinimizeButton,
+    /// Near an edge or a corner of the window, dragging it resizes the window
+    Resize(ResizeEdge),
+    /// The window's content (the pixels owned by the client)
+    Client,
+}
+
+/// An edge or a corner of a window that can be dragged to resize the window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResizeEdge {
+    Top,
+    Bottom,
+    Left,
+    Right,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl ResizeEdge {
+    /// Returns -1 if dragging the edge moves the left side of the window, 1 if it moves the right side, 0 otherwise
+    pub const fn horizontal(&self) -> isize {
+        match self {
+            Self::Left | Self::TopLeft | Self::BottomLeft => -1,
+            Self::Right | Self::TopRight | Self::BottomRight => 1,
+            Self::Top | Self::Bottom => 0,
+        }
+    }
+
+    /// Returns -1 if dragging the edge moves the top side of the window, 1 if it moves the bottom side, 0 otherwise
+    pub const fn vertical(&self) -> isize {
+        match self {
+            Self::Top | Self::TopLeft | Self::TopRight => -1,
+            Self::Bottom | Self::BottomLeft | Self::BottomRight => 1,
+            Self::Left | Self::Right => 0,
+        }
+    }
+
+    /// Returns the cursor shape to display while the cursor is over (or dragging) the edge
+    pub const fn cursor_shape(&self) -> CursorShape {
+        match self {
+            Self::Left | Self::Right => CursorShape::ResizeHorizontal,
+            Self::Top | Self::Bottom => CursorShape::ResizeVertical,
+            Self::TopLeft | Self::BottomRight => CursorShape::ResizeNwSe,
+            Self::TopRight | Self::BottomLeft => CursorShape::ResizeNeSw,
+        }
+    }
+}
+
+/// A rectangle relative to the top-left corner of a decorated window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameRect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl FrameRect {
+    const fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+/// Server-side decorations drawn around a window: a title bar with the window's title,
+/// close, maximize and minimize buttons and a border.
+///
+/// The decorations are rendered into their own pixel buffer that has the size of the whole window (including the decorations),
+/// only the parts returned by [`Decorations::frame_parts`] should be drawn from it.
+pub struct Decorations {
+    title: String,
+    focused: bool,
+    outer_width: usize,
+    outer_height: usize,
+    pixels: Vec<Pixel>,
+}
+
+impl Decorations {
+    /// Creates decorations around a window with the content size `client_width`*`client_height`.
+    pub fn new(client_width: usize, client_height: usize) -> Self {
+        let mut results = Self {
+            title: String::new(),
+            focused: false,
+            outer_width: 0,
+            outer_height: 0,
+            pixels: Vec::new(),
+        };
+        results.resize(client_width, client_height);
+        results
+    }
+
+    /// The offset of the window's content from the top-left corner of the whole window
+    pub const fn client_offset() -> (usize, usize) {
+        (BORDER_WIDTH, BORDER_WIDTH + TITLE_BAR_HEIGHT)
+    }
+
+    /// The amount of width and height the decorations add to the window's content
+    pub const fn extra_size() -> (usize, usize) {
+        (BORDER_WIDTH * 2, BORDER_WIDTH * 2 + TITLE_BAR_HEIGHT)
+    }
+
+    /// Returns the pixels of the decorations, the size of these pixels is the size of the whole window.
+    pub fn pixels(&self) -> &[Pixel] {
+        &self.pixels
+    }
+
+    /// Returns the width of the whole window including the decorations
+    pub const fn outer_width(&self) -> usize {
+        self.outer_width
+    }
+
+    /// Returns the height of the whole window including the decorations
+    pub const fn outer_height(&self) -> usize {
+        self.outer_height
+    }
+
+    /// Resizes the decorations to fit around a window's content with the size `client_width`*`client_height`.
+    pub fn resize(&mut self, client_width: usize, client_height: usize) {
+        let (extra_width, extra_height) = Self::extra_size();
+        self.outer_width = client_width + extra_width;
+        self.outer_height = client_height + extra_height;
+        self.pixels = vec![Pixel::from_hex(0); self.outer_width * self.outer_height];
+        self.render();
+    }
+
+    /// Sets whether or not the decorated window is focused, returns true if this changed the decorations
+    pub fn set_focused(&mut self, focused: bool) -> bool {
+        if self.focused == focused {
+            return false;
+        }
+
+        self.focused = focused;
+        self.render();
+        true
+    }
+
+    /// Sets the title displayed in the title bar
+    pub fn set_title(&mut self, title: &str) {
+        self.title.clear();
+        self.title.push_str(title);
+        self.render();
+    }
+
+    /// Returns the parts of the window that are covered by the decorations
+    pub const fn frame_parts(&self) -> [FrameRect; 4] {
+        let (client_x, client_y) = Self::client_offset();
+        let client_height = self.outer_height - client_y - BORDER_WIDTH;
+        [
+            // Title bar and top border
+            FrameRect {
+                x: 0,
+                y: 0,
+                width: self.outer_width,
+                height: client_y,
+            },
+            // Left border
+            FrameRect {
+                x: 0,
+                y: client_y,
+                width: client_x,
+                height: client_height,
+            },
+            // Right border
+            FrameRect {
+                x: self.outer_width - BORDER_WIDTH,
+                y: client_y,
+                width: BORDER_WIDTH,
+                height: client_height,
+            },
+            // Bottom border
+            FrameRect {
+                x: 0,
+                y: self.outer_height - BORDER_WIDTH,
+                width: self.outer_width,
+                height: BORDER_WIDTH,
+            },
+        ]
+    }
+
+    /// Returns the rectangle of the nth button from the right of the title bar
+    const fn button_rect(&self, nth: usize) -> FrameRect {
+        let y = BORDER_WIDTH + (TITLE_BAR_HEIGHT - BUTTON_SIZE) / 2;
+        let x = self.outer_width.saturating_sub(
+            BORDER_WIDTH + (BUTTON_SIZE + BUTTON_SPACING) * (nth + 1) - BUTTON_SPACING / 2,
+        );
+        FrameRect {
+            x,
+            y,
+            width: BUTTON_SIZE,
+            height: BUTTON_SIZE,
+        }
+    }
+
+    const fn close_button(&self) -> FrameRect {
+        self.button_rect(0)
+    }
+
+    const fn maximize_button(&self) -> FrameRect {
+        self.button_rect(1)
+    }
+
+    const fn minimize_button(&self) -> FrameRect {
+        self.button_rect(2)
+    }
+
+    /// Returns the part of the window the point (`x`, `y`) relative to the top-left corner of the whole window is in.
+    pub fn hit_test(&self, x: usize, y: usize) -> DecorationHit {
+        let (_, client_y) = Self::client_offset();
+
+        if self.close_button().contains(x, y) {
+            DecorationHit::CloseButton
+        } else if self.maximize_button().contains(x, y) {
+            DecorationHit::MaximizeButton
+        } else if self.minimize_button().contains(x, y) {
+            DecorationHit::MinimizeButton
+        } else if let Some(edge) = self.resize_edge(x, y) {
+            DecorationHit::Resize(edge)
+        } else if y < client_y {
+            DecorationHit::TitleBar
+        } else {
+            DecorationHit::Client
+        }
+    }
+
+    /// Returns the edge or the corner of the window the point (`x`, `y`) relative to the top-left corner of the whole window is near if any
+    const fn resize_edge(&self, x: usize, y: usize) -> Option<ResizeEdge> {
+        let near_vertical_edge =
+            x < RESIZE_MARGIN || x >= self.outer_width.saturating_sub(RESIZE_MARGIN);
+        let near_horizontal_edge =
+            y < RESIZE_MARGIN || y >= self.outer_height.saturating_sub(RESIZE_MARGIN);
+
+        if !near_vertical_edge && !near_horizontal_edge {
+            return None;
+        }
+
+        // Being close to a corner along an edge counts as being on the corner
+        let x_margin = if near_horizontal_edge {
+            RESIZE_CORNER_SIZE
+        } else {
+            RESIZE_MARGIN
+        };
+        let y_margin = if near_vertical_edge {
+            RESIZE_CORNER_SIZE
+        } else {
+            RESIZE_MARGIN
+        };
+
+        let left = x < x_margin;
+        let right = x >= self.outer_width.saturating_sub(x_margin);
+        let top = y < y_margin;
+        let bottom = y >= self.outer_height.saturating_sub(y_margin);
+
+        Some(match (left, right, top, bottom) {
+            (true, _, true, _) => ResizeEdge::TopLeft,
+            (_, true, true, _) => ResizeEdge::TopRight,
+            (true, _, _, true) => ResizeEdge::BottomLeft,
+            (_, true, _, true) => ResizeEdge::BottomRight,
+            (_, _, true, _) => ResizeEdge::Top,
+            (_, _, _, true) => ResizeEdge::Bottom,
+            (true, _, _, _) => ResizeEdge::Left,
+            _ => ResizeEdge::Right,
+        })
+    }
+
+    fn fill_rect(&mut self, rect: FrameRect, pixel: Pixel) {
+        for row in rect.y..(rect.y + rect.height).min(self.outer_height) {
+            let start = row * self.outer_width + rect.x;
+            let end = row * self.outer_width + (rect.x + rect.width).min(self.outer_width);
+            self.pixels[start..end].fill(pixel);
+        }
+    }
+
+    fn put_pixel(&mut self, x: usize, y: usize, pixel: Pixel) {
+        if x < self.outer_width && y < self.outer_height {
+            self.pixels[y * self.outer_width + x] = pixel;
+        }
+    }
+
+    /// Draws a glyph of the builtin font at (`x`, `y`), pixels past `max_x` are not drawn
+    fn draw_glyph(&mut self, c: char, x: usize, y: usize, max_x: usize, pixel: Pixel) {
+        for (row, bits) in font::glyph(c).iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if (bits << col) & 0x80 != 0 && x + col < max_x {
+                    self.put_pixel(x + col, y + row, pixel);
+                }
+            }
+        }
+    }
+
+    /// Redraws the decorations into the pixel buffer
+    fn render(&mut self) {
+        let (title_color, text_color) = if self.focused {
+            (TITLE_COLOR_FOCUSED, TEXT_COLOR_FOCUSED)
+        } else {
+            (TITLE_COLOR_UNFOCUSED, TEXT_COLOR_UNFOCUSED)
+        };
+
+        for part in self.frame_parts() {
+            self.fill_rect(part, BORDER_COLOR);
+        }
+
+        self.fill_rect(
+            FrameRect {
+                x: BORDER_WIDTH,
+                y: BORDER_WIDTH,
+                width: self.outer_width - BORDER_WIDTH * 2,
+                height: TITLE_BAR_HEIGHT,
+            },
+            title_color,
+        );
+
+        // Buttons
+        let close = self.close_button();
+        let maximize = self.maximize_button();
+        let minimize = self.minimize_button();
+
+        self.fill_rect(close, CLOSE_COLOR);
+        self.fill_rect(maximize, MAXIMIZE_COLOR);
+        self.fill_rect(minimize, MINIMIZE_COLOR);
+
+        // A cross on the close button
+        for i in 3..BUTTON_SIZE - 3 {
+            self.put_pixel(close.x + i, close.y + i, BUTTON_GLYPH_COLOR);
+            self.put_pixel(
+                close.x + BUTTON_SIZE - 1 - i,
+                close.y + i,
+                BUTTON_GLYPH_COLOR,
+            );
+        }
+
+        // A square outline on the maximize button
+        for i in 3..BUTTON_SIZE - 3 {
+            self.put_pixel(maximize.x + i, maximize.y + 3, BUTTON_GLYPH_COLOR);
+            self.put_pixel(
+                maximize.x + i,
+                maximize.y + BUTTON_SIZE - 4,
+                BUTTON_GLYPH_COLOR,
+            );
+            self.put_pixel(maximize.x + 3, maximize.y + i, BUTTON_GLYPH_COLOR);
+            self.put_pixel(
+                maximize.x + BUTTON_SIZE - 4,
+                maximize.y + i,
+                BUTTON_GLYPH_COLOR,
+            );
+        }
+
+        // A line on the minimize button
+        for i in 3..BUTTON_SIZE - 3 {
+            self.put_pixel(
+                minimize.x + i,
+                minimize.y + BUTTON_SIZE - 4,
+                BUTTON_GLYPH_COLOR,
+            );
+        }
+
+        // The title, clipped before the buttons
+        let text_y = BORDER_WIDTH + (TITLE_BAR_HEIGHT - GLYPH_HEIGHT) / 2;
+        let max_x = minimize.x.saturating_sub(BUTTON_SPACING);
+        let mut text_x = BORDER_WIDTH + TITLE_PADDING;
+
+        let title = core::mem::take(&mut self.title);
+        for c in title.chars() {
+            if text_x >= max_x {
+                break;
+            }
+
+            self.draw_glyph(c, text_x, text_y, max_x, text_color);
+            text_x += GLYPH_WIDTH;
+        }
+        self.title = title;
+    }
+}
@@ -0,0 +1,306 @@
+pub use opal_abi::fb::AlphaFormat;
+use opal_abi::fb::mul_div_255;
+
+use crate::framebuffer::Pixel;
+
+/// The amount of pixels checked at once for the opaque fast path, see [`blend_over`]
+const BLEND_CHUNK_SIZE: usize = 8;
+
+impl Pixel {
+    /// Converts a pixel with straight alpha to a pixel with premultiplied alpha, see [`AlphaFormat`]
+    #[inline(always)]
+    pub const fn premultiplied(&self) -> Self {
+        Self {
+            blue: mul_div_255(self.blue, self.alpha),
+            green: mul_div_255(self.green, self.alpha),
+            red: mul_div_255(self.red, self.alpha),
+            alpha: self.alpha,
+        }
+    }
+
+    /// Converts a pixel with premultiplied alpha back to a pixel with straight alpha, see [`AlphaFormat`]
+    pub const fn unpremultiplied(&self) -> Self {
+        const fn div(channel: u8, alpha: u8) -> u8 {
+            let channel = (channel as u16 * 255 + alpha as u16 / 2) / alpha as u16;
+            if channel > 255 { 255 } else { channel as u8 }
+        }
+
+        match self.alpha {
+            0 => Self::from_rgba(0, 0, 0, 0),
+            0xFF => *self,
+            alpha => Self {
+                blue: div(self.blue, alpha),
+                green: div(self.green, alpha),
+                red: div(self.red, alpha),
+                alpha,
+            },
+        }
+    }
+
+    /// Linearly interpolates between `self` and `other`, a `t` of 0 returns `self` and a `t` of 255 returns `other`
+    pub const fn lerp(&self, other: &Self, t: u8) -> Self {
+        const fn mix(a: u8, b: u8, t: u8) -> u8 {
+            mul_div_255(a, 255 - t) + mul_div_255(b, t)
+        }
+
+        Self {
+            blue: mix(self.blue, other.blue, t),
+            green: mix(self.green, other.green, t),
+            red: mix(self.red, other.red, t),
+            alpha: mix(self.alpha, other.alpha, t),
+        }
+    }
+
+    /// Composites `self` over `dst` using the Porter-Duff "over" operator, both pixels must use premultiplied alpha
+    #[inline(always)]
+    pub const fn over(&self, dst: &Self) -> Self {
+        let inv_alpha = 255 - self.alpha;
+        // With premultiplied alpha each channel is at most the alpha so this never saturates,
+        // unless a client lies about it's pixels being premultiplied
+        Self {
+            blue: self.blue.saturating_add(mul_div_255(dst.blue, inv_alpha)),
+            green: self.green.saturating_add(mul_div_255(dst.green, inv_alpha)),
+            red: self.red.saturating_add(mul_div_255(dst.red, inv_alpha)),
+            alpha: self.alpha.saturating_add(mul_div_255(dst.alpha, inv_alpha)),
+        }
+    }
+}
+
+/// Composites the `src` pixels that use the alpha format `format` over the premultiplied `dst` pixels,
+/// only as many pixels as the shorter of the two are blended.
+///
+/// Chunks of fully opaque source pixels are copied as is without blending.
+pub fn blend_over(dst: &mut [Pixel], src: &[Pixel], format: AlphaFormat) {
+    let len = dst.len().min(src.len());
+    let (dst, src) = (&mut dst[..len], &src[..len]);
+
+    let mut dst_chunks = dst.chunks_exact_mut(BLEND_CHUNK_SIZE);
+    let mut src_chunks = src.chunks_exact(BLEND_CHUNK_SIZE);
+
+    for (dst, src) in (&mut dst_chunks).zip(&mut src_chunks) {
+        if src.iter().all(Pixel::is_opaque) {
+            dst.copy_from_slice(src);
+            continue;
+        }
+
+        blend_chunk(dst, src, format);
+    }
+
+    blend_chunk(dst_chunks.into_remainder(), src_chunks.remainder(), format);
+}
+
+/// Blends without the opaque fast path, the format is matched once outside of the loops so each loop stays branch-free
+#[inline(always)]
+fn blend_chunk(dst: &mut [Pixel], src: &[Pixel], format: AlphaFormat) {
+    // Blend as many pixels as possible with SIMD, the rest is blended one pixel at a time
+    #[cfg(all(target_arch = "x86_64", target_feature = "sse2"))]
+    let (dst, src) = {
+        let mut dst_lanes = dst.chunks_exact_mut(sse2::LANES);
+        let mut src_lanes = src.chunks_exact(sse2::LANES);
+        for (dst, src) in (&mut dst_lanes).zip(&mut src_lanes) {
+            // Safety: SSE2 is enabled for the whole build, see the `cfg` above
+            unsafe { sse2::blend(dst, src, format) };
+        }
+        (dst_lanes.into_remainder(), src_lanes.remainder())
+    };
+
+    match format {
+        AlphaFormat::Premultiplied => {
+            for (dst, src) in dst.iter_mut().zip(src) {
+                *dst = src.over(dst);
+            }
+        }
+        AlphaFormat::Straight => {
+            for (dst, src) in dst.iter_mut().zip(src) {
+                *dst = src.premultiplied().over(dst);
+            }
+        }
+    }
+}
+
+/// Blending 4 pixels at once with SSE2, gives the exact same results as [`Pixel::over`] and [`Pixel::premultiplied`].
+///
+/// Each channel is widened to 16 bits so the multiplications can't overflow.
+#[cfg(all(target_arch = "x86_64", target_feature = "sse2"))]
+mod sse2 {
+    use core::arch::x86_64::*;
+
+    use super::{AlphaFormat, Pixel};
+
+    /// The amount of pixels blended at once
+    pub const LANES: usize = 4;
+
+    /// Same as [`opal_abi::fb::mul_div_255`] for each 16 bit lane
+    #[inline]
+    #[target_feature(enable = "sse2")]
+    fn mul_div_255(a: __m128i, b: __m128i) -> __m128i {
+        let t = _mm_add_epi16(_mm_mullo_epi16(a, b), _mm_set1_epi16(128));
+        _mm_srli_epi16::<8>(_mm_add_epi16(t, _mm_srli_epi16::<8>(t)))
+    }
+
+    /// Copies the alpha of each of the 2 widened pixels to all 4 of it's channels
+    #[inline]
+    #[target_feature(enable = "sse2")]
+    fn broadcast_alpha(pixels: __m128i) -> __m128i {
+        _mm_shufflehi_epi16::<0xFF>(_mm_shufflelo_epi16::<0xFF>(pixels))
+    }
+
+    /// Multiplies the color channels of 2 widened pixels by their alpha, the alpha itself is kept as is
+    #[inline]
+    #[target_feature(enable = "sse2")]
+    fn premultiply(pixels: __m128i) -> __m128i {
+        // The alpha channel is multiplied by 255 instead, which doesn't change it
+        let alpha_lanes = _mm_set_epi16(0xFF, 0, 0, 0, 0xFF, 0, 0, 0);
+        mul_div_255(pixels, _mm_or_si128(broadcast_alpha(pixels), alpha_lanes))
+    }
+
+    /// Composites the [`LANES`] `src` pixels that use the alpha format `format` over the [`LANES`] premultiplied `dst` pixels
+    #[inline]
+    #[target_feature(enable = "sse2")]
+    pub fn blend(dst: &mut [Pixel], src: &[Pixel], format: AlphaFormat) {
+        assert!(dst.len() == LANES && src.len() == LANES);
+
+        let zero = _mm_setzero_si128();
+        // Safety: both slices are exactly 16 bytes long, see the assertion above
+        let (src, dst_pixels) = unsafe {
+            (
+                _mm_loadu_si128(src.as_ptr().cast()),
+                _mm_loadu_si128(dst.as_ptr().cast()),
+            )
+        };
+
+        let mut src_lo = _mm_unpacklo_epi8(src, zero);
+        let mut src_hi = _mm_unpackhi_epi8(src, zero);
+        if format == AlphaFormat::Straight {
+            src_lo = premultiply(src_lo);
+            src_hi = premultiply(src_hi);
+        }
+
+        let max = _mm_set1_epi16(0xFF);
+        let dst_lo = mul_div_255(
+            _mm_unpacklo_epi8(dst_pixels, zero),
+            _mm_sub_epi16(max, broadcast_alpha(src_lo)),
+        );
+        let dst_hi = mul_div_255(
+            _mm_unpackhi_epi8(dst_pixels, zero),
+            _mm_sub_epi16(max, broadcast_alpha(src_hi)),
+        );
+
+        let results = _mm_adds_epu8(
+            _mm_packus_epi16(src_lo, src_hi),
+            _mm_packus_epi16(dst_lo, dst_hi),
+        );
+        // Safety: same as above
+        unsafe { _mm_storeu_si128(dst.as_mut_ptr().cast(), results) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GREEN: Pixel = Pixel::from_rgba(0, 0xFF, 0, 0xFF);
+    /// The background drawn when there is no wallpaper
+    const BACKGROUND: Pixel = Pixel::from_hex(0xFF28_2828);
+    /// The half-transparent red of the demo windows
+    const HALF_RED: Pixel = Pixel::from_rgba(0xFF, 0, 0, 0xFF / 2);
+
+    #[test]
+    fn mul_div_255_rounds_to_nearest() {
+        for a in 0..=255u8 {
+            for b in 0..=255u8 {
+                let exact = (a as u32 * b as u32 + 127) / 255;
+                assert_eq!(mul_div_255(a, b) as u32, exact, "{a} * {b} / 255");
+            }
+        }
+    }
+
+    #[test]
+    fn premultiplied_golden() {
+        assert_eq!(HALF_RED.premultiplied(), Pixel::from_rgba(0x7F, 0, 0, 0x7F));
+        assert_eq!(
+            Pixel::from_rgba(0x80, 0x40, 0xFF, 0x80).premultiplied(),
+            Pixel::from_rgba(0x40, 0x20, 0x80, 0x80)
+        );
+        assert_eq!(GREEN.premultiplied(), GREEN);
+        assert_eq!(
+            Pixel::from_rgba(0xFF, 0xFF, 0xFF, 0).premultiplied(),
+            Pixel::from_rgba(0, 0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn over_golden() {
+        let red = HALF_RED.premultiplied();
+        assert_eq!(red.over(&GREEN), Pixel::from_rgba(0x7F, 0x80, 0, 0xFF));
+        assert_eq!(
+            red.over(&BACKGROUND),
+            Pixel::from_rgba(0x93, 0x14, 0x14, 0xFF)
+        );
+        assert_eq!(
+            red.over(&Pixel::from_rgba(0, 0, 0, 0)),
+            Pixel::from_rgba(0x7F, 0, 0, 0x7F)
+        );
+        // Transparent pixels leave the destination as is, opaque ones replace it
+        assert_eq!(Pixel::from_rgba(0, 0, 0, 0).over(&BACKGROUND), BACKGROUND);
+        assert_eq!(GREEN.over(&BACKGROUND), GREEN);
+    }
+
+    #[test]
+    fn blend_over_golden() {
+        // Long enough to go through an opaque chunk, a blended chunk and the remainder
+        let src: Vec<Pixel> = [[GREEN; 8], [HALF_RED; 8]]
+            .concat()
+            .into_iter()
+            .chain([HALF_RED, Pixel::from_rgba(0, 0, 0, 0), GREEN])
+            .collect();
+
+        let mut dst = vec![BACKGROUND; src.len()];
+        blend_over(&mut dst, &src, AlphaFormat::Straight);
+        let half_red_over_background = Pixel::from_rgba(0x93, 0x14, 0x14, 0xFF);
+        assert_eq!(dst[..8], [GREEN; 8]);
+        assert_eq!(dst[8..17], [half_red_over_background; 9]);
+        assert_eq!(dst[17..], [BACKGROUND, GREEN]);
+
+        let src: Vec<Pixel> = src.iter().map(Pixel::premultiplied).collect();
+        let mut dst = vec![GREEN; src.len() + 3];
+        blend_over(&mut dst, &src, AlphaFormat::Premultiplied);
+        assert_eq!(dst[8..17], [Pixel::from_rgba(0x7F, 0x80, 0, 0xFF); 9]);
+        // Only as many pixels as the shorter slice are blended
+        assert_eq!(dst[src.len()..], [GREEN; 3]);
+    }
+
+    #[test]
+    fn blend_over_matches_over_for_every_pixel() {
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        let mut random_pixel = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            Pixel::from_hex(state as u32)
+        };
+
+        for format in [AlphaFormat::Straight, AlphaFormat::Premultiplied] {
+            let src: Vec<Pixel> = (0..1021).map(|_| random_pixel()).collect();
+            // Premultiplied sources and destinations never have channels above their alpha
+            let src: Vec<Pixel> = match format {
+                AlphaFormat::Straight => src,
+                AlphaFormat::Premultiplied => src.iter().map(Pixel::premultiplied).collect(),
+            };
+            let dst: Vec<Pixel> = (0..src.len())
+                .map(|_| random_pixel().premultiplied())
+                .collect();
+
+            let mut blended = dst.clone();
+            blend_over(&mut blended, &src, format);
+
+            for ((blended, dst), src) in blended.iter().zip(&dst).zip(&src) {
+                let src = match format {
+                    AlphaFormat::Straight => src.premultiplied(),
+                    AlphaFormat::Premultiplied => *src,
+                };
+                assert_eq!(*blended, src.over(dst), "{src:?} over {dst:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use std::fs::OpenOptions;
+use std::os::safaos::AsRawResource;
+use std::os::safaos::IoUtils;
+
+use safa_api::abi::mem::MemMapFlags;
+use safa_api::syscalls::types::Ri;
+
+use crate::dlog;
+use crate::framebuffer::{DisplayBackend, Pixel};
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+/// A struct represinting information about the virtual framebuffer
+pub struct FramebufferDevInfo {
+    pub width: usize,
+    pub height: usize,
+    /// Bits per pixel, for now the virtual framebuffer always have 32bits per pixel
+    bpp: usize,
+    /// Whether or not each pixel is encoded as BGR and not RGB (always false for now)
+    bgr: bool,
+}
+
+const CMD_RECEIVE_FB_INFO: u16 = 1;
+const CMD_SYNC_PIXELS: u16 = 2;
+
+/// A display backend that draws to the SafaOS virtual framebuffer device at `dev:/fb`
+pub struct DeviceFramebuffer {
+    width: usize,
+    height: usize,
+    /// The mapping lives as long as the process
+    pixels: &'static mut [Pixel],
+    mmap_ri: Ri,
+}
+
+impl DeviceFramebuffer {
+    /// Opens and maps the framebuffer device, panics on failure
+    pub fn open() -> Self {
+        let fb_file = OpenOptions::new()
+            .write(true)
+            .open("dev:/fb")
+            .expect("failed to open the framebuffer");
+        // First we want to receive the framebuffer info
+        let mut fb_info: FramebufferDevInfo = unsafe { core::mem::zeroed() };
+        fb_file
+            .send_command(CMD_RECEIVE_FB_INFO, &raw mut fb_info as usize as u64)
+            .expect("Failed to receive information about the framebuffer");
+
+        assert!(fb_info.bpp == size_of::<u32>() * 8);
+        assert!(!fb_info.bgr);
+
+        dlog!("Got Framebuffer: {fb_info:#?}");
+        let pixels_required = fb_info.height * fb_info.width;
+        let bytes_required = pixels_required * size_of::<Pixel>();
+
+        // The Mapping should live as long as the Process
+        let (mmap_ri, bytes) = safa_api::syscalls::mem::map(
+            core::ptr::null(),
+            bytes_required.div_ceil(4096),
+            0,
+            Some(fb_file.as_raw_resource()),
+            None,
+            MemMapFlags::WRITE,
+        )
+        .expect("Failed to SysMemMap the Framebuffer");
+
+        let pixels = unsafe {
+            std::slice::from_raw_parts_mut(bytes.as_ptr() as *mut u8 as *mut Pixel, pixels_required)
+        };
+
+        Self {
+            width: fb_info.width,
+            height: fb_info.height,
+            pixels,
+            mmap_ri,
+        }
+    }
+}
+
+impl DisplayBackend for DeviceFramebuffer {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn pixels(&self) -> &[Pixel] {
+        self.pixels
+    }
+
+    fn pixels_mut(&mut self) -> &mut [Pixel] {
+        self.pixels
+    }
+
+    fn sync_pixels_rect(&mut self, off_x: usize, off_y: usize, width: usize, height: usize) {
+        #[derive(Debug, Clone, Copy)]
+        #[repr(C)]
+        struct SyncRect {
+            off_x: usize,
+            off_y: usize,
+            width: usize,
+            height: usize,
+        }
+
+        let rect = SyncRect {
+            off_x,
+            off_y,
+            width,
+            height,
+        };
+
+        safa_api::syscalls::io::io_command(
+            self.mmap_ri,
+            CMD_SYNC_PIXELS,
+            (&raw const rect) as usize as u64,
+        )
+        .expect("Failed to Sync framebuffer")
+    }
+}
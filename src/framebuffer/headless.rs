@@ -0,0 +1,72 @@
+use crate::framebuffer::{DisplayBackend, Pixel};
+
+/// A rectangle that was synced to a [`HeadlessBackend`]
+#[cfg(test)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyncedRect {
+    pub off_x: usize,
+    pub off_y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// A display backend that keeps the screen in memory,
+/// used to run the compositor without a display device (for example on a development machine or in CI).
+///
+/// In tests every synced rectangle is recorded, and can be retrieved with [`HeadlessBackend::take_synced_rects`].
+pub struct HeadlessBackend {
+    width: usize,
+    height: usize,
+    pixels: Vec<Pixel>,
+    #[cfg(test)]
+    synced_rects: Vec<SyncedRect>,
+}
+
+impl HeadlessBackend {
+    /// Creates a new headless surface of `width`*`height` pixels, initially filled with zeroes.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Pixel::from_hex(0); width * height],
+            #[cfg(test)]
+            synced_rects: Vec::new(),
+        }
+    }
+
+    /// Takes the rectangles synced so far in the order they were synced in, leaving no recorded rectangles behind.
+    #[cfg(test)]
+    pub fn take_synced_rects(&mut self) -> Vec<SyncedRect> {
+        core::mem::take(&mut self.synced_rects)
+    }
+}
+
+impl DisplayBackend for HeadlessBackend {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn pixels(&self) -> &[Pixel] {
+        &self.pixels
+    }
+
+    fn pixels_mut(&mut self) -> &mut [Pixel] {
+        &mut self.pixels
+    }
+
+    #[cfg_attr(not(test), allow(unused_variables))]
+    fn sync_pixels_rect(&mut self, off_x: usize, off_y: usize, width: usize, height: usize) {
+        // The pixels are already where they belong, there is no device to sync them to
+        #[cfg(test)]
+        self.synced_rects.push(SyncedRect {
+            off_x,
+            off_y,
+            width,
+            height,
+        });
+    }
+}
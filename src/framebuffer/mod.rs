@@ -0,0 +1,231 @@
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+use zerocopy_derive::FromBytes;
+use zerocopy_derive::Immutable;
+use zerocopy_derive::IntoBytes;
+
+use crate::dlog;
+
+pub use blend::AlphaFormat;
+#[cfg(target_os = "safaos")]
+pub use device::DeviceFramebuffer;
+pub use headless::HeadlessBackend;
+
+mod blend;
+#[cfg(target_os = "safaos")]
+mod device;
+mod headless;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, IntoBytes, FromBytes, Immutable)]
+/// Represents a single pixel
+#[repr(C)]
+pub struct Pixel {
+    blue: u8,
+    green: u8,
+    red: u8,
+    alpha: u8,
+}
+
+impl Pixel {
+    /// Construct a Pixel from an RGBA Color
+    pub const fn from_rgba(r: u8, g: u8, b: u8, alpha: u8) -> Self {
+        Self {
+            blue: b,
+            green: g,
+            red: r,
+            alpha,
+        }
+    }
+    /// Construct a Pixel from a hex RGB Color
+    pub const fn from_hex(rgb: u32) -> Self {
+        unsafe { core::mem::transmute(rgb) }
+    }
+
+    /// Returns true if the pixel is fully opaque, blending it over another pixel gives the pixel itself
+    pub const fn is_opaque(&self) -> bool {
+        self.alpha == 0xFF
+    }
+}
+
+/// A display backend the compositor draws to, implementors only have to provide a surface of pixels
+/// and a way to present (sync) a rectangle of that surface to the display.
+pub trait DisplayBackend: Send {
+    /// The width of the surface in pixels
+    fn width(&self) -> usize;
+    /// The height of the surface in pixels
+    fn height(&self) -> usize;
+    /// The pixels of the surface, row by row, must be `width` * `height` long
+    fn pixels(&self) -> &[Pixel];
+    /// Mutable version of [`Self::pixels`]
+    fn pixels_mut(&mut self) -> &mut [Pixel];
+
+    /// Syncs a rectangle of the surface to the display
+    fn sync_pixels_rect(&mut self, off_x: usize, off_y: usize, width: usize, height: usize);
+
+    /// Draws a rectangle with the given pixels
+    /// # Arguments
+    /// - `off_x`: top-left X offset within the framebuffer.
+    /// - `off_y`: top-right Y offset within the framebuffer.
+    /// - `width`: amount of pixels to draw per row.
+    /// - `height`: amount of rows to draw
+    /// - `pixels: the pixels to draw, must be at least `width` * `height` long
+    /// - `format`: the alpha format of `pixels`, the surface's pixels are always premultiplied
+    fn draw_rect(
+        &mut self,
+        off_x: usize,
+        off_y: usize,
+        width: usize,
+        height: usize,
+        pixels: &[Pixel],
+        format: AlphaFormat,
+    ) {
+        let fb_width = self.width();
+        let fb_pixels = self.pixels_mut();
+
+        for row in 0..height {
+            let target_row_index = off_x + ((off_y + row) * fb_width);
+            let src_row_index = row * width;
+
+            if target_row_index + width > fb_pixels.len() {
+                return;
+            }
+
+            let target_pixels = &mut fb_pixels[target_row_index..target_row_index + width];
+            let src_pixels = &pixels[src_row_index..src_row_index + width];
+
+            /* we want to blend the target and the src pixels together, opaque pixels just replace the target */
+            blend::blend_over(target_pixels, src_pixels, format);
+        }
+    }
+
+    /// Similar to [`Self::draw_rect`] but it also takes a position with the pixels to draw
+    /// # Arguments
+    /// Same as [`Self::draw_rect`] with the additional arguments:
+    /// - `pixels_width`: The total width of the given pixels rectangale.
+    /// - `pixels_height`: The total height of the given pixels rectangle.
+    /// - `pixel_rel_x`: The relative X offset within the rectangle in which we start to draw.
+    /// - `pixel_rel_y`: The relative Y offset within the rectangle in which we start to draw.
+    ///
+    /// We will draw to the framebuffer starting from (`off_x`, `off_y`),
+    /// BUT the pixels will start from (`pixel_rel_x`, `pixel_rel_y`) and both these offsets will
+    /// be relative to the given rectangale.
+    fn draw_rect_within(
+        &mut self,
+        off_x: usize,
+        off_y: usize,
+        width: usize,
+        height: usize,
+        pixels: &[Pixel],
+        pixels_width: usize,
+        pixels_height: usize,
+        pixel_rel_x: usize,
+        pixel_rel_y: usize,
+        format: AlphaFormat,
+    ) {
+        assert!(
+            (pixels_width - pixel_rel_x) >= width,
+            "The given pixels rectangle must have width greater than or equal to the requested draw width"
+        );
+        assert!(
+            (pixels_height - pixel_rel_y) >= height,
+            "The given pixels rectangle must have height greater than or equal to the requested draw height"
+        );
+
+        let fb_width = self.width();
+        let height = height.min(self.height() - off_y);
+        let width = width.min(fb_width - off_x);
+        let fb_pixels = self.pixels_mut();
+
+        for row in 0..height {
+            let target_row_index = off_x + ((off_y + row) * fb_width);
+            let src_row_index = pixel_rel_x + ((pixel_rel_y + row) * pixels_width);
+
+            let end_target_row_index = (target_row_index + width).min(fb_pixels.len());
+            let end_src_row_index = (src_row_index + width).min(pixels.len());
+
+            let target_pixels = &mut fb_pixels[target_row_index..end_target_row_index];
+            let src_pixels = &pixels[src_row_index..end_src_row_index];
+
+            /* we want to blend the target and the src pixels together, opaque pixels just replace the target */
+            blend::blend_over(target_pixels, src_pixels, format);
+        }
+    }
+
+    /// Draws a rectangle filled with a pixel `pixel`
+    fn draw_rect_filled_with(
+        &mut self,
+        off_x: usize,
+        off_y: usize,
+        width: usize,
+        height: usize,
+        pixel: Pixel,
+    ) {
+        let fb_width = self.width();
+        let fb_pixels = self.pixels_mut();
+
+        for row in 0..height {
+            let row_index = off_x + ((off_y + row) * fb_width);
+            let pixels = &mut fb_pixels[row_index..row_index + width];
+            pixels.fill(pixel);
+        }
+    }
+
+    /// Syncs the full framebuffer double buffer to the real buffer
+    fn sync_pixels_full(&mut self) {
+        self.sync_pixels_rect(0, 0, self.width(), self.height());
+    }
+}
+
+/// The display backend that is currently in use
+static FRAMEBUFFER: OnceLock<Mutex<Box<dyn DisplayBackend>>> = OnceLock::new();
+
+/// The size of the screen kept in memory when there is no display device, see [`default_backend`]
+#[cfg(not(target_os = "safaos"))]
+const HEADLESS_SCREEN_SIZE: (usize, usize) = (1024, 768);
+
+/// Returns the display backend used if none is set using [`init_with_backend`], the SafaOS framebuffer device
+#[cfg(target_os = "safaos")]
+fn default_backend() -> Box<dyn DisplayBackend> {
+    Box::new(DeviceFramebuffer::open())
+}
+
+/// Returns the display backend used if none is set using [`init_with_backend`],
+/// there is no display device outside of SafaOS so the screen is kept in memory.
+#[cfg(not(target_os = "safaos"))]
+fn default_backend() -> Box<dyn DisplayBackend> {
+    let (width, height) = HEADLESS_SCREEN_SIZE;
+    Box::new(HeadlessBackend::new(width, height))
+}
+
+/// Sets the display backend to use, must be called before anything is drawn,
+/// if never called the [`default_backend`] is used.
+///
+/// Returns false if a backend is already in use.
+pub fn init_with_backend(backend: Box<dyn DisplayBackend>) -> bool {
+    FRAMEBUFFER.set(Mutex::new(backend)).is_ok()
+}
+
+/// Returns a lock on the framebuffer interface
+pub fn framebuffer() -> MutexGuard<'static, Box<dyn DisplayBackend>> {
+    FRAMEBUFFER
+        .get_or_init(|| Mutex::new(default_backend()))
+        .lock()
+        .expect("Failed to acquire lock on framebuffer")
+}
+
+/// Returns the width and the height of the screen in pixels
+pub fn screen_size() -> (usize, usize) {
+    let fb = framebuffer();
+    (fb.width(), fb.height())
+}
+
+pub const BG_PIXEL: Pixel = Pixel::from_hex(0x282828);
+
+/// Clears the screen
+pub fn clear() {
+    let mut fb = framebuffer();
+    let (width, height) = (fb.width(), fb.height());
+    fb.draw_rect_filled_with(0, 0, width, height, BG_PIXEL);
+    fb.sync_pixels_full();
+    dlog!("Cleared screen");
+}
@@ -0,0 +1,822 @@
+use thiserror::Error;
+use zerocopy::{FromBytes, IntoBytes};
+use zerocopy_derive::{FromBytes, Immutable, IntoBytes, KnownLayout, Unaligned};
+
+use super::MAX_IMAGE_PIXELS;
+use crate::{dlog, framebuffer::Pixel};
+
+/// The header located at the start of the bitmap
+#[derive(Debug, Clone, Copy, FromBytes, IntoBytes, Immutable, Unaligned, KnownLayout)]
+#[repr(C, packed)]
+struct BMPHeader {
+    // should be BM
+    magic: [u8; 2],
+    size: u32,
+    _reserved0: [u8; 2],
+    _reserved1: [u8; 2],
+    pixels_off: u32,
+}
+
+const _: () = assert!(size_of::<BMPHeader>() == 14);
+
+const COMPRESS_B_RGB: u32 = 0x0;
+const COMPRESS_B_RLE8: u32 = 0x1;
+const COMPRESS_B_RLE4: u32 = 0x2;
+const COMPRESS_B_BITFIELDS: u32 = 0x3;
+const COMPRESS_B_ALPHABITFIELDS: u32 = 0x6;
+
+/// The size of a BITMAPV3INFOHEADER, the first DIB header that has an alpha bitmask in it
+const V3_HEADER_SIZE: u32 = 56;
+
+/// The most pixels a byte of BI_RLE8 or BI_RLE4 compressed data is allowed to describe,
+/// a run of 255 pixels takes 2 bytes so only images that skip most of their pixels using deltas go over it.
+const MAX_RLE_PIXELS_PER_BYTE: usize = 128;
+
+/// BITMAPINFOHEADER
+/// Located after [`BMPHeader`]
+#[derive(FromBytes, IntoBytes, Immutable, Debug)]
+#[repr(C)]
+struct DIBHeader {
+    size: u32,
+    width: i32,
+    height: i32,
+    color_panels_num: u16,
+    bpp: u16,
+    compression: u32,
+    image_size: u32,
+    horizontal_resolution: i32,
+    vertical_resolution: i32,
+    color_platte_colors: u32,
+    important_colors: u32,
+}
+
+const _: () = assert!(size_of::<DIBHeader>() == 40);
+
+#[derive(Debug, Clone, Copy, Immutable, FromBytes, IntoBytes, KnownLayout)]
+#[repr(C)]
+struct BMPBitmasks {
+    red_channel: u32,
+    green_channel: u32,
+    blue_channel: u32,
+    alpha_channel: u32,
+}
+
+impl Default for BMPBitmasks {
+    fn default() -> Self {
+        // RGB
+        Self {
+            red_channel: 0xFF0000,
+            green_channel: 0x00FF00,
+            blue_channel: 0x0000FF,
+            alpha_channel: 0xFF000000,
+        }
+    }
+}
+
+impl BMPBitmasks {
+    /// The bitmasks of 16 bits per pixel images without the bitfields compression
+    const RGB555: Self = Self {
+        red_channel: 0x7C00,
+        green_channel: 0x03E0,
+        blue_channel: 0x001F,
+        alpha_channel: 0,
+    };
+
+    /// The bitmasks of 24 bits per pixel images, which have no alpha
+    const RGB888: Self = Self {
+        red_channel: 0xFF0000,
+        green_channel: 0x00FF00,
+        blue_channel: 0x0000FF,
+        alpha_channel: 0,
+    };
+
+    /// Returns whether or not every bitmask is a single run of set bits, [`channel`] can't scale the others
+    fn are_contiguous(&self) -> bool {
+        [
+            self.red_channel,
+            self.green_channel,
+            self.blue_channel,
+            self.alpha_channel,
+        ]
+        .iter()
+        .all(|&mask| {
+            mask == 0
+                || mask.count_ones() == u32::BITS - mask.leading_zeros() - mask.trailing_zeros()
+        })
+    }
+
+    /// Converts a pixel value read from the image to a pixel
+    fn pixel(&self, value: u32) -> Pixel {
+        Pixel::from_rgba(
+            channel(value, self.red_channel, 0),
+            channel(value, self.green_channel, 0),
+            channel(value, self.blue_channel, 0),
+            channel(value, self.alpha_channel, 0xFF),
+        )
+    }
+}
+
+/// Returns the channel `mask` selects from the pixel value `value` scaled to 8 bits, or `default` if the mask is empty,
+/// the set bits of `mask` must be contiguous
+fn channel(value: u32, mask: u32, default: u8) -> u8 {
+    if mask == 0 {
+        return default;
+    }
+
+    let bits = mask.count_ones();
+    let raw = (value & mask) >> mask.trailing_zeros();
+    if bits >= 8 {
+        (raw >> (bits - 8)) as u8
+    } else {
+        (raw * 0xFF / ((1 << bits) - 1)).min(0xFF) as u8
+    }
+}
+
+#[derive(Debug, Clone, Copy, Error)]
+pub enum BMPParseError {
+    #[error("Bad BMP Header, unsupported magic value")]
+    UnsupportedMagic,
+    #[error("Bad BMP File Size")]
+    InvalidSize,
+    #[error("Unsupported Bits Per Pixel amount")]
+    UnsupportedBPP,
+    #[error("Unsupported compression kind")]
+    UnsupportedComperssion,
+    #[error("BMP File corrupted")]
+    Corrupted,
+    #[error("Unsupported, reason {0}")]
+    Unsupported(&'static str),
+}
+
+/// How the pixel values stored in the image are converted to pixels
+enum PixelFormat {
+    /// Each value is an index into the color table
+    Indexed(Vec<Pixel>),
+    /// Each value is the color itself, split into channels by the bitmasks
+    Masked(BMPBitmasks),
+}
+
+/// Returns `len` bytes of `slice` starting at `off`
+fn bytes_at(slice: &[u8], off: usize, len: usize) -> Result<&[u8], BMPParseError> {
+    off.checked_add(len)
+        .and_then(|end| slice.get(off..end))
+        .ok_or(BMPParseError::InvalidSize)
+}
+
+/// Returns the pixel value of the `x`th pixel in a row of pixel values that are `bpp` bits each
+fn value_at(row: &[u8], x: usize, bpp: u16) -> u32 {
+    match bpp {
+        1 | 2 | 4 | 8 => {
+            let bit = x * bpp as usize;
+            // The left-most pixel is in the most significant bits
+            let shift = 8 - bpp as usize - bit % 8;
+            ((row[bit / 8] >> shift) & ((1u16 << bpp) - 1) as u8) as u32
+        }
+        16 => u16::from_le_bytes([row[x * 2], row[x * 2 + 1]]) as u32,
+        24 => u32::from_le_bytes([row[x * 3], row[x * 3 + 1], row[x * 3 + 2], 0]),
+        32 => u32::from_le_bytes([row[x * 4], row[x * 4 + 1], row[x * 4 + 2], row[x * 4 + 3]]),
+        _ => unreachable!("the bpp is validated while parsing"),
+    }
+}
+
+/// Decodes BI_RLE8 (`bpp` is 8) or BI_RLE4 (`bpp` is 4) compressed color table indices,
+/// returns the index of each pixel from the bottom row to the top row, `None` for pixels the image skipped.
+fn decode_rle(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    bpp: u16,
+) -> Result<Vec<Option<u8>>, BMPParseError> {
+    let mut indices = vec![None; width * height];
+    let mut put = |x: usize, y: usize, index: u8| {
+        if x < width && y < height {
+            indices[y * width + x] = Some(index);
+        }
+    };
+    // The `n`th index of a run whose bytes are `byte`
+    let nth_index = |byte: u8, n: usize| match bpp {
+        8 => byte,
+        _ if n.is_multiple_of(2) => byte >> 4,
+        _ => byte & 0xF,
+    };
+
+    let (mut x, mut y) = (0usize, 0usize);
+    let mut i = 0;
+    while y < height {
+        let (Some(&count), Some(&value)) = (data.get(i), data.get(i + 1)) else {
+            // Missing the end of bitmap marker, use what we have
+            break;
+        };
+        i += 2;
+
+        if count > 0 {
+            // Encoded mode, `count` pixels repeating `value`
+            for n in 0..count as usize {
+                put(x, y, nth_index(value, n));
+                x += 1;
+            }
+            continue;
+        }
+
+        match value {
+            // End of line
+            0 => {
+                x = 0;
+                y += 1;
+            }
+            // End of bitmap
+            1 => break,
+            // Delta, skip to the right and up
+            2 => {
+                let delta = bytes_at(data, i, 2).map_err(|_| BMPParseError::Corrupted)?;
+                x += delta[0] as usize;
+                y += delta[1] as usize;
+                i += 2;
+            }
+            // Absolute mode, `value` pixels that are stored as is padded to 16 bits
+            count => {
+                let count = count as usize;
+                let len = if bpp == 8 { count } else { count.div_ceil(2) };
+                let run = bytes_at(data, i, len).map_err(|_| BMPParseError::Corrupted)?;
+                for n in 0..count {
+                    let byte = if bpp == 8 { run[n] } else { run[n / 2] };
+                    put(x, y, nth_index(byte, n));
+                    x += 1;
+                }
+                i += len.next_multiple_of(2);
+            }
+        }
+    }
+
+    Ok(indices)
+}
+
+/// A Parsed BMP Image
+pub struct BMPImage {
+    width: u32,
+    height: u32,
+    /// Row by row from the top
+    pixels: Vec<Pixel>,
+}
+
+impl BMPImage {
+    pub const fn width(&self) -> usize {
+        self.width as usize
+    }
+
+    pub const fn height(&self) -> usize {
+        self.height as usize
+    }
+
+    /// Prase a BMP Image from a given byte slice
+    ///
+    /// Supports the BITMAPINFOHEADER and later DIB headers (up to BITMAPV5HEADER), images with 1, 2, 4 or 8 bits per pixel that use a color table,
+    /// images with 16, 24 or 32 bits per pixel, the BI_RLE8, BI_RLE4 and the bitfields compressions and both bottom-up and top-down images.
+    pub fn from_slice(slice: &[u8]) -> Result<Self, BMPParseError> {
+        let header: BMPHeader =
+            BMPHeader::read_from_bytes(bytes_at(slice, 0, size_of::<BMPHeader>())?)
+                .expect("reading a BMPHeader should never fail");
+
+        if header.magic != *b"BM" {
+            return Err(BMPParseError::UnsupportedMagic);
+        }
+
+        if slice.len() < header.size as usize {
+            return Err(BMPParseError::Corrupted);
+        }
+
+        dlog!("BMPHeader is {header:#x?}");
+
+        let dib_off = size_of::<BMPHeader>();
+        let dib_header: DIBHeader =
+            DIBHeader::read_from_bytes(bytes_at(slice, dib_off, size_of::<DIBHeader>())?)
+                .expect("reading DIBHeader should never fail");
+
+        dlog!("DIBHeader is {dib_header:#x?}");
+        if (dib_header.size as usize) < size_of::<DIBHeader>() {
+            return Err(BMPParseError::Unsupported(
+                "OS/2 BMP headers are unsupported",
+            ));
+        }
+
+        let bpp = dib_header.bpp;
+        let compression = dib_header.compression;
+
+        let valid_bpp = match compression {
+            COMPRESS_B_RGB => matches!(bpp, 1 | 2 | 4 | 8 | 16 | 24 | 32),
+            COMPRESS_B_RLE8 => bpp == 8,
+            COMPRESS_B_RLE4 => bpp == 4,
+            COMPRESS_B_BITFIELDS | COMPRESS_B_ALPHABITFIELDS => matches!(bpp, 16 | 32),
+            _ => return Err(BMPParseError::UnsupportedComperssion),
+        };
+
+        if !valid_bpp {
+            return Err(BMPParseError::UnsupportedBPP);
+        }
+
+        // The bitmasks come right after the BITMAPINFOHEADER fields, either as a part of a later DIB header or after the BITMAPINFOHEADER
+        let bitmasks_off = dib_off + size_of::<DIBHeader>();
+        // Only images with a color table use it, and those never have bitmasks
+        let color_table_off = dib_off + dib_header.size as usize;
+
+        let format = match compression {
+            COMPRESS_B_BITFIELDS | COMPRESS_B_ALPHABITFIELDS => {
+                let has_alpha_mask =
+                    compression == COMPRESS_B_ALPHABITFIELDS || dib_header.size >= V3_HEADER_SIZE;
+                let masks_len = if has_alpha_mask { 16 } else { 12 };
+
+                let mut masks_bytes = [0u8; size_of::<BMPBitmasks>()];
+                masks_bytes[..masks_len].copy_from_slice(bytes_at(slice, bitmasks_off, masks_len)?);
+
+                let bitmasks =
+                    BMPBitmasks::read_from_bytes(&masks_bytes).expect("Should never fail");
+
+                dlog!("DIB Bitfield masks are: {bitmasks:#x?}");
+                if !bitmasks.are_contiguous() {
+                    return Err(BMPParseError::Unsupported("Non-contiguous bitmask"));
+                }
+                PixelFormat::Masked(bitmasks)
+            }
+            _ if bpp <= 8 => {
+                let max_colors = 1usize << bpp;
+                let colors = match dib_header.color_platte_colors as usize {
+                    0 => max_colors,
+                    colors => colors.min(max_colors),
+                };
+
+                let table = bytes_at(slice, color_table_off, colors * 4)?;
+                let palette = table
+                    .chunks_exact(4)
+                    .map(|color| Pixel::from_rgba(color[2], color[1], color[0], 0xFF))
+                    .collect();
+                PixelFormat::Indexed(palette)
+            }
+            _ => PixelFormat::Masked(match bpp {
+                16 => BMPBitmasks::RGB555,
+                24 => BMPBitmasks::RGB888,
+                _ => BMPBitmasks::default(),
+            }),
+        };
+
+        let width = dib_header.width;
+        let height = dib_header.height;
+
+        if width.is_negative() {
+            return Err(BMPParseError::Unsupported("Negative Width"));
+        }
+
+        // Images are stored from bottom to top unless the height is negative
+        let top_down = height.is_negative();
+        if top_down && matches!(compression, COMPRESS_B_RLE8 | COMPRESS_B_RLE4) {
+            return Err(BMPParseError::Corrupted);
+        }
+
+        let width = width as usize;
+        let height = height.unsigned_abs() as usize;
+        if width
+            .checked_mul(height)
+            .is_none_or(|pixels| pixels > MAX_IMAGE_PIXELS)
+        {
+            return Err(BMPParseError::Unsupported("Image too large"));
+        }
+
+        let data = slice
+            .get(header.pixels_off as usize..)
+            .ok_or(BMPParseError::InvalidSize)?;
+
+        // Each row is padded to 32 bits
+        let row_len = (width * bpp as usize).div_ceil(8);
+        let stride = row_len.next_multiple_of(4);
+
+        // Make sure the data can describe the whole image before allocating it, so a tiny file can't force a huge allocation
+        let enough_data = match compression {
+            COMPRESS_B_RLE8 | COMPRESS_B_RLE4 => {
+                width * height <= data.len() * MAX_RLE_PIXELS_PER_BYTE
+            }
+            // The padding of the last row may be left out
+            _ => height == 0 || data.len() >= (height - 1) * stride + row_len,
+        };
+        if !enough_data {
+            return Err(BMPParseError::InvalidSize);
+        }
+
+        let mut pixels = Vec::with_capacity(width * height);
+        match (compression, format) {
+            (COMPRESS_B_RLE8 | COMPRESS_B_RLE4, PixelFormat::Indexed(palette)) => {
+                let indices = decode_rle(data, width, height, bpp)?;
+                for row in indices.chunks_exact(width.max(1)).rev() {
+                    for index in row {
+                        let pixel = match index {
+                            Some(index) => *palette
+                                .get(*index as usize)
+                                .ok_or(BMPParseError::Corrupted)?,
+                            // Skipped pixels are transparent
+                            None => Pixel::from_rgba(0, 0, 0, 0),
+                        };
+                        pixels.push(pixel);
+                    }
+                }
+            }
+            (_, format) => {
+                let row_at = |y: usize| bytes_at(data, y * stride, row_len);
+
+                let format = match format {
+                    // 32 bits per pixel images without bitfields usually leave the alpha empty, they are opaque in that case
+                    PixelFormat::Masked(mut bitmasks)
+                        if compression == COMPRESS_B_RGB && bpp == 32 =>
+                    {
+                        let has_alpha = (0..height).try_fold(false, |has_alpha, y| {
+                            Ok(has_alpha || row_at(y)?.chunks_exact(4).any(|pixel| pixel[3] != 0))
+                        })?;
+
+                        if !has_alpha {
+                            bitmasks.alpha_channel = 0;
+                        }
+                        PixelFormat::Masked(bitmasks)
+                    }
+                    format => format,
+                };
+
+                for y in 0..height {
+                    let row = row_at(if top_down { y } else { height - 1 - y })?;
+                    for x in 0..width {
+                        let value = value_at(row, x, bpp);
+                        let pixel = match &format {
+                            PixelFormat::Indexed(palette) => *palette
+                                .get(value as usize)
+                                .ok_or(BMPParseError::Corrupted)?,
+                            PixelFormat::Masked(bitmasks) => bitmasks.pixel(value),
+                        };
+                        pixels.push(pixel);
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            width: width as u32,
+            height: height as u32,
+            pixels,
+        })
+    }
+
+    /// Returns the pixels of the parsed BMP Image, row by row from the top
+    pub fn into_pixels(self) -> Vec<Pixel> {
+        self.pixels
+    }
+}
+
+/// The size of a BITMAPV4HEADER, the DIB header written by [`encode`]
+const V4_HEADER_SIZE: u32 = 108;
+/// `LCS_sRGB`, the color space written by [`encode`]
+const LCS_SRGB: u32 = u32::from_be_bytes(*b"sRGB");
+/// 72 DPI
+const PIXELS_PER_METER: i32 = 2835;
+
+/// Encodes the `width`*`height` `pixels` (straight alpha, row by row from the top) as a 32 bits per pixel BMP image,
+/// the result can be parsed using [`BMPImage::from_slice`].
+pub fn encode(width: usize, height: usize, pixels: &[Pixel]) -> Vec<u8> {
+    assert_eq!(
+        pixels.len(),
+        width * height,
+        "The pixels to encode must have a length of width*height"
+    );
+
+    let pixels_off = size_of::<BMPHeader>() + V4_HEADER_SIZE as usize;
+    let pixels_bytes = size_of_val(pixels);
+
+    let header = BMPHeader {
+        magic: *b"BM",
+        size: (pixels_off + pixels_bytes) as u32,
+        _reserved0: [0; 2],
+        _reserved1: [0; 2],
+        pixels_off: pixels_off as u32,
+    };
+
+    let dib_header = DIBHeader {
+        size: V4_HEADER_SIZE,
+        width: width as i32,
+        height: height as i32,
+        color_panels_num: 1,
+        bpp: 32,
+        compression: COMPRESS_B_BITFIELDS,
+        image_size: pixels_bytes as u32,
+        horizontal_resolution: PIXELS_PER_METER,
+        vertical_resolution: PIXELS_PER_METER,
+        color_platte_colors: 0,
+        important_colors: 0,
+    };
+
+    let mut bytes = Vec::with_capacity(pixels_off + pixels_bytes);
+    bytes.extend_from_slice(header.as_bytes());
+    bytes.extend_from_slice(dib_header.as_bytes());
+    // `Pixel` is laid out the same as the default bitmasks
+    bytes.extend_from_slice(BMPBitmasks::default().as_bytes());
+    // The rest of the BITMAPV4HEADER, the color space followed by the endpoints and the gamma which are unused for sRGB
+    bytes.extend_from_slice(&LCS_SRGB.to_le_bytes());
+    bytes.resize(pixels_off, 0);
+
+    // Rows are stored from bottom to top, rows of 32 bits per pixel never need padding
+    if width != 0 {
+        for row in pixels.chunks_exact(width).rev() {
+            bytes.extend_from_slice(row.as_bytes());
+        }
+    }
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns `width`*`height` pixels that differ in every channel, including the alpha
+    fn gradient(width: usize, height: usize) -> Vec<Pixel> {
+        (0..width * height)
+            .map(|i| {
+                let (x, y) = ((i % width) as u8, (i / width) as u8);
+                Pixel::from_rgba(x * 40, y * 40, x ^ y, 0xFF - x * 20 - y)
+            })
+            .collect()
+    }
+
+    fn round_trip(width: usize, height: usize, pixels: &[Pixel]) {
+        let bytes = encode(width, height, pixels);
+        let image = BMPImage::from_slice(&bytes).unwrap();
+        assert_eq!((image.width(), image.height()), (width, height));
+        assert_eq!(image.into_pixels(), pixels);
+    }
+
+    #[test]
+    fn encode_round_trips() {
+        round_trip(7, 5, &gradient(7, 5));
+        round_trip(1, 1, &[Pixel::from_rgba(0x12, 0x34, 0x56, 0x78)]);
+        // Fully transparent pixels keep their alpha, it isn't mistaken for an image without alpha
+        round_trip(2, 2, &[Pixel::from_rgba(0, 0, 0, 0); 4]);
+        round_trip(0, 3, &[]);
+    }
+
+    /// Returns a BITMAPINFOHEADER without a color table size
+    fn dib(width: i32, height: i32, bpp: u16, compression: u32) -> DIBHeader {
+        DIBHeader {
+            size: size_of::<DIBHeader>() as u32,
+            width,
+            height,
+            color_panels_num: 1,
+            bpp,
+            compression,
+            image_size: 0,
+            horizontal_resolution: PIXELS_PER_METER,
+            vertical_resolution: PIXELS_PER_METER,
+            color_platte_colors: 0,
+            important_colors: 0,
+        }
+    }
+
+    /// Builds a BMP file out of the BITMAPINFOHEADER fields of `dib` followed by `extra`
+    /// (the rest of a later DIB header, the bitmasks or the color table) and the pixel `data`
+    fn bmp(dib: DIBHeader, extra: &[u8], data: &[u8]) -> Vec<u8> {
+        let pixels_off = size_of::<BMPHeader>() + size_of::<DIBHeader>() + extra.len();
+        let header = BMPHeader {
+            magic: *b"BM",
+            size: (pixels_off + data.len()) as u32,
+            _reserved0: [0; 2],
+            _reserved1: [0; 2],
+            pixels_off: pixels_off as u32,
+        };
+
+        [header.as_bytes(), dib.as_bytes(), extra, data].concat()
+    }
+
+    /// Returns a color table of `colors` distinct colors, the `i`th color is [`palette_color`]`(i)`
+    fn palette(colors: u8) -> Vec<u8> {
+        (0..colors)
+            .flat_map(|i| [0xFF - i, i * 2, i * 16, 0])
+            .collect()
+    }
+
+    fn palette_color(i: u8) -> Pixel {
+        Pixel::from_rgba(i * 16, i * 2, 0xFF - i, 0xFF)
+    }
+
+    fn masks(red: u32, green: u32, blue: u32, alpha: u32) -> Vec<u8> {
+        [red, green, blue, alpha]
+            .iter()
+            .flat_map(|mask| mask.to_le_bytes())
+            .collect()
+    }
+
+    fn decode(bytes: &[u8]) -> (usize, usize, Vec<Pixel>) {
+        let image = BMPImage::from_slice(bytes).unwrap();
+        (image.width(), image.height(), image.into_pixels())
+    }
+
+    #[test]
+    fn decodes_1bpp_palettes() {
+        let black = Pixel::from_rgba(0, 0, 0, 0xFF);
+        let white = Pixel::from_rgba(0xFF, 0xFF, 0xFF, 0xFF);
+        let table = [0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0];
+        // Bottom row first, the left-most pixel is in the most significant bit
+        let data = [0xFF, 0xC0, 0, 0, 0xAA, 0x80, 0, 0];
+
+        let (width, height, pixels) = decode(&bmp(dib(10, 2, 1, COMPRESS_B_RGB), &table, &data));
+        assert_eq!((width, height), (10, 2));
+        assert_eq!(pixels[..10], [white, black].repeat(5));
+        assert_eq!(pixels[10..], [white; 10]);
+    }
+
+    #[test]
+    fn decodes_4bpp_and_8bpp_palettes() {
+        let data = [0x12, 0x3F, 0, 0];
+        let (_, _, pixels) = decode(&bmp(dib(4, 1, 4, COMPRESS_B_RGB), &palette(16), &data));
+        assert_eq!(pixels, [1, 2, 3, 15].map(palette_color));
+
+        // Only the colors the header says are used are in the color table
+        let dib_header = DIBHeader {
+            color_platte_colors: 3,
+            ..dib(3, 2, 8, COMPRESS_B_RGB)
+        };
+        let data = [2, 1, 0, 0, 0, 0, 2, 0];
+        let (_, _, pixels) = decode(&bmp(dib_header, &palette(3), &data));
+        assert_eq!(pixels, [0, 0, 2, 2, 1, 0].map(palette_color));
+
+        // An index past the end of the color table
+        let dib_header = DIBHeader {
+            color_platte_colors: 3,
+            ..dib(1, 1, 8, COMPRESS_B_RGB)
+        };
+        let result = BMPImage::from_slice(&bmp(dib_header, &palette(3), &[3, 0, 0, 0]));
+        assert!(matches!(result, Err(BMPParseError::Corrupted)));
+    }
+
+    #[test]
+    fn decodes_16bpp_555_and_565() {
+        let data: Vec<u8> = [0x7C00u16, 0x03E0, 0x001F, 0x4210]
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        let (_, _, pixels) = decode(&bmp(dib(4, 1, 16, COMPRESS_B_RGB), &[], &data));
+        assert_eq!(
+            pixels,
+            [
+                Pixel::from_rgba(0xFF, 0, 0, 0xFF),
+                Pixel::from_rgba(0, 0xFF, 0, 0xFF),
+                Pixel::from_rgba(0, 0, 0xFF, 0xFF),
+                // 5 bit channels are scaled to 8 bits
+                Pixel::from_rgba(0x83, 0x83, 0x83, 0xFF),
+            ]
+        );
+
+        let data: Vec<u8> = [0xF800u16, 0x07E0, 0x0020, 0x001F]
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        let rgb565 = &masks(0xF800, 0x07E0, 0x001F, 0)[..12];
+        let (_, _, pixels) = decode(&bmp(dib(4, 1, 16, COMPRESS_B_BITFIELDS), rgb565, &data));
+        assert_eq!(
+            pixels,
+            [
+                Pixel::from_rgba(0xFF, 0, 0, 0xFF),
+                Pixel::from_rgba(0, 0xFF, 0, 0xFF),
+                Pixel::from_rgba(0, 4, 0, 0xFF),
+                Pixel::from_rgba(0, 0, 0xFF, 0xFF),
+            ]
+        );
+    }
+
+    #[test]
+    fn decodes_rle8() {
+        let transparent = Pixel::from_rgba(0, 0, 0, 0);
+        #[rustfmt::skip]
+        let data = [
+            // Bottom row, a run of 3 then the end of the line
+            3, 1, 0, 0,
+            // Absolute mode with 3 pixels padded to 16 bits, a run of 1 then the end of the line
+            0, 3, 2, 3, 1, 0, 1, 0, 0, 0,
+            // Top row, a delta of 2 pixels to the right, a run of 2 then the end of the bitmap
+            0, 2, 2, 0, 2, 3, 0, 1,
+        ];
+
+        let dib_header = DIBHeader {
+            color_platte_colors: 4,
+            ..dib(4, 3, 8, COMPRESS_B_RLE8)
+        };
+        let (width, height, pixels) = decode(&bmp(dib_header, &palette(4), &data));
+        assert_eq!((width, height), (4, 3));
+        let [c0, c1, c2, c3] = [0, 1, 2, 3].map(palette_color);
+        #[rustfmt::skip]
+        let expected = [
+            transparent, transparent, c3, c3,
+            c2, c3, c1, c0,
+            c1, c1, c1, transparent,
+        ];
+        assert_eq!(pixels, expected);
+    }
+
+    #[test]
+    fn decodes_rle4() {
+        #[rustfmt::skip]
+        let data = [
+            // Bottom row, a run alternating between 2 indices then the end of the line
+            5, 0x12, 0, 0,
+            // Absolute mode with 3 pixels padded to 16 bits, a run of 2 then the end of the bitmap
+            0, 3, 0x34, 0x50, 2, 0x66, 0, 1,
+        ];
+
+        let (_, _, pixels) = decode(&bmp(dib(5, 2, 4, COMPRESS_B_RLE4), &palette(16), &data));
+        assert_eq!(pixels, [3, 4, 5, 6, 6, 1, 2, 1, 2, 1].map(palette_color));
+    }
+
+    #[test]
+    fn decodes_top_down_images() {
+        // Each row is padded to 32 bits
+        let data = [0, 0, 0xFF, 0, 0xFF, 0, 0, 0];
+        let (width, height, pixels) = decode(&bmp(dib(1, -2, 24, COMPRESS_B_RGB), &[], &data));
+        assert_eq!((width, height), (1, 2));
+        assert_eq!(
+            pixels,
+            [
+                Pixel::from_rgba(0xFF, 0, 0, 0xFF),
+                Pixel::from_rgba(0, 0, 0xFF, 0xFF)
+            ]
+        );
+    }
+
+    #[test]
+    fn decodes_v4_and_v5_headers() {
+        let expected = [Pixel::from_rgba(0x30, 0x20, 0x10, 0x40)];
+
+        // The bitmasks are a part of the header, followed by the color space fields
+        let mut v4 = masks(0x00FF_0000, 0x0000_FF00, 0x0000_00FF, 0xFF00_0000);
+        v4.resize(V4_HEADER_SIZE as usize - size_of::<DIBHeader>(), 0);
+        let dib_header = DIBHeader {
+            size: V4_HEADER_SIZE,
+            ..dib(1, 1, 32, COMPRESS_B_BITFIELDS)
+        };
+        let (_, _, pixels) = decode(&bmp(dib_header, &v4, &[0x10, 0x20, 0x30, 0x40]));
+        assert_eq!(pixels, expected);
+
+        let mut v5 = masks(0xFF00_0000, 0x00FF_0000, 0x0000_FF00, 0x0000_00FF);
+        v5.resize(124 - size_of::<DIBHeader>(), 0);
+        let dib_header = DIBHeader {
+            size: 124,
+            ..dib(1, 1, 32, COMPRESS_B_BITFIELDS)
+        };
+        let (_, _, pixels) = decode(&bmp(dib_header, &v5, &[0x40, 0x10, 0x20, 0x30]));
+        assert_eq!(pixels, expected);
+    }
+
+    #[test]
+    fn decodes_32bpp_without_alpha_as_opaque() {
+        let data = [0x10, 0x20, 0x30, 0, 0x40, 0x50, 0x60, 0];
+        let (_, _, pixels) = decode(&bmp(dib(2, 1, 32, COMPRESS_B_RGB), &[], &data));
+        assert_eq!(
+            pixels,
+            [
+                Pixel::from_rgba(0x30, 0x20, 0x10, 0xFF),
+                Pixel::from_rgba(0x60, 0x50, 0x40, 0xFF)
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_images_larger_than_their_data() {
+        let result = BMPImage::from_slice(&bmp(dib(8000, 8000, 32, COMPRESS_B_RGB), &[], &[0; 4]));
+        assert!(matches!(result, Err(BMPParseError::InvalidSize)));
+
+        // The last row is missing a byte
+        let result = BMPImage::from_slice(&bmp(dib(2, 2, 24, COMPRESS_B_RGB), &[], &[0; 13]));
+        assert!(matches!(result, Err(BMPParseError::InvalidSize)));
+        // but it's padding can be left out
+        assert!(BMPImage::from_slice(&bmp(dib(2, 2, 24, COMPRESS_B_RGB), &[], &[0; 14])).is_ok());
+
+        let dib_header = dib(8000, 8000, 8, COMPRESS_B_RLE8);
+        let result = BMPImage::from_slice(&bmp(dib_header, &palette(2), &[0, 1]));
+        assert!(matches!(result, Err(BMPParseError::InvalidSize)));
+    }
+
+    #[test]
+    fn rejects_non_contiguous_bitmasks() {
+        let sparse = masks(0x8000_0001, 0x0000_FF00, 0x00FF_0000, 0);
+        let dib_header = dib(1, 1, 32, COMPRESS_B_ALPHABITFIELDS);
+        let result = BMPImage::from_slice(&bmp(dib_header, &sparse, &[0xFF; 4]));
+        assert!(matches!(result, Err(BMPParseError::Unsupported(_))));
+    }
+
+    #[test]
+    fn encode_writes_consistent_headers() {
+        let bytes = encode(3, 2, &gradient(3, 2));
+        let header = BMPHeader::read_from_bytes(&bytes[..size_of::<BMPHeader>()]).unwrap();
+        let dib_header =
+            DIBHeader::read_from_bytes(&bytes[size_of::<BMPHeader>()..][..size_of::<DIBHeader>()])
+                .unwrap();
+
+        assert_eq!(header.size as usize, bytes.len());
+        assert_eq!(
+            header.pixels_off as usize,
+            size_of::<BMPHeader>() + V4_HEADER_SIZE as usize
+        );
+        assert_eq!(dib_header.image_size, 3 * 2 * 4);
+        assert_eq!((dib_header.width, dib_header.height), (3, 2));
+    }
+}
@@ -0,0 +1,522 @@
+//! A decompressor for zlib (RFC 1950) streams of DEFLATE (RFC 1951) compressed data
+
+use thiserror::Error;
+
+/// The maximum length of a Huffman code in bits
+const MAX_CODE_LEN: u32 = 15;
+
+/// The order the lengths of the code length codes are stored in
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// The base length and the amount of extra bits of each length symbol, starting at 257
+const LENGTHS: [(u16, u8); 29] = [
+    (3, 0),
+    (4, 0),
+    (5, 0),
+    (6, 0),
+    (7, 0),
+    (8, 0),
+    (9, 0),
+    (10, 0),
+    (11, 1),
+    (13, 1),
+    (15, 1),
+    (17, 1),
+    (19, 2),
+    (23, 2),
+    (27, 2),
+    (31, 2),
+    (35, 3),
+    (43, 3),
+    (51, 3),
+    (59, 3),
+    (67, 4),
+    (83, 4),
+    (99, 4),
+    (115, 4),
+    (131, 5),
+    (163, 5),
+    (195, 5),
+    (227, 5),
+    (258, 0),
+];
+
+/// The base distance and the amount of extra bits of each distance symbol
+const DISTANCES: [(u16, u8); 30] = [
+    (1, 0),
+    (2, 0),
+    (3, 0),
+    (4, 0),
+    (5, 1),
+    (7, 1),
+    (9, 2),
+    (13, 2),
+    (17, 3),
+    (25, 3),
+    (33, 4),
+    (49, 4),
+    (65, 5),
+    (97, 5),
+    (129, 6),
+    (193, 6),
+    (257, 7),
+    (385, 7),
+    (513, 8),
+    (769, 8),
+    (1025, 9),
+    (1537, 9),
+    (2049, 10),
+    (3073, 10),
+    (4097, 11),
+    (6145, 11),
+    (8193, 12),
+    (12289, 12),
+    (16385, 13),
+    (24577, 13),
+];
+
+#[derive(Debug, Clone, Copy, Error)]
+pub enum InflateError {
+    #[error("Unexpected end of the compressed data")]
+    UnexpectedEnd,
+    #[error("Unsupported zlib header")]
+    UnsupportedHeader,
+    #[error("Invalid block type")]
+    InvalidBlockType,
+    #[error("Invalid Huffman code")]
+    InvalidCode,
+    #[error("Stored block length doesn't match it's complement")]
+    InvalidStoredLength,
+    #[error("Distance points before the start of the data")]
+    InvalidDistance,
+    #[error("Decompressed data is larger than expected")]
+    TooLarge,
+    #[error("Adler-32 checksum mismatch")]
+    ChecksumMismatch,
+}
+
+/// Reads bits from a byte slice, least significant bit first
+struct BitReader<'a> {
+    data: &'a [u8],
+    /// The index of the next byte to move into `bits`, may go past the end of `data` which reads zeros
+    pos: usize,
+    bits: u64,
+    bits_count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    const fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            bits: 0,
+            bits_count: 0,
+        }
+    }
+
+    fn refill(&mut self) {
+        while self.bits_count <= 56 {
+            let byte = self.data.get(self.pos).copied().unwrap_or(0);
+            self.bits |= (byte as u64) << self.bits_count;
+            self.bits_count += 8;
+            self.pos += 1;
+        }
+    }
+
+    /// Returns the next `count` bits without consuming them
+    fn peek(&mut self, count: u32) -> u32 {
+        if self.bits_count < count {
+            self.refill();
+        }
+        (self.bits & ((1 << count) - 1)) as u32
+    }
+
+    /// Consumes `count` bits, fails if that goes past the end of the data
+    fn consume(&mut self, count: u32) -> Result<(), InflateError> {
+        self.bits >>= count;
+        self.bits_count -= count;
+
+        let consumed = self.pos * 8 - self.bits_count as usize;
+        if consumed > self.data.len() * 8 {
+            return Err(InflateError::UnexpectedEnd);
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, count: u32) -> Result<u32, InflateError> {
+        let bits = self.peek(count);
+        self.consume(count)?;
+        Ok(bits)
+    }
+
+    /// Skips the remaining bits of the current byte
+    fn align_to_byte(&mut self) {
+        let partial = self.bits_count % 8;
+        self.bits >>= partial;
+        self.bits_count -= partial;
+    }
+}
+
+/// A Huffman code decoded using a table of every possible [`MAX_CODE_LEN`] bits long input
+struct Huffman {
+    /// Each entry is the symbol shifted left by 4 ORed with the length of it's code, or 0 if no code matches
+    table: Vec<u16>,
+}
+
+impl Huffman {
+    /// Builds the canonical Huffman code where the code of the `n`th symbol is `lengths[n]` bits long, 0 meaning the symbol is unused
+    fn new(lengths: &[u8]) -> Result<Self, InflateError> {
+        let mut counts = [0u32; MAX_CODE_LEN as usize + 1];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut next_code = [0u32; MAX_CODE_LEN as usize + 1];
+        let mut code = 0;
+        for len in 1..=MAX_CODE_LEN as usize {
+            code = (code + counts[len - 1]) << 1;
+            next_code[len] = code;
+            // More codes of this length than there is room for
+            if code + counts[len] > 1 << len {
+                return Err(InflateError::InvalidCode);
+            }
+        }
+
+        let mut table = vec![0u16; 1 << MAX_CODE_LEN];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len == 0 {
+                continue;
+            }
+
+            let len = len as u32;
+            let code = next_code[len as usize];
+            next_code[len as usize] += 1;
+
+            // Codes are stored starting at their most significant bit, while we read starting at the least significant bit
+            let reversed = code.reverse_bits() >> (32 - len);
+            let entry = ((symbol as u16) << 4) | len as u16;
+            for index in (reversed as usize..table.len()).step_by(1 << len) {
+                table[index] = entry;
+            }
+        }
+
+        Ok(Self { table })
+    }
+
+    /// The code used for literals and lengths in blocks compressed with fixed Huffman codes
+    fn fixed_literals() -> Self {
+        let mut lengths = [0u8; 288];
+        lengths[..144].fill(8);
+        lengths[144..256].fill(9);
+        lengths[256..280].fill(7);
+        lengths[280..].fill(8);
+        Self::new(&lengths).expect("the fixed literal code is valid")
+    }
+
+    /// The code used for distances in blocks compressed with fixed Huffman codes
+    fn fixed_distances() -> Self {
+        Self::new(&[5; 30]).expect("the fixed distance code is valid")
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, InflateError> {
+        let entry = self.table[reader.peek(MAX_CODE_LEN) as usize];
+        if entry == 0 {
+            return Err(InflateError::InvalidCode);
+        }
+
+        reader.consume((entry & 0xF) as u32)?;
+        Ok(entry >> 4)
+    }
+}
+
+/// Reads the Huffman codes of a block compressed with dynamic Huffman codes, returns the literal/length code and the distance code
+fn read_dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), InflateError> {
+    let literals_count = reader.read(5)? as usize + 257;
+    let distances_count = reader.read(5)? as usize + 1;
+    let code_lengths_count = reader.read(4)? as usize + 4;
+
+    let mut code_length_lengths = [0u8; CODE_LENGTH_ORDER.len()];
+    for &symbol in &CODE_LENGTH_ORDER[..code_lengths_count] {
+        code_length_lengths[symbol] = reader.read(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_length_lengths)?;
+
+    // The literal/length and the distance code lengths are encoded as a single sequence
+    let mut lengths = vec![0u8; literals_count + distances_count];
+    let mut i = 0;
+    while i < lengths.len() {
+        let (len, repeat) = match code_lengths.decode(reader)? {
+            len @ 0..=15 => (len as u8, 1),
+            // Repeat the previous length 3-6 times
+            16 => {
+                let previous = *lengths[..i].last().ok_or(InflateError::InvalidCode)?;
+                (previous, 3 + reader.read(2)? as usize)
+            }
+            // Repeat a zero length 3-10 times
+            17 => (0, 3 + reader.read(3)? as usize),
+            // Repeat a zero length 11-138 times
+            18 => (0, 11 + reader.read(7)? as usize),
+            _ => return Err(InflateError::InvalidCode),
+        };
+
+        let run = lengths
+            .get_mut(i..i + repeat)
+            .ok_or(InflateError::InvalidCode)?;
+        run.fill(len);
+        i += repeat;
+    }
+
+    let (literal_lengths, distance_lengths) = lengths.split_at(literals_count);
+    Ok((
+        Huffman::new(literal_lengths)?,
+        Huffman::new(distance_lengths)?,
+    ))
+}
+
+/// Decompresses a block compressed using `literals` and `distances` into `out`
+fn inflate_block(
+    reader: &mut BitReader,
+    out: &mut Vec<u8>,
+    max_len: usize,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), InflateError> {
+    loop {
+        let symbol = literals.decode(reader)?;
+        match symbol {
+            0..=255 => {
+                if out.len() >= max_len {
+                    return Err(InflateError::TooLarge);
+                }
+                out.push(symbol as u8);
+            }
+            256 => return Ok(()),
+            _ => {
+                let &(base, extra) = LENGTHS
+                    .get(symbol as usize - 257)
+                    .ok_or(InflateError::InvalidCode)?;
+                let len = base as usize + reader.read(extra as u32)? as usize;
+
+                let distance_symbol = distances.decode(reader)?;
+                let &(base, extra) = DISTANCES
+                    .get(distance_symbol as usize)
+                    .ok_or(InflateError::InvalidCode)?;
+                let distance = base as usize + reader.read(extra as u32)? as usize;
+
+                if distance > out.len() {
+                    return Err(InflateError::InvalidDistance);
+                }
+                if out.len() + len > max_len {
+                    return Err(InflateError::TooLarge);
+                }
+
+                // The copy may overlap the bytes it produces, so it has to go byte by byte
+                let start = out.len() - distance;
+                for i in start..start + len {
+                    out.push(out[i]);
+                }
+            }
+        }
+    }
+}
+
+/// Returns the Adler-32 checksum of `data`
+pub(super) fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    // The largest amount of bytes that can be summed before `b` could overflow
+    const CHUNK_SIZE: usize = 5552;
+
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(CHUNK_SIZE) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+    (b << 16) | a
+}
+
+/// Decompresses the zlib stream `data`, fails if the decompressed data is larger than `max_len` bytes
+pub fn inflate_zlib(data: &[u8], max_len: usize) -> Result<Vec<u8>, InflateError> {
+    let mut reader = BitReader::new(data);
+
+    let cmf = reader.read(8)?;
+    let flags = reader.read(8)?;
+    // Only DEFLATE is defined, and preset dictionaries aren't allowed in PNG
+    let is_deflate = cmf & 0xF == 8 && cmf >> 4 <= 7;
+    let has_dictionary = flags & 0x20 != 0;
+    if !is_deflate || has_dictionary || ((cmf << 8) | flags) % 31 != 0 {
+        return Err(InflateError::UnsupportedHeader);
+    }
+
+    let mut out = Vec::new();
+    loop {
+        let is_final = reader.read(1)? == 1;
+        match reader.read(2)? {
+            // Stored
+            0 => {
+                reader.align_to_byte();
+                let len = reader.read(16)?;
+                let len_complement = reader.read(16)?;
+                if len != !len_complement & 0xFFFF {
+                    return Err(InflateError::InvalidStoredLength);
+                }
+
+                if out.len() + len as usize > max_len {
+                    return Err(InflateError::TooLarge);
+                }
+                for _ in 0..len {
+                    out.push(reader.read(8)? as u8);
+                }
+            }
+            // Fixed Huffman codes
+            1 => inflate_block(
+                &mut reader,
+                &mut out,
+                max_len,
+                &Huffman::fixed_literals(),
+                &Huffman::fixed_distances(),
+            )?,
+            // Dynamic Huffman codes
+            2 => {
+                let (literals, distances) = read_dynamic_codes(&mut reader)?;
+                inflate_block(&mut reader, &mut out, max_len, &literals, &distances)?;
+            }
+            _ => return Err(InflateError::InvalidBlockType),
+        }
+
+        if is_final {
+            break;
+        }
+    }
+
+    reader.align_to_byte();
+    let mut checksum = 0;
+    for _ in 0..4 {
+        checksum = (checksum << 8) | reader.read(8)?;
+    }
+    if checksum != adler32(&out) {
+        return Err(InflateError::ChecksumMismatch);
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a zlib stream that stores each of `blocks` as is in it's own stored block
+    fn stored_zlib(blocks: &[&[u8]]) -> Vec<u8> {
+        let mut stream = vec![0x78, 0x01];
+        for (i, block) in blocks.iter().enumerate() {
+            let is_final = i == blocks.len() - 1;
+            // The block type (0) comes right after the final bit, the rest of the byte is padding
+            stream.push(is_final as u8);
+            let len = block.len() as u16;
+            stream.extend_from_slice(&len.to_le_bytes());
+            stream.extend_from_slice(&(!len).to_le_bytes());
+            stream.extend_from_slice(block);
+        }
+        stream.extend_from_slice(&adler32(&blocks.concat()).to_be_bytes());
+        stream
+    }
+
+    /// `FIXED_INPUT` compressed with fixed Huffman codes, with back-references
+    const FIXED: [u8; 21] = [
+        0x78, 0xDA, 0xF3, 0x2F, 0x48, 0xCC, 0x51, 0xF0, 0x87, 0x13, 0xE1, 0xBE, 0x3A, 0x30, 0x86,
+        0x22, 0x00, 0x7B, 0xF6, 0x08, 0x66,
+    ];
+    const FIXED_INPUT: &[u8] = b"Opal Opal Opal WM, Opal WM!";
+
+    /// [`dynamic_input`] compressed with dynamic Huffman codes
+    const DYNAMIC: [u8; 126] = [
+        0x78, 0xDA, 0xED, 0xCE, 0x81, 0x0D, 0x03, 0x21, 0x08, 0x40, 0xD1, 0x59, 0x11, 0xA1, 0x2A,
+        0x88, 0xA7, 0x1C, 0xBA, 0x7E, 0xAF, 0x49, 0x97, 0x68, 0xD2, 0x3F, 0xC0, 0xCB, 0x07, 0x80,
+        0x84, 0x99, 0x4B, 0xD3, 0xB1, 0x22, 0x71, 0x33, 0x07, 0xD6, 0x05, 0x2F, 0x0B, 0xEA, 0xC1,
+        0x76, 0xEA, 0xA2, 0x91, 0xF4, 0xC8, 0x96, 0xA3, 0x60, 0x79, 0x96, 0xDD, 0xB3, 0x0B, 0xBA,
+        0x52, 0x8C, 0x8A, 0x71, 0x09, 0xA7, 0x98, 0x26, 0x85, 0x10, 0x76, 0xDC, 0xBE, 0x9E, 0xFC,
+        0x8E, 0x0D, 0x48, 0x45, 0x6C, 0x3E, 0xA2, 0x5C, 0x81, 0x75, 0x04, 0xA9, 0xA3, 0x78, 0xEE,
+        0xBB, 0xCC, 0x6C, 0xF0, 0x05, 0xD3, 0xA0, 0x55, 0x8F, 0x71, 0x74, 0x0A, 0x7B, 0xC1, 0x52,
+        0x06, 0xB7, 0xF6, 0x88, 0x6B, 0x68, 0x2B, 0x9C, 0x31, 0xC1, 0xA7, 0xFF, 0xE0, 0xEF, 0x0D,
+        0xBE, 0x01, 0xF0, 0x7C, 0xFC, 0x3F,
+    ];
+
+    fn dynamic_input() -> Vec<u8> {
+        (0..600u32).map(|i| (i * i / 7 % 23) as u8 + b'a').collect()
+    }
+
+    #[test]
+    fn inflates_stored_blocks() {
+        let stream = stored_zlib(&[b"Hello, ", b"", b"Opal!"]);
+        assert_eq!(inflate_zlib(&stream, 64).unwrap(), b"Hello, Opal!");
+
+        let mut corrupted = stream.clone();
+        // The complement of the first block's length
+        corrupted[5] ^= 1;
+        assert!(matches!(
+            inflate_zlib(&corrupted, 64),
+            Err(InflateError::InvalidStoredLength)
+        ));
+    }
+
+    #[test]
+    fn inflates_fixed_huffman_blocks() {
+        assert_eq!((FIXED[2] >> 1) & 0b11, 1);
+        assert_eq!(inflate_zlib(&FIXED, 64).unwrap(), FIXED_INPUT);
+    }
+
+    #[test]
+    fn inflates_dynamic_huffman_blocks() {
+        assert_eq!((DYNAMIC[2] >> 1) & 0b11, 2);
+        assert_eq!(inflate_zlib(&DYNAMIC, 600).unwrap(), dynamic_input());
+    }
+
+    #[test]
+    fn rejects_invalid_streams() {
+        assert!(matches!(
+            inflate_zlib(&FIXED, FIXED_INPUT.len() - 1),
+            Err(InflateError::TooLarge)
+        ));
+        assert!(matches!(
+            inflate_zlib(&stored_zlib(&[&[0; 10]]), 9),
+            Err(InflateError::TooLarge)
+        ));
+
+        let mut wrong_checksum = FIXED;
+        wrong_checksum[FIXED.len() - 1] ^= 1;
+        assert!(matches!(
+            inflate_zlib(&wrong_checksum, 64),
+            Err(InflateError::ChecksumMismatch)
+        ));
+
+        assert!(matches!(
+            inflate_zlib(&DYNAMIC[..DYNAMIC.len() / 2], 600),
+            Err(InflateError::UnexpectedEnd)
+        ));
+
+        // A preset dictionary
+        assert!(matches!(
+            inflate_zlib(&[0x78, 0xBB, 0, 0, 0, 0], 64),
+            Err(InflateError::UnsupportedHeader)
+        ));
+        // The reserved block type
+        assert!(matches!(
+            inflate_zlib(&[0x78, 0x01, 0b111], 64),
+            Err(InflateError::InvalidBlockType)
+        ));
+    }
+
+    #[test]
+    fn adler32_matches_known_values() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+        // Long enough to need the modulo in between chunks
+        assert_eq!(adler32(&[0xFF; 10_000]), 0xB623_EB2B);
+    }
+}
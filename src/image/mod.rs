@@ -0,0 +1,93 @@
+//! Image decoding, used to load cursors, wallpapers and icons.
+//!
+//! The format of an image is picked by sniffing it's magic bytes, see [`ImageFormat::sniff`].
+
+use thiserror::Error;
+
+use crate::framebuffer::Pixel;
+
+pub use bmp::BMPParseError;
+pub use png::PNGParseError;
+pub use qoi::QOIParseError;
+
+pub mod bmp;
+mod inflate;
+mod png;
+mod qoi;
+
+/// The maximum amount of pixels in an image we are willing to decode
+const MAX_IMAGE_PIXELS: usize = 1 << 26;
+
+/// A supported image format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Bmp,
+    Png,
+    Qoi,
+}
+
+impl ImageFormat {
+    /// Returns the format of the image `bytes` using the magic bytes it starts with, or `None` if the format is unknown
+    pub fn sniff(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(&png::PNG_MAGIC) {
+            Some(Self::Png)
+        } else if bytes.starts_with(&qoi::QOI_MAGIC) {
+            Some(Self::Qoi)
+        } else if bytes.starts_with(b"BM") {
+            Some(Self::Bmp)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Error)]
+pub enum ImageError {
+    #[error("Unknown image format")]
+    UnknownFormat,
+    #[error("Invalid BMP image: {0}")]
+    Bmp(#[from] BMPParseError),
+    #[error("Invalid PNG image: {0}")]
+    Png(#[from] PNGParseError),
+    #[error("Invalid QOI image: {0}")]
+    Qoi(#[from] QOIParseError),
+}
+
+/// A decoded image, with straight alpha
+pub struct Image {
+    width: usize,
+    height: usize,
+    /// Row by row from the top
+    pixels: Vec<Pixel>,
+}
+
+impl Image {
+    /// Decodes an image in any of the supported formats from the given byte slice
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, ImageError> {
+        match ImageFormat::sniff(bytes).ok_or(ImageError::UnknownFormat)? {
+            ImageFormat::Bmp => {
+                let bmp = bmp::BMPImage::from_slice(bytes)?;
+                Ok(Self {
+                    width: bmp.width(),
+                    height: bmp.height(),
+                    pixels: bmp.into_pixels(),
+                })
+            }
+            ImageFormat::Png => Ok(png::decode(bytes)?),
+            ImageFormat::Qoi => Ok(qoi::decode(bytes)?),
+        }
+    }
+
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+
+    pub const fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns an iterator of the pixels in the image, row by row from the top
+    pub fn pixels(&self) -> impl ExactSizeIterator<Item = Pixel> + '_ {
+        self.pixels.iter().copied()
+    }
+}
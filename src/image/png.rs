@@ -0,0 +1,789 @@
+use thiserror::Error;
+
+use super::{
+    Image, MAX_IMAGE_PIXELS,
+    inflate::{InflateError, inflate_zlib},
+};
+use crate::{dlog, framebuffer::Pixel};
+
+/// The signature every PNG file starts with
+pub const PNG_MAGIC: [u8; 8] = *b"\x89PNG\r\n\x1a\n";
+
+const COLOR_GRAYSCALE: u8 = 0;
+const COLOR_RGB: u8 = 2;
+const COLOR_INDEXED: u8 = 3;
+const COLOR_GRAYSCALE_ALPHA: u8 = 4;
+const COLOR_RGBA: u8 = 6;
+
+/// The starting column, the starting row, the column step and the row step of each of the 7 Adam7 interlacing passes
+const ADAM7_PASSES: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+/// The CRC-32 lookup table, used to check each chunk
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut crc = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            crc = if crc & 1 != 0 {
+                0xEDB88320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            k += 1;
+        }
+        table[n] = crc;
+        n += 1;
+    }
+    table
+};
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| {
+        CRC_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+#[derive(Debug, Clone, Copy, Error)]
+pub enum PNGParseError {
+    #[error("Bad PNG File Size")]
+    InvalidSize,
+    #[error("PNG File corrupted")]
+    Corrupted,
+    #[error("Chunk CRC mismatch")]
+    ChecksumMismatch,
+    #[error("Missing the {0} chunk")]
+    MissingChunk(&'static str),
+    #[error("Unsupported color type and bit depth combination")]
+    UnsupportedColorType,
+    #[error("Unsupported, reason {0}")]
+    Unsupported(&'static str),
+    #[error("Failed to decompress the image data: {0}")]
+    Inflate(#[from] InflateError),
+}
+
+/// The IHDR chunk
+#[derive(Debug, Clone, Copy)]
+struct PNGHeader {
+    width: usize,
+    height: usize,
+    bit_depth: u8,
+    color_type: u8,
+    interlaced: bool,
+}
+
+impl PNGHeader {
+    fn parse(data: &[u8]) -> Result<Self, PNGParseError> {
+        if data.len() != 13 {
+            return Err(PNGParseError::Corrupted);
+        }
+
+        let width = u32::from_be_bytes(data[0..4].try_into().unwrap()) as usize;
+        let height = u32::from_be_bytes(data[4..8].try_into().unwrap()) as usize;
+        let (bit_depth, color_type) = (data[8], data[9]);
+        let (compression, filter, interlace) = (data[10], data[11], data[12]);
+
+        if width == 0 || height == 0 {
+            return Err(PNGParseError::Corrupted);
+        }
+        if width
+            .checked_mul(height)
+            .is_none_or(|pixels| pixels > MAX_IMAGE_PIXELS)
+        {
+            return Err(PNGParseError::Unsupported("Image too large"));
+        }
+        if compression != 0 || filter != 0 || interlace > 1 {
+            return Err(PNGParseError::Unsupported(
+                "Unknown compression, filter or interlace method",
+            ));
+        }
+
+        let valid_depths: &[u8] = match color_type {
+            COLOR_GRAYSCALE => &[1, 2, 4, 8, 16],
+            COLOR_INDEXED => &[1, 2, 4, 8],
+            COLOR_RGB | COLOR_GRAYSCALE_ALPHA | COLOR_RGBA => &[8, 16],
+            _ => &[],
+        };
+        if !valid_depths.contains(&bit_depth) {
+            return Err(PNGParseError::UnsupportedColorType);
+        }
+
+        Ok(Self {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            interlaced: interlace == 1,
+        })
+    }
+
+    const fn channels(&self) -> usize {
+        match self.color_type {
+            COLOR_RGB => 3,
+            COLOR_GRAYSCALE_ALPHA => 2,
+            COLOR_RGBA => 4,
+            _ => 1,
+        }
+    }
+
+    const fn bits_per_pixel(&self) -> usize {
+        self.channels() * self.bit_depth as usize
+    }
+
+    /// Returns the length of a row of `width` pixels in bytes, without the filter type byte
+    const fn row_len(&self, width: usize) -> usize {
+        (width * self.bits_per_pixel()).div_ceil(8)
+    }
+}
+
+/// Undoes the filter `filter` of the row `row` in place, `previous` is the already unfiltered row above it (all zeros for the first row),
+/// `bpp` is the amount of bytes per complete pixel rounded up to 1.
+fn unfilter(filter: u8, row: &mut [u8], previous: &[u8], bpp: usize) -> Result<(), PNGParseError> {
+    match filter {
+        // None
+        0 => {}
+        // Sub
+        1 => {
+            for i in bpp..row.len() {
+                row[i] = row[i].wrapping_add(row[i - bpp]);
+            }
+        }
+        // Up
+        2 => {
+            for (byte, above) in row.iter_mut().zip(previous) {
+                *byte = byte.wrapping_add(*above);
+            }
+        }
+        // Average
+        3 => {
+            for i in 0..row.len() {
+                let left = if i >= bpp { row[i - bpp] } else { 0 };
+                let average = ((left as u16 + previous[i] as u16) / 2) as u8;
+                row[i] = row[i].wrapping_add(average);
+            }
+        }
+        // Paeth
+        4 => {
+            for i in 0..row.len() {
+                let (left, upper_left) = if i >= bpp {
+                    (row[i - bpp], previous[i - bpp])
+                } else {
+                    (0, 0)
+                };
+                row[i] = row[i].wrapping_add(paeth(left, previous[i], upper_left));
+            }
+        }
+        _ => return Err(PNGParseError::Corrupted),
+    }
+
+    Ok(())
+}
+
+/// The Paeth predictor, returns whichever of `a` (left), `b` (above) or `c` (upper left) is closest to `a + b - c`
+const fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Returns the `index`th sample in `row` where each sample is `depth` bits, 16 bit samples are big endian
+fn sample(row: &[u8], index: usize, depth: u8) -> u16 {
+    match depth {
+        16 => u16::from_be_bytes([row[index * 2], row[index * 2 + 1]]),
+        8 => row[index] as u16,
+        _ => {
+            let bit = index * depth as usize;
+            // The left-most sample is in the most significant bits
+            let shift = 8 - depth as usize - bit % 8;
+            ((row[bit / 8] >> shift) & ((1u16 << depth) - 1) as u8) as u16
+        }
+    }
+}
+
+/// Scales a `depth` bits sample to 8 bits
+const fn scale(sample: u16, depth: u8) -> u8 {
+    match depth {
+        16 => (sample >> 8) as u8,
+        8 => sample as u8,
+        _ => (sample * 0xFF / ((1 << depth) - 1)) as u8,
+    }
+}
+
+/// Converts the samples of the pixels in the rows of an image to pixels
+struct PixelConverter {
+    header: PNGHeader,
+    /// The palette of indexed images with the alpha from the tRNS chunk applied
+    palette: Vec<Pixel>,
+    /// The raw samples (gray, or red, green and blue) of the only fully transparent color of non-indexed images without an alpha channel
+    transparent: Option<[u16; 3]>,
+}
+
+impl PixelConverter {
+    fn convert(&self, row: &[u8], x: usize) -> Result<Pixel, PNGParseError> {
+        let depth = self.header.bit_depth;
+        let channels = self.header.channels();
+        let sample = |channel: usize| sample(row, x * channels + channel, depth);
+        let scaled = |channel: usize| scale(sample(channel), depth);
+
+        Ok(match self.header.color_type {
+            COLOR_GRAYSCALE => {
+                let gray = sample(0);
+                let alpha = if self.transparent == Some([gray; 3]) {
+                    0
+                } else {
+                    0xFF
+                };
+                let gray = scale(gray, depth);
+                Pixel::from_rgba(gray, gray, gray, alpha)
+            }
+            COLOR_RGB => {
+                let raw = [sample(0), sample(1), sample(2)];
+                let alpha = if self.transparent == Some(raw) {
+                    0
+                } else {
+                    0xFF
+                };
+                Pixel::from_rgba(scaled(0), scaled(1), scaled(2), alpha)
+            }
+            COLOR_INDEXED => *self
+                .palette
+                .get(sample(0) as usize)
+                .ok_or(PNGParseError::Corrupted)?,
+            COLOR_GRAYSCALE_ALPHA => {
+                let gray = scaled(0);
+                Pixel::from_rgba(gray, gray, gray, scaled(1))
+            }
+            COLOR_RGBA => Pixel::from_rgba(scaled(0), scaled(1), scaled(2), scaled(3)),
+            _ => unreachable!("the color type is validated while parsing"),
+        })
+    }
+}
+
+/// Decodes a PNG Image from a given byte slice
+///
+/// Supports every color type and bit depth, transparency from the tRNS chunk and Adam7 interlacing,
+/// 16 bit samples are reduced to 8 bits and ancillary chunks other than tRNS are ignored.
+pub fn decode(slice: &[u8]) -> Result<Image, PNGParseError> {
+    let mut rest = slice
+        .strip_prefix(&PNG_MAGIC)
+        .ok_or(PNGParseError::Corrupted)?;
+
+    let mut header = None;
+    let mut palette: &[u8] = &[];
+    let mut transparency: &[u8] = &[];
+    let mut compressed = Vec::new();
+
+    loop {
+        if rest.len() < 12 {
+            return Err(PNGParseError::InvalidSize);
+        }
+
+        let len = u32::from_be_bytes(rest[0..4].try_into().unwrap()) as usize;
+        let kind: [u8; 4] = rest[4..8].try_into().unwrap();
+        let data = rest.get(8..8 + len).ok_or(PNGParseError::InvalidSize)?;
+        let crc_bytes = rest
+            .get(8 + len..12 + len)
+            .ok_or(PNGParseError::InvalidSize)?;
+        if u32::from_be_bytes(crc_bytes.try_into().unwrap()) != crc32(&rest[4..8 + len]) {
+            return Err(PNGParseError::ChecksumMismatch);
+        }
+        rest = &rest[12 + len..];
+
+        if header.is_none() && kind != *b"IHDR" {
+            return Err(PNGParseError::MissingChunk("IHDR"));
+        }
+
+        match &kind {
+            b"IHDR" if header.is_none() => {
+                let parsed = PNGHeader::parse(data)?;
+                dlog!("PNG IHDR is {parsed:#?}");
+                header = Some(parsed);
+            }
+            b"PLTE" => {
+                if data.len() % 3 != 0 || data.len() > 256 * 3 {
+                    return Err(PNGParseError::Corrupted);
+                }
+                palette = data;
+            }
+            b"tRNS" => transparency = data,
+            b"IDAT" => compressed.extend_from_slice(data),
+            b"IEND" => break,
+            // Chunks that start with an uppercase letter are critical, we can't decode the image if we don't understand them
+            kind if kind[0].is_ascii_uppercase() => {
+                return Err(PNGParseError::Unsupported("Unknown critical chunk"));
+            }
+            _ => {}
+        }
+    }
+
+    let header = header.ok_or(PNGParseError::MissingChunk("IHDR"))?;
+    if compressed.is_empty() {
+        return Err(PNGParseError::MissingChunk("IDAT"));
+    }
+
+    if header.color_type == COLOR_INDEXED && palette.is_empty() {
+        return Err(PNGParseError::MissingChunk("PLTE"));
+    }
+
+    // Each palette entry may have an alpha in the tRNS chunk, entries without one are opaque
+    let palette = palette
+        .chunks_exact(3)
+        .enumerate()
+        .map(|(i, color)| {
+            let alpha = transparency.get(i).copied().unwrap_or(0xFF);
+            Pixel::from_rgba(color[0], color[1], color[2], alpha)
+        })
+        .collect();
+
+    let mut transparent = None;
+    match header.color_type {
+        COLOR_GRAYSCALE if transparency.len() >= 2 => {
+            let gray = u16::from_be_bytes([transparency[0], transparency[1]]);
+            transparent = Some([gray; 3]);
+        }
+        COLOR_RGB if transparency.len() >= 6 => {
+            let channel =
+                |i: usize| u16::from_be_bytes([transparency[i * 2], transparency[i * 2 + 1]]);
+            transparent = Some([channel(0), channel(1), channel(2)]);
+        }
+        _ => {}
+    }
+
+    let passes: &[(usize, usize, usize, usize)] = if header.interlaced {
+        &ADAM7_PASSES
+    } else {
+        &[(0, 0, 1, 1)]
+    };
+
+    // The width and the height of each pass, passes of interlaced images may be empty
+    let pass_sizes = passes.iter().map(|&(x0, y0, dx, dy)| {
+        (
+            header.width.saturating_sub(x0).div_ceil(dx),
+            header.height.saturating_sub(y0).div_ceil(dy),
+        )
+    });
+    let data_len = pass_sizes
+        .clone()
+        .filter(|&(width, height)| width > 0 && height > 0)
+        .map(|(width, height)| height * (1 + header.row_len(width)))
+        .sum();
+
+    let mut data = inflate_zlib(&compressed, data_len)?;
+    if data.len() != data_len {
+        return Err(PNGParseError::InvalidSize);
+    }
+
+    let converter = PixelConverter {
+        header,
+        palette,
+        transparent,
+    };
+
+    let bpp = header.bits_per_pixel().div_ceil(8);
+    let mut pixels = vec![Pixel::from_rgba(0, 0, 0, 0); header.width * header.height];
+    let mut offset = 0;
+
+    for (&(x0, y0, dx, dy), (width, height)) in passes.iter().zip(pass_sizes) {
+        if width == 0 || height == 0 {
+            continue;
+        }
+
+        let row_len = header.row_len(width);
+        let mut previous = vec![0u8; row_len];
+        for y in 0..height {
+            let filter = data[offset];
+            let row = &mut data[offset + 1..offset + 1 + row_len];
+            unfilter(filter, row, &previous, bpp)?;
+
+            for x in 0..width {
+                pixels[(y0 + y * dy) * header.width + x0 + x * dx] = converter.convert(row, x)?;
+            }
+
+            previous.copy_from_slice(row);
+            offset += 1 + row_len;
+        }
+    }
+
+    Ok(Image {
+        width: header.width,
+        height: header.height,
+        pixels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::inflate::adler32;
+
+    fn chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let body = [kind.as_slice(), data].concat();
+        [
+            (data.len() as u32).to_be_bytes().as_slice(),
+            &body,
+            &crc32(&body).to_be_bytes(),
+        ]
+        .concat()
+    }
+
+    /// Returns `data` as a zlib stream made of stored blocks
+    fn zlib_stored(data: &[u8]) -> Vec<u8> {
+        let mut stream = vec![0x78, 0x01];
+        let blocks: Vec<&[u8]> = data.chunks(u16::MAX as usize).collect();
+        for (i, block) in blocks.iter().enumerate() {
+            stream.push((i == blocks.len() - 1) as u8);
+            let len = block.len() as u16;
+            stream.extend_from_slice(&len.to_le_bytes());
+            stream.extend_from_slice(&(!len).to_le_bytes());
+            stream.extend_from_slice(block);
+        }
+        stream.extend_from_slice(&adler32(data).to_be_bytes());
+        stream
+    }
+
+    /// Builds a PNG file out of it's header fields, the chunks `extra` that come before the image data and the filtered `scanlines`
+    fn png(
+        (width, height): (u32, u32),
+        depth: u8,
+        color_type: u8,
+        interlaced: bool,
+        extra: &[Vec<u8>],
+        scanlines: &[u8],
+    ) -> Vec<u8> {
+        let mut header = [width.to_be_bytes(), height.to_be_bytes()].concat();
+        header.extend_from_slice(&[depth, color_type, 0, 0, interlaced as u8]);
+
+        let mut bytes = PNG_MAGIC.to_vec();
+        bytes.extend(chunk(b"IHDR", &header));
+        bytes.extend(extra.concat());
+        bytes.extend(chunk(b"IDAT", &zlib_stored(scanlines)));
+        bytes.extend(chunk(b"IEND", &[]));
+        bytes
+    }
+
+    /// Prefixes each row with the None filter
+    fn unfiltered(rows: &[&[u8]]) -> Vec<u8> {
+        rows.iter().flat_map(|row| [&[0], *row].concat()).collect()
+    }
+
+    fn decode_pixels(bytes: &[u8]) -> Vec<Pixel> {
+        decode(bytes).unwrap().pixels
+    }
+
+    fn gray(value: u8) -> Pixel {
+        Pixel::from_rgba(value, value, value, 0xFF)
+    }
+
+    #[test]
+    fn decodes_every_grayscale_depth() {
+        let cases: [(u8, u32, &[u8], &[u8]); 5] = [
+            (1, 8, &[0b1010_0001], &[0xFF, 0, 0xFF, 0, 0, 0, 0, 0xFF]),
+            (2, 4, &[0b00_01_10_11], &[0, 0x55, 0xAA, 0xFF]),
+            (4, 2, &[0x7F], &[0x77, 0xFF]),
+            (8, 2, &[0x12, 0xFE], &[0x12, 0xFE]),
+            // 16 bit samples are reduced to their most significant byte
+            (16, 1, &[0xAB, 0xCD], &[0xAB]),
+        ];
+
+        for (depth, width, row, expected) in cases {
+            let bytes = png(
+                (width, 1),
+                depth,
+                COLOR_GRAYSCALE,
+                false,
+                &[],
+                &unfiltered(&[row]),
+            );
+            let expected: Vec<Pixel> = expected.iter().copied().map(gray).collect();
+            assert_eq!(decode_pixels(&bytes), expected, "{depth} bits grayscale");
+        }
+    }
+
+    #[test]
+    fn decodes_rgb_and_alpha_color_types() {
+        let rgb = png((1, 1), 8, COLOR_RGB, false, &[], &unfiltered(&[&[1, 2, 3]]));
+        assert_eq!(decode_pixels(&rgb), [Pixel::from_rgba(1, 2, 3, 0xFF)]);
+
+        let row = [0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC];
+        let rgb16 = png((1, 1), 16, COLOR_RGB, false, &[], &unfiltered(&[&row]));
+        assert_eq!(
+            decode_pixels(&rgb16),
+            [Pixel::from_rgba(0x12, 0x56, 0x9A, 0xFF)]
+        );
+
+        let gray_alpha = png(
+            (1, 1),
+            8,
+            COLOR_GRAYSCALE_ALPHA,
+            false,
+            &[],
+            &unfiltered(&[&[9, 7]]),
+        );
+        assert_eq!(decode_pixels(&gray_alpha), [Pixel::from_rgba(9, 9, 9, 7)]);
+
+        let row = [0x90, 0x01, 0x70, 0x02];
+        let gray_alpha16 = png(
+            (1, 1),
+            16,
+            COLOR_GRAYSCALE_ALPHA,
+            false,
+            &[],
+            &unfiltered(&[&row]),
+        );
+        assert_eq!(
+            decode_pixels(&gray_alpha16),
+            [Pixel::from_rgba(0x90, 0x90, 0x90, 0x70)]
+        );
+
+        let rgba = png(
+            (1, 1),
+            8,
+            COLOR_RGBA,
+            false,
+            &[],
+            &unfiltered(&[&[1, 2, 3, 4]]),
+        );
+        assert_eq!(decode_pixels(&rgba), [Pixel::from_rgba(1, 2, 3, 4)]);
+
+        let row = [0x10, 0, 0x20, 0, 0x30, 0, 0x40, 0];
+        let rgba16 = png((1, 1), 16, COLOR_RGBA, false, &[], &unfiltered(&[&row]));
+        assert_eq!(
+            decode_pixels(&rgba16),
+            [Pixel::from_rgba(0x10, 0x20, 0x30, 0x40)]
+        );
+    }
+
+    #[test]
+    fn decodes_every_indexed_depth() {
+        let plte = chunk(b"PLTE", &[0, 0, 0, 10, 20, 30, 40, 50, 60, 70, 80, 90]);
+        let trns = chunk(b"tRNS", &[0, 0x80]);
+        let colors = [
+            Pixel::from_rgba(0, 0, 0, 0),
+            Pixel::from_rgba(10, 20, 30, 0x80),
+            Pixel::from_rgba(40, 50, 60, 0xFF),
+            Pixel::from_rgba(70, 80, 90, 0xFF),
+        ];
+
+        let cases: [(u8, u32, &[u8], &[usize]); 4] = [
+            (1, 2, &[0b1000_0000], &[1, 0]),
+            (2, 4, &[0b00_01_10_11], &[0, 1, 2, 3]),
+            (4, 2, &[0x32], &[3, 2]),
+            (8, 2, &[2, 1], &[2, 1]),
+        ];
+
+        for (depth, width, row, indices) in cases {
+            let bytes = png(
+                (width, 1),
+                depth,
+                COLOR_INDEXED,
+                false,
+                &[plte.clone(), trns.clone()],
+                &unfiltered(&[row]),
+            );
+            let expected: Vec<Pixel> = indices.iter().map(|i| colors[*i]).collect();
+            assert_eq!(decode_pixels(&bytes), expected, "{depth} bits indexed");
+        }
+
+        // An index past the end of the palette
+        let bytes = png(
+            (1, 1),
+            8,
+            COLOR_INDEXED,
+            false,
+            &[plte],
+            &unfiltered(&[&[4]]),
+        );
+        assert!(matches!(decode(&bytes), Err(PNGParseError::Corrupted)));
+    }
+
+    #[test]
+    fn applies_the_transparent_color() {
+        let trns = chunk(b"tRNS", &[0, 0x12]);
+        let bytes = png(
+            (2, 1),
+            8,
+            COLOR_GRAYSCALE,
+            false,
+            &[trns],
+            &unfiltered(&[&[0x12, 0x13]]),
+        );
+        assert_eq!(
+            decode_pixels(&bytes),
+            [Pixel::from_rgba(0x12, 0x12, 0x12, 0), gray(0x13)]
+        );
+
+        let trns = chunk(b"tRNS", &[0, 1, 0, 2, 0, 3]);
+        let rows = unfiltered(&[&[1, 2, 3, 1, 2, 4]]);
+        let bytes = png((2, 1), 8, COLOR_RGB, false, &[trns], &rows);
+        assert_eq!(
+            decode_pixels(&bytes),
+            [
+                Pixel::from_rgba(1, 2, 3, 0),
+                Pixel::from_rgba(1, 2, 4, 0xFF)
+            ]
+        );
+    }
+
+    #[test]
+    fn undoes_every_filter() {
+        // 3 RGBA pixels a row
+        let rows: Vec<Vec<u8>> = (0..5u8)
+            .map(|y| {
+                (0..12u8)
+                    .map(|i| i.wrapping_mul(37) ^ y.wrapping_mul(91))
+                    .collect()
+            })
+            .collect();
+
+        // Filter each row with a different filter, the first row is filtered against a row of zeros
+        let mut scanlines = Vec::new();
+        let zeros = vec![0u8; 12];
+        for (filter, row) in rows.iter().enumerate() {
+            let previous = if filter == 0 {
+                &zeros
+            } else {
+                &rows[filter - 1]
+            };
+            scanlines.push(filter as u8);
+            for i in 0..row.len() {
+                let left = if i >= 4 { row[i - 4] } else { 0 };
+                let upper_left = if i >= 4 { previous[i - 4] } else { 0 };
+                let predicted = match filter {
+                    0 => 0,
+                    1 => left,
+                    2 => previous[i],
+                    3 => ((left as u16 + previous[i] as u16) / 2) as u8,
+                    _ => paeth(left, previous[i], upper_left),
+                };
+                scanlines.push(row[i].wrapping_sub(predicted));
+            }
+        }
+
+        let bytes = png((3, 5), 8, COLOR_RGBA, false, &[], &scanlines);
+        let expected: Vec<Pixel> = rows
+            .concat()
+            .chunks_exact(4)
+            .map(|p| Pixel::from_rgba(p[0], p[1], p[2], p[3]))
+            .collect();
+        assert_eq!(decode_pixels(&bytes), expected);
+
+        let mut unknown_filter = scanlines;
+        unknown_filter[0] = 5;
+        let bytes = png((3, 5), 8, COLOR_RGBA, false, &[], &unknown_filter);
+        assert!(matches!(decode(&bytes), Err(PNGParseError::Corrupted)));
+    }
+
+    #[test]
+    fn decodes_adam7_interlacing() {
+        let pixel =
+            |x: usize, y: usize| [x as u8 * 20, y as u8 * 30, (x ^ y) as u8, 0xFF - x as u8];
+
+        // Both sizes have passes of every size, 2*2 also has empty passes
+        for (width, height) in [(9, 7), (2, 2), (1, 1)] {
+            let mut scanlines = Vec::new();
+            for (x0, y0, dx, dy) in ADAM7_PASSES {
+                for y in (y0..height).step_by(dy) {
+                    let row: Vec<u8> = (x0..width).step_by(dx).flat_map(|x| pixel(x, y)).collect();
+                    if !row.is_empty() {
+                        scanlines.push(0);
+                        scanlines.extend(row);
+                    }
+                }
+            }
+
+            let size = (width as u32, height as u32);
+            let bytes = png(size, 8, COLOR_RGBA, true, &[], &scanlines);
+            let expected: Vec<Pixel> = (0..width * height)
+                .map(|i| {
+                    let [r, g, b, a] = pixel(i % width, i / width);
+                    Pixel::from_rgba(r, g, b, a)
+                })
+                .collect();
+            assert_eq!(decode_pixels(&bytes), expected, "{width}*{height}");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_files() {
+        let valid = png((1, 1), 8, COLOR_GRAYSCALE, false, &[], &unfiltered(&[&[0]]));
+        assert!(decode(&valid).is_ok());
+
+        let mut wrong_crc = valid.clone();
+        let last = wrong_crc.len() - 1;
+        wrong_crc[last] ^= 1;
+        assert!(matches!(
+            decode(&wrong_crc),
+            Err(PNGParseError::ChecksumMismatch)
+        ));
+
+        assert!(matches!(
+            decode(&valid[..valid.len() - 4]),
+            Err(PNGParseError::InvalidSize)
+        ));
+
+        let no_palette = png((1, 1), 8, COLOR_INDEXED, false, &[], &unfiltered(&[&[0]]));
+        assert!(matches!(
+            decode(&no_palette),
+            Err(PNGParseError::MissingChunk("PLTE"))
+        ));
+
+        let unknown_critical = chunk(b"ABCD", &[]);
+        let bytes = png(
+            (1, 1),
+            8,
+            COLOR_GRAYSCALE,
+            false,
+            &[unknown_critical],
+            &unfiltered(&[&[0]]),
+        );
+        assert!(matches!(decode(&bytes), Err(PNGParseError::Unsupported(_))));
+
+        // Ancillary chunks we don't know are skipped
+        let unknown_ancillary = chunk(b"abCD", &[1, 2, 3]);
+        let bytes = png(
+            (1, 1),
+            8,
+            COLOR_GRAYSCALE,
+            false,
+            &[unknown_ancillary],
+            &unfiltered(&[&[0]]),
+        );
+        assert!(decode(&bytes).is_ok());
+
+        // 16 bits per sample indexed images don't exist
+        let bytes = png(
+            (1, 1),
+            16,
+            COLOR_INDEXED,
+            false,
+            &[],
+            &unfiltered(&[&[0, 0]]),
+        );
+        assert!(matches!(
+            decode(&bytes),
+            Err(PNGParseError::UnsupportedColorType)
+        ));
+
+        // Missing a row
+        let bytes = png((1, 2), 8, COLOR_GRAYSCALE, false, &[], &unfiltered(&[&[0]]));
+        assert!(decode(&bytes).is_err());
+    }
+}
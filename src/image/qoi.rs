@@ -0,0 +1,249 @@
+use thiserror::Error;
+
+use super::{Image, MAX_IMAGE_PIXELS};
+use crate::framebuffer::Pixel;
+
+/// The magic bytes every QOI file starts with
+pub const QOI_MAGIC: [u8; 4] = *b"qoif";
+/// The size of the header, the magic followed by the big endian width and height, the amount of channels and the colorspace
+const QOI_HEADER_SIZE: usize = 14;
+
+const QOI_OP_RGB: u8 = 0xFE;
+const QOI_OP_RGBA: u8 = 0xFF;
+const QOI_OP_INDEX: u8 = 0b00;
+const QOI_OP_DIFF: u8 = 0b01;
+const QOI_OP_LUMA: u8 = 0b10;
+const QOI_OP_RUN: u8 = 0b11;
+
+#[derive(Debug, Clone, Copy, Error)]
+pub enum QOIParseError {
+    #[error("Bad QOI File Size")]
+    InvalidSize,
+    #[error("QOI File corrupted")]
+    Corrupted,
+    #[error("Unsupported, reason {0}")]
+    Unsupported(&'static str),
+}
+
+/// Returns the position of a color in the array of previously seen colors
+const fn color_hash([r, g, b, a]: [u8; 4]) -> usize {
+    (r as usize * 3 + g as usize * 5 + b as usize * 7 + a as usize * 11) % 64
+}
+
+/// Decodes a QOI Image from a given byte slice
+///
+/// The colorspace in the header is ignored, both sRGB and linear images are decoded as is.
+pub fn decode(slice: &[u8]) -> Result<Image, QOIParseError> {
+    let header = slice
+        .get(..QOI_HEADER_SIZE)
+        .ok_or(QOIParseError::InvalidSize)?;
+    if header[..4] != QOI_MAGIC {
+        return Err(QOIParseError::Corrupted);
+    }
+
+    let width = u32::from_be_bytes(header[4..8].try_into().unwrap()) as usize;
+    let height = u32::from_be_bytes(header[8..12].try_into().unwrap()) as usize;
+    let channels = header[12];
+    if !matches!(channels, 3 | 4) {
+        return Err(QOIParseError::Corrupted);
+    }
+
+    let pixels_count = width
+        .checked_mul(height)
+        .filter(|&pixels| pixels <= MAX_IMAGE_PIXELS)
+        .ok_or(QOIParseError::Unsupported("Image too large"))?;
+
+    let mut data = slice[QOI_HEADER_SIZE..].iter().copied();
+    let mut next = || data.next().ok_or(QOIParseError::InvalidSize);
+
+    let mut seen = [[0u8; 4]; 64];
+    let mut color = [0, 0, 0, 0xFF];
+    let mut pixels = Vec::with_capacity(pixels_count);
+
+    while pixels.len() < pixels_count {
+        let op = next()?;
+        let mut run = 1;
+
+        match op {
+            QOI_OP_RGB => {
+                color = [next()?, next()?, next()?, color[3]];
+            }
+            QOI_OP_RGBA => {
+                color = [next()?, next()?, next()?, next()?];
+            }
+            _ => match op >> 6 {
+                QOI_OP_INDEX => color = seen[(op & 0x3F) as usize],
+                QOI_OP_DIFF => {
+                    // Each channel differs by -2..=1 from the previous pixel, with a bias of 2
+                    let diff = |shift: u8| ((op >> shift) & 0b11).wrapping_sub(2);
+                    color[0] = color[0].wrapping_add(diff(4));
+                    color[1] = color[1].wrapping_add(diff(2));
+                    color[2] = color[2].wrapping_add(diff(0));
+                }
+                QOI_OP_LUMA => {
+                    // The green channel differs by -32..=31 with a bias of 32,
+                    // the red and blue channels differ by -8..=7 plus the green difference with a bias of 8
+                    let green_diff = (op & 0x3F).wrapping_sub(32);
+                    let other = next()?;
+                    color[0] = color[0]
+                        .wrapping_add(green_diff)
+                        .wrapping_add((other >> 4).wrapping_sub(8));
+                    color[1] = color[1].wrapping_add(green_diff);
+                    color[2] = color[2]
+                        .wrapping_add(green_diff)
+                        .wrapping_add((other & 0xF).wrapping_sub(8));
+                }
+                QOI_OP_RUN => run = (op & 0x3F) as usize + 1,
+                _ => unreachable!(),
+            },
+        }
+
+        seen[color_hash(color)] = color;
+
+        let [r, g, b, a] = color;
+        let remaining = pixels_count - pixels.len();
+        pixels.extend(std::iter::repeat_n(
+            Pixel::from_rgba(r, g, b, a),
+            run.min(remaining),
+        ));
+    }
+
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const END_MARKER: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+
+    fn qoi(width: u32, height: u32, channels: u8, ops: &[u8]) -> Vec<u8> {
+        let mut bytes = QOI_MAGIC.to_vec();
+        bytes.extend_from_slice(&width.to_be_bytes());
+        bytes.extend_from_slice(&height.to_be_bytes());
+        bytes.extend_from_slice(&[channels, 0]);
+        bytes.extend_from_slice(ops);
+        bytes.extend_from_slice(&END_MARKER);
+        bytes
+    }
+
+    fn decode_pixels(bytes: &[u8]) -> Vec<Pixel> {
+        decode(bytes).unwrap().pixels
+    }
+
+    #[test]
+    fn decodes_rgb_and_rgba_ops() {
+        let ops = [
+            QOI_OP_RGB,
+            1,
+            2,
+            3,
+            QOI_OP_RGBA,
+            4,
+            5,
+            6,
+            7,
+            QOI_OP_RGB,
+            8,
+            9,
+            10,
+        ];
+        assert_eq!(
+            decode_pixels(&qoi(3, 1, 4, &ops)),
+            [
+                Pixel::from_rgba(1, 2, 3, 0xFF),
+                Pixel::from_rgba(4, 5, 6, 7),
+                // The alpha is kept from the previous pixel
+                Pixel::from_rgba(8, 9, 10, 7),
+            ]
+        );
+    }
+
+    #[test]
+    fn decodes_index_ops() {
+        let first = [10, 20, 30, 0xFF];
+        let second = [40, 50, 60, 0xFF];
+        let ops = [
+            QOI_OP_RGB,
+            10,
+            20,
+            30,
+            QOI_OP_RGB,
+            40,
+            50,
+            60,
+            (QOI_OP_INDEX << 6) | color_hash(first) as u8,
+            (QOI_OP_INDEX << 6) | color_hash(second) as u8,
+        ];
+        let [a, b] = [first, second].map(|[r, g, b, a]| Pixel::from_rgba(r, g, b, a));
+        assert_eq!(decode_pixels(&qoi(4, 1, 3, &ops)), [a, b, a, b]);
+    }
+
+    #[test]
+    fn decodes_diff_and_luma_ops() {
+        let ops = [
+            QOI_OP_RGB,
+            100,
+            100,
+            100,
+            // -2, -1 and +1 with a bias of 2
+            (QOI_OP_DIFF << 6) | 0b00_01_11,
+            // Wraps around
+            QOI_OP_RGB,
+            0,
+            0xFF,
+            0,
+            (QOI_OP_DIFF << 6) | 0b00_11_10,
+            // Green +10, red -3 and blue +7 relative to the green difference
+            (QOI_OP_LUMA << 6) | (32 + 10),
+            ((8 - 3) << 4) | (8 + 7),
+        ];
+        assert_eq!(
+            decode_pixels(&qoi(5, 1, 3, &ops)),
+            [
+                Pixel::from_rgba(100, 100, 100, 0xFF),
+                Pixel::from_rgba(98, 99, 101, 0xFF),
+                Pixel::from_rgba(0, 0xFF, 0, 0xFF),
+                Pixel::from_rgba(0xFE, 0, 0, 0xFF),
+                Pixel::from_rgba(5, 10, 17, 0xFF),
+            ]
+        );
+    }
+
+    #[test]
+    fn decodes_run_ops() {
+        let red = Pixel::from_rgba(0xFF, 0, 0, 0xFF);
+        // Runs start from opaque black before the first pixel, and are cut at the end of the image
+        let ops = [
+            (QOI_OP_RUN << 6) | 1,
+            QOI_OP_RGB,
+            0xFF,
+            0,
+            0,
+            (QOI_OP_RUN << 6) | 61,
+        ];
+        let pixels = decode_pixels(&qoi(2, 3, 3, &ops));
+        let black = Pixel::from_rgba(0, 0, 0, 0xFF);
+        assert_eq!(pixels, [black, black, red, red, red, red]);
+    }
+
+    #[test]
+    fn rejects_invalid_files() {
+        assert!(matches!(
+            decode(&qoi(2, 1, 3, &[QOI_OP_RGB, 1, 2])[..QOI_HEADER_SIZE + 3]),
+            Err(QOIParseError::InvalidSize)
+        ));
+        assert!(matches!(
+            decode(&qoi(1, 1, 5, &[QOI_OP_RGB, 1, 2, 3])),
+            Err(QOIParseError::Corrupted)
+        ));
+        assert!(matches!(
+            decode(&qoi(u32::MAX, u32::MAX, 4, &[])),
+            Err(QOIParseError::Unsupported(_))
+        ));
+    }
+}
@@ -0,0 +1,415 @@
+use std::{
+    fs::File,
+    io::{BufReader, ErrorKind, Read},
+};
+
+use opal_abi::{
+    com::response::event::{Event, KeyEvent, KeyModifiers},
+    keysym::{self, Keysym},
+};
+use safa_api::syscalls::types::Ri;
+use zerocopy::FromBytes;
+use zerocopy_derive::{FromBytes, Immutable, KnownLayout};
+
+use crate::{
+    capture,
+    com::listener,
+    config::{Action, KeyBinding},
+    dlog,
+    logging::{self, LogLevel},
+    poll,
+    window::{WINDOWS, WinID, Windows},
+};
+
+/// A key event as read from the keyboard device
+#[derive(Debug, Clone, Copy, FromBytes, Immutable, KnownLayout)]
+#[repr(C)]
+struct RawKeyEvent {
+    /// A PS/2 (set 1) scancode, extended keys (the ones prefixed with 0xE0) have their prefix in the high byte
+    scancode: u16,
+    /// Non-zero if the key was released, zero if it was pressed
+    released: u8,
+    _reserved: u8,
+}
+
+const EXTENDED: u16 = 0xE000;
+
+/// The characters produced by each scancode in the US layout, indexed by the scancode, `\0` if the key isn't printable
+const US_LAYOUT: &[u8] = b"\0\x001234567890-=\0\0qwertyuiop[]\0\0asdfghjkl;'`\0\\zxcvbnm,./\0*\0 ";
+/// Same as [`US_LAYOUT`] but with shift held
+const US_LAYOUT_SHIFTED: &[u8] =
+    b"\0\0!@#$%^&*()_+\0\0QWERTYUIOP{}\0\0ASDFGHJKL:\"~\0|ZXCVBNM<>?\0*\0 ";
+
+const _: () = assert!(US_LAYOUT.len() == US_LAYOUT_SHIFTED.len());
+
+/// Converts a scancode to it's layout independent key symbol
+fn scancode_to_keysym(scancode: u16) -> Keysym {
+    match scancode {
+        0x01 => keysym::ESCAPE,
+        0x0E => keysym::BACKSPACE,
+        0x0F => keysym::TAB,
+        0x1C | 0xE01C => keysym::ENTER,
+        0x1D => keysym::LEFT_CTRL,
+        0x2A => keysym::LEFT_SHIFT,
+        0x36 => keysym::RIGHT_SHIFT,
+        0x38 => keysym::LEFT_ALT,
+        0x3A => keysym::CAPS_LOCK,
+        0x3B..=0x44 => keysym::F1 + (scancode - 0x3B) as Keysym,
+        0x45 => keysym::NUM_LOCK,
+        0x46 => keysym::SCROLL_LOCK,
+        0x57 => keysym::F1 + 10,
+        0x58 => keysym::F12,
+        0xE01D => keysym::RIGHT_CTRL,
+        0xE035 => '/' as Keysym,
+        0xE037 => keysym::PRINT_SCREEN,
+        0xE038 => keysym::RIGHT_ALT,
+        0xE047 => keysym::HOME,
+        0xE048 => keysym::UP,
+        0xE049 => keysym::PAGE_UP,
+        0xE04B => keysym::LEFT,
+        0xE04D => keysym::RIGHT,
+        0xE04F => keysym::END,
+        0xE050 => keysym::DOWN,
+        0xE051 => keysym::PAGE_DOWN,
+        0xE052 => keysym::INSERT,
+        0xE053 => keysym::DELETE,
+        0xE05B => keysym::LEFT_SUPER,
+        0xE05C => keysym::RIGHT_SUPER,
+        code if code < EXTENDED => US_LAYOUT
+            .get(code as usize)
+            .map_or(keysym::NONE, |c| *c as Keysym),
+        _ => keysym::NONE,
+    }
+}
+
+/// The modifier keys that are held and the locks that are toggled on
+#[derive(Debug, Default)]
+struct ModifierKeys {
+    /// Which modifier keys are currently held, indexed by `keysym - keysym::LEFT_SHIFT`
+    held: [bool; 8],
+    caps_lock: bool,
+    num_lock: bool,
+}
+
+impl ModifierKeys {
+    /// Returns the modifiers that are currently active
+    fn modifiers(&self) -> KeyModifiers {
+        let held = |left: Keysym, right: Keysym| {
+            self.held[(left - keysym::LEFT_SHIFT) as usize]
+                || self.held[(right - keysym::LEFT_SHIFT) as usize]
+        };
+
+        let mut modifiers = KeyModifiers::empty();
+        modifiers.set(
+            KeyModifiers::SHIFT,
+            held(keysym::LEFT_SHIFT, keysym::RIGHT_SHIFT),
+        );
+        modifiers.set(
+            KeyModifiers::CTRL,
+            held(keysym::LEFT_CTRL, keysym::RIGHT_CTRL),
+        );
+        modifiers.set(KeyModifiers::ALT, held(keysym::LEFT_ALT, keysym::RIGHT_ALT));
+        modifiers.set(
+            KeyModifiers::SUPER,
+            held(keysym::LEFT_SUPER, keysym::RIGHT_SUPER),
+        );
+        modifiers.set(KeyModifiers::CAPS_LOCK, self.caps_lock);
+        modifiers.set(KeyModifiers::NUM_LOCK, self.num_lock);
+        modifiers
+    }
+
+    /// Updates the modifiers with the key event `raw` and converts it to the event sent to the focused window
+    fn key_event(&mut self, raw: RawKeyEvent) -> Event {
+        let pressed = raw.released == 0;
+        let keysym = scancode_to_keysym(raw.scancode);
+
+        match keysym {
+            keysym::LEFT_SHIFT..=keysym::RIGHT_SUPER => {
+                self.held[(keysym - keysym::LEFT_SHIFT) as usize] = pressed
+            }
+            keysym::CAPS_LOCK if pressed => self.caps_lock = !self.caps_lock,
+            keysym::NUM_LOCK if pressed => self.num_lock = !self.num_lock,
+            _ => {}
+        }
+
+        let modifiers = self.modifiers();
+        if pressed {
+            let text = text_of(raw.scancode, modifiers);
+            Event::KeyPress(KeyEvent::new(raw.scancode as u32, keysym, modifiers, text))
+        } else {
+            Event::KeyRelease(KeyEvent::new(raw.scancode as u32, keysym, modifiers, None))
+        }
+    }
+}
+
+/// Returns the text produced by pressing the key with the scancode `scancode` given the current `modifiers`
+fn text_of(scancode: u16, modifiers: KeyModifiers) -> Option<char> {
+    if modifiers.intersects(KeyModifiers::CTRL | KeyModifiers::ALT | KeyModifiers::SUPER) {
+        return None;
+    }
+
+    let index = scancode as usize;
+    let c = *US_LAYOUT.get(index)?;
+    if c == 0 {
+        return None;
+    }
+
+    let shifted = if c.is_ascii_alphabetic() {
+        modifiers.contains(KeyModifiers::SHIFT) != modifiers.contains(KeyModifiers::CAPS_LOCK)
+    } else {
+        modifiers.contains(KeyModifiers::SHIFT)
+    };
+
+    if shifted {
+        Some(US_LAYOUT_SHIFTED[index] as char)
+    } else {
+        Some(c as char)
+    }
+}
+
+/// Runs the action of the first of `bindings` pressing a key triggers, or delivers the key event `event` to the focused window otherwise,
+/// returns the ID of the window the event was delivered to if any.
+fn route(bindings: &[KeyBinding], windows: &mut Windows, event: Event) -> Option<WinID> {
+    // WM key bindings, not sent to the focused window
+    if let Event::KeyPress(key) = &event
+        && let Some(binding) = bindings
+            .iter()
+            .find(|binding| binding.matches(key.keysym(), key.modifiers()))
+    {
+        Keyboard::run(windows, binding.action());
+        return None;
+    }
+
+    /* It is ok the focused window might be gone by now */
+    let focused_id = windows.focused_window()?;
+    windows.send_event(focused_id, event).ok()?;
+    Some(focused_id)
+}
+
+/// The keyboard, reads key events and delivers them to the focused window.
+pub struct Keyboard {
+    modifier_keys: ModifierKeys,
+    /// The key combinations that run an action instead of being sent to the focused window
+    bindings: Vec<KeyBinding>,
+    reader: BufReader<File>,
+}
+
+impl Keyboard {
+    /// Creates a new Keyboard instance that handles the key bindings `bindings`
+    pub fn create(bindings: Vec<KeyBinding>) -> Self {
+        let file = File::open("dev:/inkey").expect("Failed to open the Keyboard Device");
+        let reader = BufReader::with_capacity(size_of::<RawKeyEvent>(), file);
+
+        Self {
+            modifier_keys: ModifierKeys::default(),
+            bindings,
+            reader,
+        }
+    }
+
+    /// Runs the action of a key binding
+    fn run(windows: &mut Windows, action: &Action) {
+        let focused = windows.focused_window();
+        /* It is ok if there is no focused window, or if it is gone by now */
+        match action {
+            Action::RestoreMinimized => _ = windows.restore_last_minimized(),
+            Action::Screenshot => capture::save_screenshot(),
+            Action::CloseWindow => {
+                if let Some(id) = focused {
+                    _ = windows.send_event(id, Event::CloseRequested);
+                }
+            }
+            Action::MinimizeWindow => {
+                if let Some(id) = focused {
+                    _ = windows.minimize(id);
+                }
+            }
+            Action::MaximizeWindow => {
+                if let Some(id) = focused {
+                    _ = windows.toggle_maximize(id);
+                }
+            }
+            Action::Spawn(command_line) => listener::spawn(command_line),
+        }
+    }
+
+    /// Returns the raw resource of the keyboard device, it becomes readable once a key event is available
+    pub fn raw_resource(&self) -> Ri {
+        poll::raw_resource(self.reader.get_ref())
+    }
+
+    /// Handles one key event if available, returns false if there were no events to handle.
+    ///
+    /// Only call this once the keyboard device is readable (see [`Self::raw_resource`]), otherwise the read may block.
+    pub fn handle_event(&mut self) -> bool {
+        let mut event_bytes = [0u8; size_of::<RawKeyEvent>()];
+        let len = match self.reader.read(&mut event_bytes) {
+            Ok(len) => len,
+            Err(err) if err.kind() == ErrorKind::WouldBlock => 0,
+            Err(err) => panic!("Failed to read a key event: {err}"),
+        };
+
+        if len == 0 {
+            return false;
+        }
+
+        assert_eq!(len, size_of::<RawKeyEvent>());
+
+        let raw = RawKeyEvent::read_from_bytes(&event_bytes)
+            .expect("reading a RawKeyEvent should never fail");
+        let event = self.modifier_keys.key_event(raw);
+
+        if logging::log_enabled(LogLevel::Verbose) {
+            dlog!("Got a key event {event:?}");
+        }
+
+        let mut windows = WINDOWS.lock().expect("failed to get lock on windows");
+        route(&self.bindings, &mut windows, event);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        framebuffer::Pixel,
+        window::{Window, WindowKind},
+    };
+
+    const A: u16 = 0x1E;
+    const ONE: u16 = 0x02;
+    const LEFT_SHIFT: u16 = 0x2A;
+    const LEFT_CTRL: u16 = 0x1D;
+    const CAPS_LOCK: u16 = 0x3A;
+    const LEFT_SUPER: u16 = 0xE05B;
+    const M: u16 = 0x32;
+
+    fn raw(scancode: u16, released: bool) -> RawKeyEvent {
+        RawKeyEvent {
+            scancode,
+            released: released as u8,
+            _reserved: 0,
+        }
+    }
+
+    /// Presses and releases the key `scancode`, returns the key event of the press
+    fn tap(modifier_keys: &mut ModifierKeys, scancode: u16) -> KeyEvent {
+        let Event::KeyPress(key) = modifier_keys.key_event(raw(scancode, false)) else {
+            panic!("A press should be a KeyPress");
+        };
+        assert!(matches!(
+            modifier_keys.key_event(raw(scancode, true)),
+            Event::KeyRelease(_)
+        ));
+        key
+    }
+
+    #[test]
+    fn scancodes_translate_to_layout_independent_keysyms() {
+        assert_eq!(scancode_to_keysym(A), 'a' as Keysym);
+        assert_eq!(scancode_to_keysym(ONE), '1' as Keysym);
+        assert_eq!(scancode_to_keysym(0x39), ' ' as Keysym);
+        assert_eq!(scancode_to_keysym(0x3B), keysym::F1);
+        assert_eq!(scancode_to_keysym(0x58), keysym::F12);
+        // The keypad's enter and slash are extended versions of the main keys
+        assert_eq!(scancode_to_keysym(0xE01C), keysym::ENTER);
+        assert_eq!(scancode_to_keysym(0xE035), '/' as Keysym);
+        assert_eq!(scancode_to_keysym(0xE048), keysym::UP);
+        assert_eq!(scancode_to_keysym(LEFT_SUPER), keysym::LEFT_SUPER);
+        assert_eq!(scancode_to_keysym(0xE0FF), keysym::NONE);
+        assert_eq!(scancode_to_keysym(0x7F), keysym::NONE);
+    }
+
+    #[test]
+    fn modifiers_apply_while_held() {
+        let mut modifier_keys = ModifierKeys::default();
+        assert_eq!(tap(&mut modifier_keys, A).text(), Some("a"));
+
+        modifier_keys.key_event(raw(LEFT_SHIFT, false));
+        let key = tap(&mut modifier_keys, A);
+        assert_eq!(key.keysym(), 'a' as Keysym);
+        assert_eq!(key.modifiers(), KeyModifiers::SHIFT);
+        assert_eq!(key.text(), Some("A"));
+        assert_eq!(tap(&mut modifier_keys, ONE).text(), Some("!"));
+
+        modifier_keys.key_event(raw(LEFT_SHIFT, true));
+        assert_eq!(
+            tap(&mut modifier_keys, A).modifiers(),
+            KeyModifiers::empty()
+        );
+
+        // Shortcuts produce no text
+        modifier_keys.key_event(raw(LEFT_CTRL, false));
+        let key = tap(&mut modifier_keys, A);
+        assert_eq!(key.modifiers(), KeyModifiers::CTRL);
+        assert_eq!(key.text(), None);
+    }
+
+    #[test]
+    fn caps_lock_toggles_on_press_and_only_shifts_letters() {
+        let mut modifier_keys = ModifierKeys::default();
+        tap(&mut modifier_keys, CAPS_LOCK);
+
+        let key = tap(&mut modifier_keys, A);
+        assert_eq!(key.modifiers(), KeyModifiers::CAPS_LOCK);
+        assert_eq!(key.text(), Some("A"));
+        assert_eq!(tap(&mut modifier_keys, ONE).text(), Some("1"));
+
+        // Shift undoes caps lock for letters
+        modifier_keys.key_event(raw(LEFT_SHIFT, false));
+        assert_eq!(tap(&mut modifier_keys, A).text(), Some("a"));
+        modifier_keys.key_event(raw(LEFT_SHIFT, true));
+
+        tap(&mut modifier_keys, CAPS_LOCK);
+        assert_eq!(tap(&mut modifier_keys, A).text(), Some("a"));
+    }
+
+    #[test]
+    fn releases_carry_no_text() {
+        let mut modifier_keys = ModifierKeys::default();
+        let Event::KeyRelease(key) = modifier_keys.key_event(raw(A, true)) else {
+            panic!("A release should be a KeyRelease");
+        };
+        assert_eq!(key.keysym(), 'a' as Keysym);
+        assert_eq!(key.text(), None);
+    }
+
+    #[test]
+    fn key_events_go_to_the_focused_window_unless_bound() {
+        let mut windows = Windows::new();
+        let pixel = Pixel::from_rgba(0, 0, 0, 0xFF);
+        let first = windows
+            .add_window(
+                Window::new_filled_with(0, 0, 10, 10, pixel),
+                WindowKind::Normal,
+            )
+            .unwrap();
+        let second = windows
+            .add_window(
+                Window::new_filled_with(20, 20, 10, 10, pixel),
+                WindowKind::Normal,
+            )
+            .unwrap();
+        let bindings = [KeyBinding::parse("super+m", Action::MinimizeWindow).unwrap()];
+        let mut modifier_keys = ModifierKeys::default();
+        let mut route_key = |windows: &mut Windows, scancode, released| {
+            let event = modifier_keys.key_event(raw(scancode, released));
+            route(&bindings, windows, event)
+        };
+
+        windows.set_focused(first);
+        assert_eq!(route_key(&mut windows, A, false), Some(first));
+        windows.set_focused(second);
+        assert_eq!(route_key(&mut windows, A, true), Some(second));
+
+        // The binding runs instead, minimizing the focused window
+        assert_eq!(route_key(&mut windows, LEFT_SUPER, false), Some(second));
+        assert_eq!(route_key(&mut windows, M, false), None);
+        assert_eq!(windows.focused_window(), None);
+
+        // Nothing is focused anymore
+        assert_eq!(route_key(&mut windows, M, true), None);
+        assert_eq!(route_key(&mut windows, LEFT_SUPER, true), None);
+    }
+}
@@ -4,21 +4,76 @@ use std::{
     io::{LineWriter, Write},
     sync::{
         LazyLock, Mutex,
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU8, Ordering},
     },
 };
 
+/// The serial device logs are written to
+#[cfg(target_os = "safaos")]
+const SERIAL_PATH: &str = "dev:/ss";
+/// There is no serial device outside of SafaOS (for example in tests), the logs only go to the terminal
+#[cfg(not(target_os = "safaos"))]
+const SERIAL_PATH: &str = "/dev/null";
+
 static SERIAL: LazyLock<Mutex<LineWriter<File>>> = LazyLock::new(|| {
     Mutex::new(LineWriter::new(
         OpenOptions::new()
             .write(true)
             .read(true)
-            .open("dev:/ss")
+            .open(SERIAL_PATH)
             .expect("Failed to open serial device"),
     ))
 });
 
 static LOG_TERM: AtomicBool = AtomicBool::new(true);
+static LOG_LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Debug as u8);
+
+/// How detailed the logs are, each level also logs the messages of the levels before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    /// Debug information that is logged too often to be useful most of the time, such as every input event
+    Verbose,
+}
+
+impl LogLevel {
+    pub const ALL: [LogLevel; 5] = [
+        Self::Error,
+        Self::Warn,
+        Self::Info,
+        Self::Debug,
+        Self::Verbose,
+    ];
+
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warn => "warn",
+            Self::Info => "info",
+            Self::Debug => "debug",
+            Self::Verbose => "verbose",
+        }
+    }
+
+    /// Returns the log level with the name `name` if any, see [`Self::name`]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|level| level.name() == name)
+    }
+}
+
+/// Sets the most detailed level of messages that are logged
+pub fn set_log_level(level: LogLevel) {
+    LOG_LEVEL.store(level as u8, Ordering::Release);
+}
+
+/// Returns whether or not messages of the level `level` are logged
+pub fn log_enabled(level: LogLevel) -> bool {
+    level as u8 <= LOG_LEVEL.load(Ordering::Acquire)
+}
 
 /// Returns a clone of the console we log to
 pub fn console_clone() -> File {
@@ -63,24 +118,26 @@ macro_rules! generic_log {
 /// Log information about an event that isn't a debug event
 #[macro_export]
 macro_rules! log {
-    ($($arg: tt)*) => ($crate::generic_log!("[  \x1b[32mInfo\x1b[0m  ]\x1b[90m:\x1b[0m {}", format_args!($($arg)*)));
+    ($($arg: tt)*) => (if $crate::logging::log_enabled($crate::logging::LogLevel::Info) { $crate::generic_log!("[  \x1b[32mInfo\x1b[0m  ]\x1b[90m:\x1b[0m {}", format_args!($($arg)*)) });
 }
 
 /// Log debug information
 #[macro_export]
 macro_rules! dlog {
-    ($($arg: tt)*) => ($crate::generic_log!("[  \x1b[91mDebug\x1b[0m  ]\x1b[90m:\x1b[0m {}", format_args!($($arg)*)));
+    ($($arg: tt)*) => (if $crate::logging::log_enabled($crate::logging::LogLevel::Debug) { $crate::generic_log!("[  \x1b[91mDebug\x1b[0m  ]\x1b[90m:\x1b[0m {}", format_args!($($arg)*)) });
 }
 
 /// Log a non fatal error (perhaps will only affect a single client)
 #[macro_export]
 macro_rules! elog {
-    ($($arg: tt)*) => ($crate::generic_log!("[  \x1b[31mError\x1b[0m  ]\x1b[90m:\x1b[0m {}", format_args!($($arg)*)));
+    ($($arg: tt)*) => (if $crate::logging::log_enabled($crate::logging::LogLevel::Error) { $crate::generic_log!("[  \x1b[31mError\x1b[0m  ]\x1b[90m:\x1b[0m {}", format_args!($($arg)*)) });
 }
 
 #[macro_export]
 macro_rules! wlog {
     ($($arg: tt)*) => {
-        $crate::generic_log!("[  \x1b[33mWarn\x1b[0m   ]\x1b[90m:\x1b[0m {}", format_args!($($arg)*));
+        if $crate::logging::log_enabled($crate::logging::LogLevel::Warn) {
+            $crate::generic_log!("[  \x1b[33mWarn\x1b[0m   ]\x1b[90m:\x1b[0m {}", format_args!($($arg)*));
+        }
     };
 }
@@ -1,34 +1,103 @@
-use crate::bmp::BMPImage;
-use crate::com::listener;
-use crate::framebuffer::Pixel;
-use crate::logging::disable_terminal_logging;
-use crate::mice::MiceCursor;
-use crate::window::{WINDOWS, Window, WindowKind, redraw};
+use std::time::Duration;
 
-/// Set to true if you want really verbose slow information
-///
-/// TODO: make this a cmd line arg or perhaps a feature
-const REALLY_VERBOSE: bool = false;
+use crate::clock::FrameClock;
+use crate::com::listener::{self, Listener};
+use crate::config::Config;
+use crate::framebuffer::{HeadlessBackend, Pixel};
+use crate::keyboard::Keyboard;
+use crate::logging::{disable_terminal_logging, set_log_level};
+use crate::mice::MiceCursor;
+use crate::poll::Poller;
+use crate::wallpaper::Wallpaper;
+use crate::window::{WINDOWS, Window, WindowKind, frame_done, redraw, should_redraw};
 
-mod bmp;
+mod capture;
+mod clock;
 mod com;
+mod config;
+mod cursor;
+mod decorations;
+mod font;
 mod framebuffer;
+mod image;
+mod keyboard;
 mod logging;
 mod mice;
+mod poll;
+mod region;
+mod shm;
+mod wallpaper;
 mod window;
 
-fn main_loop() {
-    let mut cursor = MiceCursor::create();
+/// How often to check on the backgrounds loading on other threads, see [`Listener::is_loading`]
+const LOAD_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Handles the clients and the input devices, all from a single thread,
+/// redraws at most `clock`'s refresh rate times per second.
+///
+/// Sleeps until a client or an input device is ready, or until the next frame is due if something needs to be redrawn.
+fn main_loop(mut listener: Listener, mut clock: FrameClock, config: Config) -> ! {
+    let mut cursor = MiceCursor::create(&config.cursor_theme, config.focus_policy);
+    let mut keyboard = Keyboard::create(config.bindings);
+    let mut poller = Poller::new();
     loop {
-        cursor.handle_event();
-        redraw();
+        poller.clear();
+        let cursor_token = poller.register(cursor.raw_resource(), false);
+        let keyboard_token = poller.register(keyboard.raw_resource(), false);
+        listener.register(&mut poller);
+
+        let mut timeout = should_redraw().then(|| clock.time_until_next_frame());
+        if listener.is_loading() {
+            timeout = Some(timeout.map_or(LOAD_CHECK_INTERVAL, |t| t.min(LOAD_CHECK_INTERVAL)));
+        }
+        if let Err(err) = poller.wait(timeout) {
+            elog!("{err}");
+            continue;
+        }
+
+        // A readable device has at least one event, reading only once per wake never blocks
+        if poller.is_readable(cursor_token) {
+            cursor.handle_event();
+        }
+        if poller.is_readable(keyboard_token) {
+            keyboard.handle_event();
+        }
+        listener.poll(&poller);
+
+        if should_redraw() && clock.time_until_next_frame().is_zero() {
+            redraw();
+            frame_done(clock::timestamp());
+            clock.frame_presented();
+        }
     }
 }
 fn main() {
+    clock::start();
     log!("WM Starting");
     disable_terminal_logging();
+
+    let config = Config::load();
+    set_log_level(config.log_level);
+
+    if let Some((width, height)) = config.headless {
+        log!("Running headless with a {width}x{height} screen");
+        framebuffer::init_with_backend(Box::new(HeadlessBackend::new(width, height)));
+    }
+
+    let refresh_rate = config.refresh_rate;
+    log!("Redrawing at {refresh_rate} frames per second");
+
     framebuffer::clear();
+    let (width, height) = framebuffer::screen_size();
+    match Wallpaper::new(config.background, width, height) {
+        Ok(wallpaper) => window::set_wallpaper(wallpaper),
+        Err(err) => elog!("Failed to load the background, using the default background: {err}"),
+    }
     {
+        let mut w = WINDOWS.lock().expect("failed to get lock on windows");
+        w.set_max_windows(config.max_windows);
+    }
+    if config.demo_windows {
         let mut w = WINDOWS.lock().expect("failed to get lock on windows");
         w.add_window(
             Window::new_filled_with(213, 442, 200, 200, Pixel::from_rgba(0, 0xFF, 0, 0xFF)),
@@ -39,6 +108,9 @@ fn main() {
             WindowKind::Normal,
         );
     }
-    std::thread::spawn(main_loop);
-    listener::listen()
+    let listener = Listener::bind();
+    for command_line in &config.autostart {
+        listener::spawn(command_line);
+    }
+    main_loop(listener, FrameClock::new(refresh_rate), config)
 }
@@ -1,46 +1,127 @@
 use std::{
     fs::File,
-    io::{BufReader, Read},
+    io::{BufReader, ErrorKind, Read},
+    path::Path,
 };
 
 use opal_abi::com::response::event::{
     Event, HeldMouseButtons, MouseChangeEvent, MouseEnterEvent, MouseLeaveEvent,
 };
-use safa_api::abi::input::{MiceBtnStatus, MiceEvent, MouseEventKind};
+use safa_api::{
+    abi::input::{MiceBtnStatus, MiceEvent, MouseEventKind},
+    syscalls::types::Ri,
+};
 
 use crate::{
-    bmp::BMPImage,
-    dlog,
-    window::{WINDOWS, WinID, Window, WindowKind},
+    config::FocusPolicy,
+    cursor::{CursorShape, CursorTheme},
+    decorations::{DecorationHit, ResizeEdge},
+    dlog, framebuffer, poll,
+    window::{MIN_WINDOW_HEIGHT, MIN_WINDOW_WIDTH, WINDOWS, WinID, Window, WindowKind, Windows},
+    wlog,
 };
 
-const CURSOR_BYTES: &[u8] = include_bytes!("../assets/epic-cursor.bmp");
+/// A window that is being resized by dragging one of it's edges
+struct ResizeDrag {
+    win_id: WinID,
+    edge: ResizeEdge,
+    /// The position of the cursor when the drag started
+    start_x: usize,
+    start_y: usize,
+    /// The position of the whole window and the size of it's pixels when the drag started
+    geometry: (usize, usize, usize, usize),
+}
+
+impl ResizeDrag {
+    /// Resizes a single axis of the window by `delta`, `direction` is the direction of the dragged edge in that axis (see [`ResizeEdge::horizontal`]).
+    ///
+    /// Returns the new position and size in that axis, the opposite edge stays in place.
+    fn resize_axis(
+        pos: usize,
+        size: usize,
+        delta: isize,
+        direction: isize,
+        min_size: usize,
+    ) -> (usize, usize) {
+        match direction {
+            -1 => {
+                let new_size = size.saturating_add_signed(-delta).max(min_size);
+                ((pos + size).saturating_sub(new_size), new_size)
+            }
+            1 => (pos, size.saturating_add_signed(delta).max(min_size)),
+            _ => (pos, size),
+        }
+    }
+
+    /// Returns the position of the whole window and the size of it's pixels with the cursor at (`x`, `y`)
+    fn geometry_at(&self, x: usize, y: usize) -> (usize, usize, usize, usize) {
+        let (win_x, win_y, width, height) = self.geometry;
+
+        let (win_x, width) = Self::resize_axis(
+            win_x,
+            width,
+            x as isize - self.start_x as isize,
+            self.edge.horizontal(),
+            MIN_WINDOW_WIDTH,
+        );
+        let (win_y, height) = Self::resize_axis(
+            win_y,
+            height,
+            y as isize - self.start_y as isize,
+            self.edge.vertical(),
+            MIN_WINDOW_HEIGHT,
+        );
+        (win_x, win_y, width, height)
+    }
+}
 
 /// The MiceCursor struct represents a mouse cursor on the screen, also handles mouse events.
 pub struct MiceCursor {
     win_id: WinID,
+    /// The position of the cursor on the screen, the cursor's window is placed so that the hotspot of the current image is at this position
     x: usize,
     y: usize,
-    height: usize,
-    width: usize,
+    theme: CursorTheme,
+    shape: CursorShape,
     last_mouse_event: MiceEvent,
     current_window: Option<WinID>,
+    /// The window that is being dragged by it's title bar if any
+    dragging: Option<WinID>,
+    /// The window that is being resized by one of it's edges if any
+    resizing: Option<ResizeDrag>,
+    focus_policy: FocusPolicy,
     reader: BufReader<File>,
 }
 
 impl MiceCursor {
-    /// Creates a new MiceCursor instance
-    pub fn create() -> Self {
-        let cursor_bmp = BMPImage::from_slice(CURSOR_BYTES).expect("Failed to parse cursor.bmp");
-        let cursor_width = cursor_bmp.width();
-        let cursor_height = cursor_bmp.height();
+    /// Creates a new MiceCursor instance using the cursor theme in the directory `theme_dir`,
+    /// the builtin theme is used instead if it fails to load.
+    pub fn create(theme_dir: &Path, focus_policy: FocusPolicy) -> Self {
+        let theme = CursorTheme::load(theme_dir).unwrap_or_else(|err| {
+            wlog!(
+                "Failed to load the cursor theme at {}, using the builtin theme: {err}",
+                theme_dir.display()
+            );
+            CursorTheme::builtin()
+        });
+        let shape = CursorShape::Arrow;
+        let image = theme.get(shape);
 
         let file = File::open("dev:/inmice").expect("Failed to open the Mouse Device");
         let reader = BufReader::with_capacity(size_of::<MiceEvent>() * 1, file);
         let win = {
             let mut windows = WINDOWS.lock().expect("failed to get lock on windows");
             windows
-                .add_window(Window::new_from_bmp(0, 0, cursor_bmp), WindowKind::Overlay)
+                .add_window(
+                    Window::new_from_pixels(
+                        0,
+                        0,
+                        image.width(),
+                        image.height(),
+                        image.pixels().iter().copied(),
+                    ),
+                    WindowKind::Overlay,
+                )
                 .expect("Failed to add the Mouse cursor's window")
         };
 
@@ -49,8 +130,8 @@ impl MiceCursor {
             win_id: win,
             x: 0,
             y: 0,
-            height: cursor_height,
-            width: cursor_width,
+            theme,
+            shape,
             last_mouse_event: MiceEvent {
                 kind: MouseEventKind::Null,
                 buttons_status: MiceBtnStatus::NO_BUTTONS,
@@ -58,20 +139,55 @@ impl MiceCursor {
                 y_rel_change: 0,
             },
             current_window: None,
+            dragging: None,
+            resizing: None,
+            focus_policy,
             reader,
         }
     }
 
-    /// Handles one mouse event if available
-    pub fn handle_event(&mut self) {
+    /// Moves the cursor's window so that the hotspot of the current image is at the cursor's position
+    fn place(&self, windows: &mut Windows) {
+        let (hotspot_x, hotspot_y) = self.theme.get(self.shape).hotspot();
+        windows.move_window(
+            self.win_id,
+            self.x.saturating_sub(hotspot_x),
+            self.y.saturating_sub(hotspot_y),
+        );
+    }
+
+    /// Changes the image of the cursor to the image of `shape`
+    fn set_shape(&mut self, windows: &mut Windows, shape: CursorShape) {
+        if self.shape == shape {
+            return;
+        }
+
+        self.shape = shape;
+        let image = self.theme.get(shape);
+        windows
+            .set_window_pixels(self.win_id, image.width(), image.height(), image.pixels())
+            .expect("The cursor's window was removed");
+        self.place(windows);
+    }
+
+    /// Returns the raw resource of the mouse device, it becomes readable once a mouse event is available
+    pub fn raw_resource(&self) -> Ri {
+        poll::raw_resource(self.reader.get_ref())
+    }
+
+    /// Handles one mouse event if available, returns false if there were no events to handle.
+    ///
+    /// Only call this once the mouse device is readable (see [`Self::raw_resource`]), otherwise the read may block.
+    pub fn handle_event(&mut self) -> bool {
         let mut event_bytes = [0u8; size_of::<MiceEvent>()];
-        let len = self
-            .reader
-            .read(&mut event_bytes)
-            .expect("Failed to read an event");
+        let len = match self.reader.read(&mut event_bytes) {
+            Ok(len) => len,
+            Err(err) if err.kind() == ErrorKind::WouldBlock => 0,
+            Err(err) => panic!("Failed to read an event: {err}"),
+        };
 
         if len == 0 {
-            return;
+            return false;
         }
 
         assert_eq!(len, size_of::<MiceEvent>());
@@ -91,28 +207,49 @@ impl MiceCursor {
 
                 let mut windows = WINDOWS.lock().expect("failed to get lock on windows");
 
-                if !(x_change == 0 && y_change == 0) {
-                    let (new_x, new_y) = windows.add_cord(self.win_id, x_change, y_change).unwrap();
-                    self.x = new_x;
-                    self.y = new_y;
+                let moved = !(x_change == 0 && y_change == 0);
+                if moved {
+                    let (max_x, max_y) = framebuffer::screen_size();
+                    self.x = self
+                        .x
+                        .saturating_add_signed(x_change as isize)
+                        .min(max_x.saturating_sub(1));
+                    self.y = self
+                        .y
+                        .saturating_add_signed(y_change as isize)
+                        .min(max_y.saturating_sub(1));
+                    self.place(&mut windows);
                 }
 
-                let window_in_contact =
-                    windows.window_in_contact(self.x, self.y, self.width, self.height);
+                let window_in_contact = windows.window_in_contact(self.x, self.y, 1, 1);
 
                 let left_button_is_pressed = event.buttons_status.contains(MiceBtnStatus::BTN_LEFT);
-                if left_button_was_pressed
-                    && let Some(focused_id) = windows.focused_window()
-                    && left_button_is_pressed
-                {
-                    windows.add_cord(focused_id, x_change, y_change);
+                if !left_button_is_pressed {
+                    self.dragging = None;
+                    self.resizing = None;
+                }
+
+                if let Some(dragging_id) = self.dragging {
+                    windows.add_cord(dragging_id, x_change, y_change);
                 }
 
+                if let Some(resizing) = &self.resizing {
+                    let (x, y, width, height) = resizing.geometry_at(self.x, self.y);
+                    /* It is ok the window might be gone by now */
+                    _ = windows.configure_window(resizing.win_id, x, y, width, height);
+                }
+
+                let mut hovered_hit = None;
+
                 match window_in_contact {
-                    Some((curr_id, contact_point)) => {
+                    Some((curr_id, contact_rect)) => {
                         let mut mouse_enter = false;
-                        let x = contact_point.x() as u32;
-                        let y = contact_point.y() as u32;
+                        let (hit, x, y) = windows
+                            .hit_test(curr_id, contact_rect.x, contact_rect.y)
+                            .expect("Window removed before we could handle a mouse event");
+                        let x = x as u32;
+                        let y = y as u32;
+                        hovered_hit = Some((curr_id, hit));
 
                         if old_win_id.is_none_or(|old_id| old_id != curr_id) {
                             windows
@@ -130,7 +267,7 @@ impl MiceCursor {
                         }
 
                         // FIXME: for some reason mouse release events are not being sent by the kernel driver.
-                        if !mouse_enter {
+                        if !mouse_enter && hit == DecorationHit::Client {
                             let mut held_buttons = HeldMouseButtons::empty();
 
                             if left_button_is_pressed {
@@ -153,11 +290,48 @@ impl MiceCursor {
                             windows.send_event(curr_id, Event::MouseChange(change_event)).expect("Current Window was removed before we could handle a mouse event");
                         }
 
+                        if left_button_is_pressed && !left_button_was_pressed {
+                            match hit {
+                                DecorationHit::TitleBar => self.dragging = Some(curr_id),
+                                DecorationHit::CloseButton => {
+                                    _ = windows.send_event(curr_id, Event::CloseRequested)
+                                }
+                                DecorationHit::MaximizeButton => {
+                                    _ = windows.toggle_maximize(curr_id)
+                                }
+                                DecorationHit::MinimizeButton => _ = windows.minimize(curr_id),
+                                DecorationHit::Resize(edge) => {
+                                    self.resizing =
+                                        windows.geometry(curr_id).map(|geometry| ResizeDrag {
+                                            win_id: curr_id,
+                                            edge,
+                                            start_x: self.x,
+                                            start_y: self.y,
+                                            geometry,
+                                        })
+                                }
+                                DecorationHit::Client => {}
+                            }
+                        }
+
+                        let clicked = left_button_is_pressed
+                            && !left_button_was_pressed
+                            && hit != DecorationHit::MinimizeButton;
+                        let focus_requested = match self.focus_policy {
+                            FocusPolicy::Click => clicked,
+                            // Not while dragging, so a window dragged under another doesn't lose the focus
+                            FocusPolicy::Hover => {
+                                clicked
+                                    || (mouse_enter
+                                        && self.dragging.is_none()
+                                        && self.resizing.is_none())
+                            }
+                        };
+
                         if windows
                             .focused_window()
                             .is_none_or(|focus_id| focus_id != curr_id)
-                            && left_button_is_pressed
-                            && !left_button_was_pressed
+                            && focus_requested
                         {
                             windows.set_focused(curr_id);
                         }
@@ -169,16 +343,30 @@ impl MiceCursor {
                                 .send_event(old_id, Event::MouseLeave(MouseLeaveEvent::new()));
                         }
 
-                        if left_button_is_pressed && !left_button_was_pressed {
+                        if self.focus_policy == FocusPolicy::Click
+                            && left_button_is_pressed
+                            && !left_button_was_pressed
+                        {
                             windows.unfocus_current();
                         }
                     }
                 }
 
+                let shape = match (&self.resizing, hovered_hit) {
+                    (Some(resizing), _) => resizing.edge.cursor_shape(),
+                    (None, Some((_, DecorationHit::Resize(edge)))) => edge.cursor_shape(),
+                    (None, Some((win_id, DecorationHit::Client))) => {
+                        windows.window_cursor(win_id).unwrap_or(CursorShape::Arrow)
+                    }
+                    _ => CursorShape::Arrow,
+                };
+                self.set_shape(&mut windows, shape);
+
                 self.last_mouse_event = event;
                 self.current_window = window_in_contact.map(|(id, _)| id);
             }
             MouseEventKind::Null => unreachable!(),
         }
+        true
     }
 }
@@ -0,0 +1,95 @@
+//! Waiting for any of several resources (sockets and input devices) to become ready,
+//! so that the WM sleeps until there is something to do instead of checking every resource over and over.
+
+use std::{io, time::Duration};
+
+use safa_api::{
+    abi::io::{PollEntry, PollEvents},
+    syscalls::types::Ri,
+};
+
+/// Returns the raw resource of `resource` to register it with a [`Poller`]
+#[cfg(target_os = "safaos")]
+pub fn raw_resource(resource: &impl std::os::safaos::AsRawResource) -> Ri {
+    resource.as_raw_resource()
+}
+
+/// Returns the raw resource of `resource` to register it with a [`Poller`],
+/// only SafaOS resources can be waited for so there are none outside of it (for example in tests)
+#[cfg(not(target_os = "safaos"))]
+pub fn raw_resource<T>(_resource: &T) -> Ri {
+    unimplemented!("Only SafaOS resources can be polled")
+}
+
+/// Returns whether or not the resource `ri` has something to read right now, never blocks,
+/// see [`Poller::is_readable`]
+pub fn is_readable_now(ri: Ri) -> io::Result<bool> {
+    let mut poller = Poller::new();
+    let token = poller.register(ri, false);
+    poller.wait(Some(Duration::ZERO))?;
+    Ok(poller.is_readable(token))
+}
+
+/// Returns whether or not the resource `ri` can be written to without blocking right now, never blocks
+pub fn is_writable_now(ri: Ri) -> io::Result<bool> {
+    let mut poller = Poller::new();
+    let token = poller.register(ri, true);
+    poller.wait(Some(Duration::ZERO))?;
+    Ok(poller.is_writable(token))
+}
+
+/// Identifies a resource registered with a [`Poller`], see [`Poller::register`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token(usize);
+
+/// A set of resources to wait for, registered again before every wait
+pub struct Poller {
+    entries: Vec<PollEntry>,
+}
+
+impl Poller {
+    pub const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Unregisters every resource, the tokens returned so far are no longer valid
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Registers the resource `ri` to wait until it is readable, and also until it is writable if `writable` is true
+    pub fn register(&mut self, ri: Ri, writable: bool) -> Token {
+        let mut events = PollEvents::IN;
+        if writable {
+            events |= PollEvents::OUT;
+        }
+
+        self.entries.push(PollEntry::new(ri, events));
+        Token(self.entries.len() - 1)
+    }
+
+    /// Blocks until at least one of the registered resources is ready, or until `timeout` passes if given
+    pub fn wait(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        let timeout_ms = timeout.map(|timeout| timeout.as_millis() as u64);
+        safa_api::syscalls::io::poll(&mut self.entries, timeout_ms)
+            .map(|_| ())
+            .map_err(|err| io::Error::other(format!("Failed to poll: {err:?}")))
+    }
+
+    /// Returns whether or not the resource `token` has something to read after the last [`Self::wait`],
+    /// a resource that was closed or failed is readable so that reading from it reports what happened.
+    pub fn is_readable(&self, token: Token) -> bool {
+        self.entries[token.0]
+            .returned_events()
+            .intersects(PollEvents::IN | PollEvents::HUP | PollEvents::ERR)
+    }
+
+    /// Returns whether or not the resource `token` can be written to without blocking after the last [`Self::wait`]
+    pub fn is_writable(&self, token: Token) -> bool {
+        self.entries[token.0]
+            .returned_events()
+            .contains(PollEvents::OUT)
+    }
+}
@@ -0,0 +1,407 @@
+//! Rectangles and regions (sets of pixels described by non-overlapping rectangles), used to track the damaged parts of the screen.
+
+/// A rectangle of `width`*`height` pixels with it's top-left corner at (`x`, `y`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub const fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Returns the x-coordinate right after the right edge of the rectangle
+    pub const fn right(&self) -> usize {
+        self.x + self.width
+    }
+
+    /// Returns the y-coordinate right after the bottom edge of the rectangle
+    pub const fn bottom(&self) -> usize {
+        self.y + self.height
+    }
+
+    /// Returns true if the rectangle contains no pixels
+    pub const fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// Returns true if the rectangle contains every pixel of `other`
+    pub const fn contains_rect(&self, other: &Rect) -> bool {
+        other.is_empty()
+            || (other.x >= self.x
+                && other.y >= self.y
+                && other.right() <= self.right()
+                && other.bottom() <= self.bottom())
+    }
+
+    /// Returns the part of `self` that is also in `other` if any
+    pub const fn intersection(&self, other: &Rect) -> Option<Rect> {
+        let x0 = if self.x > other.x { self.x } else { other.x };
+        let y0 = if self.y > other.y { self.y } else { other.y };
+        let x1 = if self.right() < other.right() {
+            self.right()
+        } else {
+            other.right()
+        };
+        let y1 = if self.bottom() < other.bottom() {
+            self.bottom()
+        } else {
+            other.bottom()
+        };
+
+        if x0 < x1 && y0 < y1 {
+            Some(Rect::new(x0, y0, x1 - x0, y1 - y0))
+        } else {
+            None
+        }
+    }
+
+    /// Returns `self` relative to (`x`, `y`) instead of the current origin, (`x`, `y`) must be before the top-left corner of `self`.
+    pub const fn relative_to(&self, x: usize, y: usize) -> Rect {
+        Rect::new(self.x - x, self.y - y, self.width, self.height)
+    }
+
+    /// Calls `f` with the (at most 4) non-overlapping rectangles that cover the part of `self` that isn't in `other`
+    fn subtract_into(&self, other: &Rect, mut f: impl FnMut(Rect)) {
+        let Some(overlap) = self.intersection(other) else {
+            if !self.is_empty() {
+                f(*self);
+            }
+            return;
+        };
+
+        // Above and below the overlap, spanning the whole width
+        if overlap.y > self.y {
+            f(Rect::new(self.x, self.y, self.width, overlap.y - self.y));
+        }
+        if overlap.bottom() < self.bottom() {
+            f(Rect::new(
+                self.x,
+                overlap.bottom(),
+                self.width,
+                self.bottom() - overlap.bottom(),
+            ));
+        }
+
+        // Left and right of the overlap, spanning the overlap's height
+        if overlap.x > self.x {
+            f(Rect::new(
+                self.x,
+                overlap.y,
+                overlap.x - self.x,
+                overlap.height,
+            ));
+        }
+        if overlap.right() < self.right() {
+            f(Rect::new(
+                overlap.right(),
+                overlap.y,
+                self.right() - overlap.right(),
+                overlap.height,
+            ));
+        }
+    }
+}
+
+/// A set of pixels, stored as a list of non-empty rectangles that never overlap each other,
+/// so every pixel in the region is covered by exactly one of it's rectangles.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Region {
+    rects: Vec<Rect>,
+}
+
+impl Region {
+    /// Returns an empty region
+    pub const fn new() -> Self {
+        Self { rects: Vec::new() }
+    }
+
+    /// Returns a region that contains the pixels of `rect`
+    #[cfg(test)]
+    pub fn from_rect(rect: Rect) -> Self {
+        let mut region = Self::new();
+        region.union_rect(rect);
+        region
+    }
+
+    /// Returns true if the region contains no pixels
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+
+    /// Returns the non-overlapping rectangles that make up the region
+    pub fn rects(&self) -> &[Rect] {
+        &self.rects
+    }
+
+    /// Returns the amount of pixels in the region
+    #[cfg(test)]
+    pub fn area(&self) -> usize {
+        self.rects.iter().map(|rect| rect.width * rect.height).sum()
+    }
+
+    /// Returns the smallest rectangle containing the whole region if the region isn't empty
+    #[cfg(test)]
+    pub fn bounds(&self) -> Option<Rect> {
+        let first = *self.rects.first()?;
+        Some(self.rects[1..].iter().fold(first, |bounds, rect| {
+            let x = bounds.x.min(rect.x);
+            let y = bounds.y.min(rect.y);
+            let right = bounds.right().max(rect.right());
+            let bottom = bounds.bottom().max(rect.bottom());
+            Rect::new(x, y, right - x, bottom - y)
+        }))
+    }
+
+    /// Returns true if the region contains every pixel of `rect`
+    #[cfg(test)]
+    pub fn contains_rect(&self, rect: &Rect) -> bool {
+        let mut uncovered = Region::from_rect(*rect);
+        uncovered.subtract(self);
+        uncovered.is_empty()
+    }
+
+    /// Adds the pixels of `rect` to the region
+    pub fn union_rect(&mut self, rect: Rect) {
+        if rect.is_empty()
+            || self
+                .rects
+                .iter()
+                .any(|existing| existing.contains_rect(&rect))
+        {
+            return;
+        }
+
+        // Rectangles that the new one covers completely are replaced by it
+        self.rects.retain(|existing| !rect.contains_rect(existing));
+
+        // Only add the parts of `rect` that aren't in the region yet, so the rectangles never overlap
+        let mut pieces = vec![rect];
+        for existing in &self.rects {
+            let mut remaining = Vec::with_capacity(pieces.len());
+            for piece in &pieces {
+                piece.subtract_into(existing, |rest| remaining.push(rest));
+            }
+
+            pieces = remaining;
+            if pieces.is_empty() {
+                return;
+            }
+        }
+
+        self.rects.extend(pieces);
+    }
+
+    /// Adds the pixels of `other` to the region
+    pub fn union(&mut self, other: &Region) {
+        for rect in &other.rects {
+            self.union_rect(*rect);
+        }
+    }
+
+    /// Removes the pixels of `rect` from the region
+    pub fn subtract_rect(&mut self, rect: &Rect) {
+        if rect.is_empty() {
+            return;
+        }
+
+        let mut rects = Vec::with_capacity(self.rects.len());
+        for existing in &self.rects {
+            existing.subtract_into(rect, |rest| rects.push(rest));
+        }
+        self.rects = rects;
+    }
+
+    /// Removes the pixels of `other` from the region
+    pub fn subtract(&mut self, other: &Region) {
+        for rect in &other.rects {
+            self.subtract_rect(rect);
+        }
+    }
+
+    /// Returns the part of the region that is within `rect`
+    pub fn intersect_rect(&self, rect: &Rect) -> Region {
+        // Intersections of non-overlapping rectangles with a single rectangle never overlap each other
+        Region {
+            rects: self
+                .rects
+                .iter()
+                .filter_map(|existing| existing.intersection(rect))
+                .collect(),
+        }
+    }
+
+    /// Returns the pixels that are in both the region and `other`
+    pub fn intersect(&self, other: &Region) -> Region {
+        // Both sides are non-overlapping so the pairwise intersections never overlap each other either
+        Region {
+            rects: self
+                .rects
+                .iter()
+                .flat_map(|a| other.rects.iter().filter_map(|b| a.intersection(b)))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The size of the square the random rectangles are generated in
+    const SIZE: usize = 48;
+    const CASES: usize = 500;
+
+    /// A small deterministic xorshift generator, so failures can be reproduced
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, max: usize) -> usize {
+            (self.next() % max as u64) as usize
+        }
+
+        /// Returns a rectangle within the square, possibly empty
+        fn rect(&mut self) -> Rect {
+            let x = self.below(SIZE);
+            let y = self.below(SIZE);
+            let width = self.below(SIZE - x + 1);
+            let height = self.below(SIZE - y + 1);
+            Rect::new(x, y, width, height)
+        }
+
+        /// Returns a region made of up to 8 random rectangles
+        fn region(&mut self) -> Region {
+            let mut region = Region::new();
+            for _ in 0..self.below(8) {
+                region.union_rect(self.rect());
+            }
+            region
+        }
+    }
+
+    /// Returns the pixels in the region, asserting that none of it's rectangles are empty or overlap each other
+    fn pixels(region: &Region) -> Vec<bool> {
+        let mut pixels = vec![false; SIZE * SIZE];
+        for rect in region.rects() {
+            assert!(!rect.is_empty(), "{region:?} contains an empty rectangle");
+            for y in rect.y..rect.bottom() {
+                for x in rect.x..rect.right() {
+                    let pixel = &mut pixels[y * SIZE + x];
+                    assert!(
+                        !*pixel,
+                        "{region:?} has overlapping rectangles at ({x}, {y})"
+                    );
+                    *pixel = true;
+                }
+            }
+        }
+        pixels
+    }
+
+    fn rect_pixels(rect: &Rect) -> Vec<bool> {
+        pixels(&Region::from_rect(*rect))
+    }
+
+    /// Combines the pixels of two regions one pixel at a time
+    fn combine(a: &[bool], b: &[bool], op: impl Fn(bool, bool) -> bool) -> Vec<bool> {
+        a.iter().zip(b).map(|(a, b)| op(*a, *b)).collect()
+    }
+
+    #[test]
+    fn union_contains_the_pixels_of_both_regions() {
+        let mut rng = Rng(0x0BAD_CAFE);
+        for _ in 0..CASES {
+            let (a, b) = (rng.region(), rng.region());
+            let mut union = a.clone();
+            union.union(&b);
+
+            assert_eq!(
+                pixels(&union),
+                combine(&pixels(&a), &pixels(&b), |a, b| a || b)
+            );
+            assert_eq!(union.area(), a.area() + b.area() - a.intersect(&b).area());
+        }
+    }
+
+    #[test]
+    fn subtract_removes_the_pixels_of_the_other_region() {
+        let mut rng = Rng(0xDEAD_BEEF);
+        for _ in 0..CASES {
+            let (a, b) = (rng.region(), rng.region());
+            let mut difference = a.clone();
+            difference.subtract(&b);
+
+            assert_eq!(
+                pixels(&difference),
+                combine(&pixels(&a), &pixels(&b), |a, b| a && !b)
+            );
+            assert_eq!(difference.area(), a.area() - a.intersect(&b).area());
+            assert!(difference.intersect(&b).is_empty());
+        }
+    }
+
+    #[test]
+    fn intersect_keeps_the_pixels_in_both_regions() {
+        let mut rng = Rng(0x1234_5678);
+        for _ in 0..CASES {
+            let (a, b) = (rng.region(), rng.region());
+            let intersection = a.intersect(&b);
+
+            assert_eq!(
+                pixels(&intersection),
+                combine(&pixels(&a), &pixels(&b), |a, b| a && b)
+            );
+            assert_eq!(intersection.area(), b.intersect(&a).area());
+
+            let rect = rng.rect();
+            assert_eq!(
+                pixels(&a.intersect_rect(&rect)),
+                combine(&pixels(&a), &rect_pixels(&rect), |a, b| a && b)
+            );
+        }
+    }
+
+    #[test]
+    fn bounds_and_contains_rect_agree_with_the_pixels() {
+        let mut rng = Rng(0xC0FF_EE00);
+        for _ in 0..CASES {
+            let region = rng.region();
+            let region_pixels = pixels(&region);
+
+            match region.bounds() {
+                Some(bounds) => {
+                    assert!(region.rects().iter().all(|rect| bounds.contains_rect(rect)));
+                    assert!(region.contains_rect(&Rect::new(bounds.x, bounds.y, 0, 0)));
+                }
+                None => assert!(region.is_empty()),
+            }
+
+            let rect = rng.rect();
+            let contained = rect_pixels(&rect)
+                .iter()
+                .zip(&region_pixels)
+                .all(|(in_rect, in_region)| !in_rect || *in_region);
+            assert_eq!(
+                region.contains_rect(&rect),
+                contained,
+                "{region:?} {rect:?}"
+            );
+        }
+    }
+}
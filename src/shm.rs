@@ -0,0 +1,98 @@
+//! Pixel buffers in shared memory, mapped by both the WM and the client they are handed to using their key.
+
+use std::ptr::NonNull;
+
+#[cfg(target_os = "safaos")]
+use safa_api::{
+    abi::mem::{MemMapFlags, ShmFlags},
+    syscalls::types::Ri,
+};
+
+use crate::framebuffer::Pixel;
+
+/// A buffer of pixels in shared memory, destroyed once dropped.
+///
+/// Without SafaOS (for example in tests) the pixels are allocated on the heap instead and can't be shared.
+pub struct SharedPixels {
+    /// The mapped pixels, safe to use because they live as long as the buffer itself.
+    pixels: NonNull<[Pixel]>,
+    key: usize,
+    #[cfg(target_os = "safaos")]
+    shm_ri: Ri,
+    #[cfg(target_os = "safaos")]
+    mmap_ri: Ri,
+}
+
+unsafe impl Send for SharedPixels {}
+unsafe impl Sync for SharedPixels {}
+
+impl SharedPixels {
+    /// Allocates `len` pixels in a new shared memory buffer filled with `fill_pixel`
+    #[cfg(target_os = "safaos")]
+    pub fn new(len: usize, fill_pixel: Pixel) -> Self {
+        let bytes_required = len * size_of::<Pixel>();
+        let pages_required = bytes_required.div_ceil(4096);
+
+        let (key, shm_ri) =
+            safa_api::syscalls::mem::shm_create(pages_required, ShmFlags::from_bits_retaining(0))
+                .expect("Failed to create a new shared mem mapping");
+
+        let (mmap_ri, bytes) = safa_api::syscalls::mem::map(
+            core::ptr::null(),
+            pages_required,
+            0,
+            Some(shm_ri),
+            None,
+            MemMapFlags::WRITE,
+        )
+        .expect("Failed to memmap a new shared pixel buffer");
+
+        let mut pixels = NonNull::slice_from_raw_parts(bytes.cast::<Pixel>(), len);
+        unsafe { pixels.as_mut() }.fill(fill_pixel);
+
+        Self {
+            pixels,
+            key,
+            shm_ri,
+            mmap_ri,
+        }
+    }
+
+    /// Allocates `len` pixels on the heap filled with `fill_pixel`, there is no shared memory to put them in
+    #[cfg(not(target_os = "safaos"))]
+    pub fn new(len: usize, fill_pixel: Pixel) -> Self {
+        let pixels = Box::into_raw(vec![fill_pixel; len].into_boxed_slice());
+        Self {
+            pixels: NonNull::new(pixels).expect("Box pointers are never null"),
+            key: 0,
+        }
+    }
+
+    /// The shared memory key clients use to map the pixels
+    pub const fn key(&self) -> usize {
+        self.key
+    }
+
+    pub const fn as_slice(&self) -> &[Pixel] {
+        unsafe { self.pixels.as_ref() }
+    }
+
+    pub const fn as_mut_slice(&mut self) -> &mut [Pixel] {
+        unsafe { self.pixels.as_mut() }
+    }
+}
+
+impl Drop for SharedPixels {
+    #[cfg(target_os = "safaos")]
+    fn drop(&mut self) {
+        safa_api::syscalls::resources::destroy_resource(self.shm_ri)
+            .expect("SHM was dropped before the pixel buffer was dropped");
+        safa_api::syscalls::resources::destroy_resource(self.mmap_ri)
+            .expect("MMAP was dropped before the pixel buffer was dropped");
+    }
+
+    #[cfg(not(target_os = "safaos"))]
+    fn drop(&mut self) {
+        drop(unsafe { Box::from_raw(self.pixels.as_ptr()) });
+    }
+}
@@ -0,0 +1,259 @@
+use std::{fs, io, path::PathBuf};
+
+pub use opal_abi::background::{Background, GradientDirection, ImageFit};
+use thiserror::Error;
+
+use crate::{
+    framebuffer::{AlphaFormat, DisplayBackend, Pixel},
+    image::{Image, ImageError},
+    region::Rect,
+};
+
+/// The color of the default background, also shows through the parts of the screen a background image doesn't cover
+const DEFAULT_COLOR: u32 = 0x282828;
+/// The background used when none is configured
+pub const DEFAULT_BACKGROUND: Background = Background::Solid(DEFAULT_COLOR);
+
+/// An error that happened while loading a background
+#[derive(Debug, Error)]
+pub enum WallpaperError {
+    #[error("The path of the background image is not valid UTF-8")]
+    InvalidPath,
+    #[error("Failed to read {0}: {1}")]
+    Io(PathBuf, io::Error),
+    #[error("Failed to decode {0}: {1}")]
+    InvalidImage(PathBuf, ImageError),
+}
+
+/// Converts a color in the format `0xRRGGBB` to an opaque pixel
+const fn opaque(rgb: u32) -> Pixel {
+    Pixel::from_hex(0xFF00_0000 | (rgb & 0xFF_FFFF))
+}
+
+/// Parses a background description, returns `None` if it is invalid.
+///
+/// The description is in one of the formats `solid <color>`, `gradient <vertical|horizontal> <from color> <to color>`,
+/// or `image <center|tile|stretch|fill> <path>`, colors are in the format `#RRGGBB`.
+pub fn parse_background(description: &str) -> Option<Background> {
+    let color = |color: &str| u32::from_str_radix(color.strip_prefix('#')?, 16).ok();
+
+    let (kind, rest) = description.trim().split_once(char::is_whitespace)?;
+    let mut parts = rest.split_whitespace();
+    match kind {
+        "solid" => match (parts.next().and_then(color), parts.next()) {
+            (Some(color), None) => Some(Background::Solid(color)),
+            _ => None,
+        },
+        "gradient" => match (
+            parts.next().and_then(GradientDirection::from_name),
+            parts.next().and_then(color),
+            parts.next().and_then(color),
+            parts.next(),
+        ) {
+            (Some(direction), Some(from), Some(to), None) => Some(Background::Gradient {
+                from,
+                to,
+                direction,
+            }),
+            _ => None,
+        },
+        // The path is the rest of the description, so it can contain spaces
+        "image" => {
+            let (fit, path) = rest.trim_start().split_once(char::is_whitespace)?;
+            Background::image(path.trim(), ImageFit::from_name(fit)?)
+        }
+        _ => None,
+    }
+}
+
+/// The background layer drawn behind every window, the damage no window covers is repaired by copying from it
+pub struct Wallpaper {
+    background: Background,
+    width: usize,
+    height: usize,
+    /// The opaque premultiplied pixels of the layer row by row,
+    /// empty for solid colors which are filled in instead of being copied.
+    pixels: Vec<Pixel>,
+}
+
+impl Wallpaper {
+    /// The wallpaper of [`DEFAULT_BACKGROUND`]
+    pub const DEFAULT: Self = Self::solid(DEFAULT_COLOR);
+
+    /// Returns a wallpaper of a single color that doesn't need any pixels, see [`Background::Solid`]
+    const fn solid(rgb: u32) -> Self {
+        Self {
+            background: Background::Solid(rgb),
+            width: 0,
+            height: 0,
+            pixels: Vec::new(),
+        }
+    }
+
+    /// Renders the background `background` for a screen of the size `width`*`height`, loading the image if it is an image background
+    pub fn new(
+        background: Background,
+        width: usize,
+        height: usize,
+    ) -> Result<Self, WallpaperError> {
+        let pixels = match background {
+            Background::Solid(rgb) => return Ok(Self::solid(rgb)),
+            Background::Gradient {
+                from,
+                to,
+                direction,
+            } => Self::render_gradient(opaque(from), opaque(to), direction, width, height),
+            Background::Image { fit, .. } => {
+                let path =
+                    PathBuf::from(background.image_path().ok_or(WallpaperError::InvalidPath)?);
+                let bytes = fs::read(&path).map_err(|err| WallpaperError::Io(path.clone(), err))?;
+                let image = Image::from_slice(&bytes)
+                    .map_err(|err| WallpaperError::InvalidImage(path, err))?;
+                Self::render_image(&image, fit, width, height)
+            }
+        };
+
+        Ok(Self {
+            background,
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    fn render_gradient(
+        from: Pixel,
+        to: Pixel,
+        direction: GradientDirection,
+        width: usize,
+        height: usize,
+    ) -> Vec<Pixel> {
+        // Returns the pixel at `pos` of a gradient `len` pixels long
+        let at = |pos: usize, len: usize| {
+            from.lerp(&to, (pos * 0xFF / len.saturating_sub(1).max(1)) as u8)
+        };
+
+        match direction {
+            GradientDirection::Vertical => (0..height)
+                .flat_map(|y| std::iter::repeat_n(at(y, height), width))
+                .collect(),
+            GradientDirection::Horizontal => {
+                let row: Vec<Pixel> = (0..width).map(|x| at(x, width)).collect();
+                row.repeat(height)
+            }
+        }
+    }
+
+    fn render_image(image: &Image, fit: ImageFit, width: usize, height: usize) -> Vec<Pixel> {
+        let (image_width, image_height) = (image.width(), image.height());
+        let image_pixels: Vec<Pixel> = image.pixels().map(|pixel| pixel.premultiplied()).collect();
+        let behind = opaque(DEFAULT_COLOR);
+
+        let image_at = |x: usize, y: usize| image_pixels[y * image_width + x];
+        // Samples the image at the fractional position (`x`, `y`) interpolating between the 4 closest pixels
+        let sample = |x: f32, y: f32| {
+            let x = x.clamp(0.0, (image_width - 1) as f32);
+            let y = y.clamp(0.0, (image_height - 1) as f32);
+            let (x0, y0) = (x as usize, y as usize);
+            let (x1, y1) = (
+                (x0 + 1).min(image_width - 1),
+                (y0 + 1).min(image_height - 1),
+            );
+            let (tx, ty) = (
+                ((x - x0 as f32) * 255.0) as u8,
+                ((y - y0 as f32) * 255.0) as u8,
+            );
+
+            let top = image_at(x0, y0).lerp(&image_at(x1, y0), tx);
+            let bottom = image_at(x0, y1).lerp(&image_at(x1, y1), tx);
+            top.lerp(&bottom, ty)
+        };
+
+        let mut pixels = Vec::with_capacity(width * height);
+        if image_width == 0 || image_height == 0 {
+            pixels.resize(width * height, behind);
+            return pixels;
+        }
+
+        // The scale of the image on each axis, and the offset of the image's top-left corner on the screen
+        let (scale_x, scale_y, off_x, off_y) = match fit {
+            ImageFit::Center | ImageFit::Tile => (1.0, 1.0, 0.0, 0.0),
+            ImageFit::Stretch => (
+                width as f32 / image_width as f32,
+                height as f32 / image_height as f32,
+                0.0,
+                0.0,
+            ),
+            ImageFit::Fill => {
+                let scale = f32::max(
+                    width as f32 / image_width as f32,
+                    height as f32 / image_height as f32,
+                );
+                (
+                    scale,
+                    scale,
+                    (width as f32 - image_width as f32 * scale) / 2.0,
+                    (height as f32 - image_height as f32 * scale) / 2.0,
+                )
+            }
+        };
+
+        for y in 0..height {
+            for x in 0..width {
+                let pixel = match fit {
+                    ImageFit::Tile => image_at(x % image_width, y % image_height),
+                    ImageFit::Center => {
+                        let image_x = (x + image_width / 2).checked_sub(width / 2);
+                        let image_y = (y + image_height / 2).checked_sub(height / 2);
+                        match (image_x, image_y) {
+                            (Some(image_x), Some(image_y))
+                                if image_x < image_width && image_y < image_height =>
+                            {
+                                image_at(image_x, image_y)
+                            }
+                            _ => behind,
+                        }
+                    }
+                    // Samples at the center of each screen pixel
+                    ImageFit::Stretch | ImageFit::Fill => sample(
+                        (x as f32 + 0.5 - off_x) / scale_x - 0.5,
+                        (y as f32 + 0.5 - off_y) / scale_y - 0.5,
+                    ),
+                };
+
+                pixels.push(pixel.over(&behind));
+            }
+        }
+
+        pixels
+    }
+
+    /// Draws the part of the wallpaper within the rectangle `rect` of the screen to `fb`
+    pub fn draw(&self, fb: &mut dyn DisplayBackend, rect: Rect) {
+        let Some(rect) = rect.intersection(&Rect::new(0, 0, fb.width(), fb.height())) else {
+            return;
+        };
+
+        if let Background::Solid(rgb) = self.background {
+            fb.draw_rect_filled_with(rect.x, rect.y, rect.width, rect.height, opaque(rgb));
+            return;
+        }
+
+        let Some(rect) = rect.intersection(&Rect::new(0, 0, self.width, self.height)) else {
+            return;
+        };
+
+        fb.draw_rect_within(
+            rect.x,
+            rect.y,
+            rect.width,
+            rect.height,
+            &self.pixels,
+            self.width,
+            self.height,
+            rect.x,
+            rect.y,
+            AlphaFormat::Premultiplied,
+        );
+    }
+}
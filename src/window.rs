@@ -1,9 +1,6 @@
 use std::{
     collections::HashMap,
     io::ErrorKind,
-    iter::Sum,
-    ops::Add,
-    ptr::NonNull,
     sync::{
         Arc, Mutex,
         atomic::{AtomicBool, Ordering},
@@ -11,18 +8,38 @@ use std::{
 };
 
 use indexmap::IndexSet;
-use opal_abi::com::response::{Response, event::Event};
+use opal_abi::com::response::{
+    Response,
+    error::ResponseError,
+    event::{ConfigureEvent, Event, EventEnvelope, FrameDoneEvent, WindowResizedEvent},
+};
 use rustc_hash::{FxBuildHasher, FxHashMap};
-use safa_api::abi::mem::{MemMapFlags, ShmFlags};
 
 use crate::{
-    REALLY_VERBOSE,
-    bmp::BMPImage,
-    com::ClientComPipe,
+    clock,
+    com::{ClientComPipe, ClientID},
+    cursor::CursorShape,
+    decorations::{DecorationHit, Decorations},
     dlog, elog,
-    framebuffer::{self, BG_PIXEL, FB_INFO, Framebuffer, Pixel},
+    framebuffer::{self, AlphaFormat, DisplayBackend, HeadlessBackend, Pixel},
+    image::Image,
+    logging::{self, LogLevel},
+    region::{Rect, Region},
+    shm::SharedPixels,
+    wallpaper::Wallpaper,
 };
 
+/// A resize the WM asked a client for, applied once the client acknowledges it
+#[derive(Debug, Clone, Copy)]
+struct PendingConfigure {
+    serial: u32,
+    /// The position to move the window to once resized
+    pos_x: usize,
+    pos_y: usize,
+    width: usize,
+    height: usize,
+}
+
 // a Rectangle
 pub struct Window {
     //
@@ -31,29 +48,37 @@ pub struct Window {
     //
     width: usize,
     height: usize,
-    /// The pixels of the window, safe to use because they live as long as the window itself.
-    pixels: NonNull<[Pixel]>,
-    // TODO: Implement a good shared memory wrapper to drop this automatically.
-    shm_key: usize,
-    // TODO: Implement a good shared memory or a resource wrapper to drop this automatically.
-    shm_ri: usize,
-    // TODO: Implement a good memory map or a resource wrapper to drop this automatically.
-    mmap_ri: usize,
+    /// The pixels of the window, shared with the client that owns it
+    pixels: SharedPixels,
     com_pipe: Option<Arc<ClientComPipe>>,
+    /// The ID of the window, set once the window is added (see [`Windows::add_window`]), every event sent to the client carries it
+    id: WinID,
+    /// The server-side decorations around the window if any, when present `pos_x` and `pos_y` are the position of the decorations
+    decorations: Option<Decorations>,
+    /// Whether or not the window was minimized, minimized windows are not displayed
+    minimized: bool,
+    /// The position and the size of the window before it was maximized, if it is maximized
+    restore_geometry: Option<(usize, usize, usize, usize)>,
+    /// The title of the window, displayed in the title bar
+    title: String,
+    /// Identifies the application that created the window
+    app_id: String,
+    /// The most recent resize sent to the client that it didn't acknowledge yet
+    pending_configure: Option<PendingConfigure>,
+    /// The serial of the last configure event sent to the client
+    configure_serial: u32,
+    /// The shape of the cursor while it is over the window's pixels
+    cursor: CursorShape,
+    /// Whether or not the client asked to be notified once the next frame is presented
+    frame_callback_requested: bool,
+    /// The part of the window's pixels the client promised is fully opaque, if not set [`Self::pixels_opaque`] is used instead
+    opaque_hint: Option<Rect>,
+    /// Whether or not every one of the window's pixels is fully opaque, only kept up to date while there is no opaque hint
+    pixels_opaque: bool,
+    /// How the alpha values of the window's pixels apply to their colors
+    alpha_format: AlphaFormat,
 }
 
-impl Drop for Window {
-    fn drop(&mut self) {
-        safa_api::syscalls::resources::destroy_resource(self.shm_ri)
-            .expect("SHM was dropped before Window was dropped");
-        safa_api::syscalls::resources::destroy_resource(self.mmap_ri)
-            .expect("MMAP was dropped before Window was dropped");
-    }
-}
-
-unsafe impl Send for Window {}
-unsafe impl Sync for Window {}
-
 impl Window {
     /// Returns a new instance of the Window with the given command pipe to send events to.
     pub fn with_com_pipe(mut self, com_pipe: Arc<ClientComPipe>) -> Self {
@@ -61,15 +86,101 @@ impl Window {
         self
     }
 
+    /// Returns a new instance of the Window with pixels in the alpha format `format`
+    pub fn with_alpha_format(mut self, format: AlphaFormat) -> Self {
+        self.alpha_format = format;
+        self
+    }
+
+    /// Returns a new instance of the Window surrounded by server-side decorations
+    pub fn with_decorations(mut self) -> Self {
+        self.decorations = Some(Decorations::new(self.width, self.height));
+        self
+    }
+
+    /// Returns the offset of the window's pixels from the top-left corner of the window (including the decorations)
+    const fn client_offset(&self) -> (usize, usize) {
+        match self.decorations {
+            Some(_) => Decorations::client_offset(),
+            None => (0, 0),
+        }
+    }
+
+    /// Returns the width of the whole window including the decorations
+    const fn outer_width(&self) -> usize {
+        match &self.decorations {
+            Some(decorations) => decorations.outer_width(),
+            None => self.width,
+        }
+    }
+
+    /// Returns the height of the whole window including the decorations
+    const fn outer_height(&self) -> usize {
+        match &self.decorations {
+            Some(decorations) => decorations.outer_height(),
+            None => self.height,
+        }
+    }
+
+    /// Converts a position relative to the whole window to a position relative to the window's pixels,
+    /// the results are clamped to the window's pixels.
+    pub fn to_client_coords(&self, x: usize, y: usize) -> (usize, usize) {
+        let (client_x, client_y) = self.client_offset();
+        (
+            x.saturating_sub(client_x).min(self.width.saturating_sub(1)),
+            y.saturating_sub(client_y)
+                .min(self.height.saturating_sub(1)),
+        )
+    }
+
+    /// Returns the part of the window the position (`x`, `y`) relative to the whole window is in
+    pub fn hit_test(&self, x: usize, y: usize) -> DecorationHit {
+        match &self.decorations {
+            Some(decorations) => decorations.hit_test(x, y),
+            None => DecorationHit::Client,
+        }
+    }
+
+    /// Updates the focus state displayed by the decorations if any
+    fn set_decorations_focused(&mut self, focused: bool) -> bool {
+        self.decorations
+            .as_mut()
+            .is_some_and(|decorations| decorations.set_focused(focused))
+    }
+
+    /// Returns the ID of the client that owns this window, windows created by the WM itself have no owner.
+    pub fn owner(&self) -> Option<ClientID> {
+        self.com_pipe.as_ref().map(|pipe| pipe.id())
+    }
+
     /// A shared memory key that lives as long as the window itself, and can be used to access the window's pixels.
-    pub const fn shm_key(&self) -> &usize {
-        &self.shm_key
+    pub const fn shm_key(&self) -> usize {
+        self.pixels.key()
     }
 
-    /// Sends an event to the client that owns this window.
+    /// Sets the title of the window, returns true if the window needs to be redrawn.
+    fn set_title(&mut self, title: String) -> bool {
+        let redraw = match &mut self.decorations {
+            Some(decorations) => {
+                decorations.set_title(&title);
+                true
+            }
+            None => false,
+        };
+
+        self.title = title;
+        redraw
+    }
+
+    /// Sends an event to the client that owns this window, never blocks,
+    /// the event is queued after the client's other responses if the client isn't receiving fast enough.
+    ///
+    /// A client that falls too far behind is disconnected by the [`crate::com::listener::Listener`].
     pub fn send_event(&self, event: Event) {
         if let Some(com_pipe) = &self.com_pipe {
-            if let Err(err) = com_pipe.sender().send_response(Response::Event(event))
+            let envelope = EventEnvelope::new(event, self.id, clock::timestamp());
+            if let Err(err) = com_pipe.sender().send_response(&Response::Event(envelope))
+                && err.kind() != ErrorKind::WouldBlock
                 && err.kind() != ErrorKind::ConnectionAborted
                 && err.kind() != ErrorKind::ConnectionReset
             {
@@ -79,39 +190,9 @@ impl Window {
         }
     }
 
-    fn allocate_pixel_buffer(
-        width: usize,
-        height: usize,
-        fill_pixel: Pixel,
-    ) -> (NonNull<[Pixel]>, usize, usize, usize) {
-        let pixels_required = width * height;
-        let bytes_required = pixels_required * size_of::<Pixel>();
-        let pages_required = bytes_required.div_ceil(4096);
-
-        let (shm_key, shm_ri) =
-            safa_api::syscalls::mem::shm_create(pages_required, ShmFlags::from_bits_retaining(0))
-                .expect("Failed to create a new shared mem mapping for a Window");
-
-        let (mmap_ri, pixels_bytes) = safa_api::syscalls::mem::map(
-            core::ptr::null(),
-            pages_required,
-            0,
-            Some(shm_ri),
-            None,
-            MemMapFlags::WRITE,
-        )
-        .expect("Failed to memmap a new Window's pixels");
-
-        let mut pixels =
-            NonNull::slice_from_raw_parts(pixels_bytes.cast::<Pixel>(), pixels_required);
-        unsafe {
-            pixels.as_mut().fill(fill_pixel);
-        }
-        (pixels, shm_ri, mmap_ri, shm_key)
-    }
-
-    /// Creates a new Window from a given BMP Image
-    pub fn new_from_bmp(pos_x: usize, pos_y: usize, image: BMPImage) -> Window {
+    /// Creates a new Window from a given Image
+    #[allow(dead_code)]
+    pub fn new_from_image(pos_x: usize, pos_y: usize, image: Image) -> Window {
         Self::new_from_pixels(pos_x, pos_y, image.width(), image.height(), image.pixels())
     }
 
@@ -123,12 +204,11 @@ impl Window {
         height: usize,
         fill_pixels: impl ExactSizeIterator + Iterator<Item = Pixel>,
     ) -> Window {
-        let (mut pixels, shm_ri, mmap_ri, shm_key) =
-            Self::allocate_pixel_buffer(width, height, Pixel::from_hex(0));
-        let pixels_mut = unsafe { pixels.as_mut() };
+        let mut pixels = SharedPixels::new(width * height, Pixel::from_hex(0));
+        let pixels_mut = pixels.as_mut_slice();
 
         assert_eq!(
-            pixels.len(),
+            pixels_mut.len(),
             fill_pixels.len(),
             "The pixels to fill with must have a length of width*height"
         );
@@ -137,6 +217,7 @@ impl Window {
         for (i, pi) in fill_pixels {
             pixels_mut[i] = pi;
         }
+        let pixels_opaque = pixels_mut.iter().all(Pixel::is_opaque);
 
         Window {
             pos_x,
@@ -144,10 +225,20 @@ impl Window {
             width,
             height,
             pixels,
-            shm_key,
-            shm_ri,
-            mmap_ri,
             com_pipe: None,
+            id: 0,
+            decorations: None,
+            minimized: false,
+            restore_geometry: None,
+            title: String::new(),
+            app_id: String::new(),
+            pending_configure: None,
+            configure_serial: 0,
+            cursor: CursorShape::Arrow,
+            frame_callback_requested: false,
+            opaque_hint: None,
+            alpha_format: AlphaFormat::Straight,
+            pixels_opaque,
         }
     }
 
@@ -159,7 +250,7 @@ impl Window {
         height: usize,
         pixel: Pixel,
     ) -> Self {
-        let (pixels, shm_ri, mmap_ri, shm_key) = Self::allocate_pixel_buffer(width, height, pixel);
+        let pixels = SharedPixels::new(width * height, pixel);
 
         Window {
             pos_x,
@@ -167,176 +258,233 @@ impl Window {
             width,
             height,
             pixels,
-            shm_ri,
-            mmap_ri,
-            shm_key,
             com_pipe: None,
+            id: 0,
+            decorations: None,
+            minimized: false,
+            restore_geometry: None,
+            title: String::new(),
+            app_id: String::new(),
+            pending_configure: None,
+            configure_serial: 0,
+            cursor: CursorShape::Arrow,
+            frame_callback_requested: false,
+            opaque_hint: None,
+            alpha_format: AlphaFormat::Straight,
+            pixels_opaque: pixel.is_opaque(),
         }
     }
 
-    /// Draws the whole window without syncing the results to the real framebuffer.
+    /// Reallocates the window's pixels to fit `width`*`height` pixels, keeping the old pixels that still fit.
+    ///
+    /// Returns the new shared memory key, the old one is destroyed.
+    fn resize(&mut self, width: usize, height: usize) -> usize {
+        let mut pixels = SharedPixels::new(width * height, Pixel::from_hex(0));
+
+        let new_pixels = pixels.as_mut_slice();
+        let old_pixels = self.client_pixels();
+
+        let copy_width = width.min(self.width);
+        for row in 0..height.min(self.height) {
+            let new_row = row * width;
+            let old_row = row * self.width;
+            new_pixels[new_row..new_row + copy_width]
+                .copy_from_slice(&old_pixels[old_row..old_row + copy_width]);
+        }
+
+        if let Some(decorations) = &mut self.decorations {
+            decorations.resize(width, height);
+        }
+
+        self.width = width;
+        self.height = height;
+        // The old pixels are destroyed once dropped
+        self.pixels = pixels;
+        self.pixels_opaque = self.pixels_are_opaque();
+        self.pixels.key()
+    }
+
+    /// Draws the whole window's pixels without syncing the results to the real framebuffer,
+    /// as if the window was at (`pos_x`, `pos_y`).
     ///
     /// [`fb.sync_pixels_rect`] must be called afterwards on the area the window is in.
-    fn draw(&self, fb: &mut Framebuffer) {
-        fb.draw_rect(self.pos_x, self.pos_y, self.width, self.height, unsafe {
-            self.pixels.as_ref()
-        });
+    fn draw_client(&self, fb: &mut dyn DisplayBackend, pos_x: usize, pos_y: usize) {
+        let (client_x, client_y) = self.client_offset();
+        fb.draw_rect(
+            pos_x + client_x,
+            pos_y + client_y,
+            self.width,
+            self.height,
+            self.client_pixels(),
+            self.alpha_format,
+        );
     }
 
-    /// Draws the window from intersection point without syncing the results to the real framebuffer.
+    /// Draws the window's pixels within `rect` (relative to the window's pixels) without syncing the results to the real framebuffer,
+    /// as if the window was at (`pos_x`, `pos_y`).
     ///
     /// [`fb.sync_pixels_rect`] must be called afterwards on the area the window is in.
-    fn draw_at(&self, fb: &mut Framebuffer, point: IntersectionPoint) {
-        let (top_x_within, top_y_within) = point.top_left_within;
-        let width = point.width();
-        let height = point.height();
+    fn draw_client_at(&self, fb: &mut dyn DisplayBackend, rect: Rect, pos_x: usize, pos_y: usize) {
+        let (top_x_within, top_y_within) = (rect.x, rect.y);
+        let width = rect.width;
+        let height = rect.height;
 
-        let pixels = &self.pixels;
+        let pixels = self.client_pixels();
         let pixels_width = self.width;
         let pixels_height = self.height;
 
         if width == pixels_width && height == pixels_height {
-            return self.draw(fb);
+            return self.draw_client(fb, pos_x, pos_y);
         }
 
         // The offset within the FB is the offset of self + the point
-        let off_x = self.pos_x + top_x_within;
-        let off_y = self.pos_y + top_y_within;
+        let (client_x, client_y) = self.client_offset();
+        let off_x = pos_x + client_x + top_x_within;
+        let off_y = pos_y + client_y + top_y_within;
 
-        // We want to draw pixels that `point` cover only
+        // We want to draw pixels that `rect` cover only
         fb.draw_rect_within(
             off_x,
             off_y,
             width,
             height,
-            unsafe { pixels.as_ref() },
+            pixels,
             pixels_width,
             pixels_height,
             top_x_within,
             top_y_within,
+            self.alpha_format,
         );
     }
 
-    /// Returns the damage a window may have caused on the framebuffer, if it's position or dimensions changed
-    /// There is 2 damages: The damage before the operation, The damage after the operation
-    fn damage(&self) -> DamageRegion {
-        DamageRegion {
-            pos_x: self.pos_x,
-            pos_y: self.pos_y,
-            width: self.width,
-            height: self.height,
+    /// Draws the window (including the decorations) within `rect` (relative to the whole window) without syncing the results to the real framebuffer,
+    /// as if the window was at (`pos_x`, `pos_y`).
+    ///
+    /// [`fb.sync_pixels_rect`] must be called afterwards on the area the window is in.
+    fn draw_at(&self, fb: &mut dyn DisplayBackend, rect: Rect, pos_x: usize, pos_y: usize) {
+        if let Some(decorations) = &self.decorations {
+            for part in decorations.frame_parts() {
+                let Some(part_rect) =
+                    rect.intersection(&Rect::new(part.x, part.y, part.width, part.height))
+                else {
+                    continue;
+                };
+
+                fb.draw_rect_within(
+                    pos_x + part_rect.x,
+                    pos_y + part_rect.y,
+                    part_rect.width,
+                    part_rect.height,
+                    decorations.pixels(),
+                    decorations.outer_width(),
+                    decorations.outer_height(),
+                    part_rect.x,
+                    part_rect.y,
+                    AlphaFormat::Straight,
+                );
+            }
         }
-    }
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct IntersectionPoint {
-    top_left_within: (usize, usize),
-    bottom_right_within: (usize, usize),
-}
 
-impl IntersectionPoint {
-    pub const fn none() -> Self {
-        Self {
-            top_left_within: (0, 0),
-            bottom_right_within: (0, 0),
+        let (client_x, client_y) = self.client_offset();
+        if let Some(client_rect) =
+            rect.intersection(&Rect::new(client_x, client_y, self.width, self.height))
+        {
+            self.draw_client_at(
+                fb,
+                client_rect.relative_to(client_x, client_y),
+                pos_x,
+                pos_y,
+            );
         }
     }
 
-    pub const fn width(&self) -> usize {
-        let (top_x, _) = self.top_left_within;
-        let (bott_x, _) = self.bottom_right_within;
-        bott_x - top_x
+    /// Returns the window's `width`*`height` pixels, the buffer they are in may be larger (see [`Windows::set_window_pixels`])
+    fn client_pixels(&self) -> &[Pixel] {
+        &self.pixels.as_slice()[..self.width * self.height]
     }
 
-    pub const fn height(&self) -> usize {
-        let (_, top_y) = self.top_left_within;
-        let (_, bott_y) = self.bottom_right_within;
-        bott_y - top_y
+    /// Returns true if every one of the window's pixels is fully opaque
+    fn pixels_are_opaque(&self) -> bool {
+        self.client_pixels().iter().all(Pixel::is_opaque)
     }
 
-    /// Returns the x-coordinate of the intersection point, from the top-left corner.
-    pub const fn x(&self) -> usize {
-        let (top_x, _) = self.top_left_within;
-        top_x
-    }
+    /// Updates [`Self::pixels_opaque`] after the pixels within `rect` (relative to the window's pixels) were changed by the client
+    fn update_pixels_opaque(&mut self, rect: Rect) {
+        if self.opaque_hint.is_some() {
+            return;
+        }
 
-    /// Returns the y-coordinate of the intersection point, from the top-left corner.
-    pub const fn y(&self) -> usize {
-        let (_, top_y) = self.top_left_within;
-        top_y
+        self.pixels_opaque = if self.pixels_opaque {
+            // The pixels outside of `rect` are still opaque
+            let pixels = self.client_pixels();
+            (rect.y..rect.bottom()).all(|row| {
+                let row_start = row * self.width;
+                pixels[row_start + rect.x..row_start + rect.right()]
+                    .iter()
+                    .all(Pixel::is_opaque)
+            })
+        } else {
+            self.pixels_are_opaque()
+        };
     }
-}
 
-impl Add<IntersectionPoint> for IntersectionPoint {
-    type Output = IntersectionPoint;
-    fn add(self, rhs: IntersectionPoint) -> Self::Output {
-        let (s_top_x, s_top_y) = self.top_left_within;
-        let (o_top_x, o_top_y) = rhs.top_left_within;
-        let (s_bott_x, s_bott_y) = self.bottom_right_within;
-        let (o_bott_x, o_bott_y) = rhs.bottom_right_within;
-        Self {
-            top_left_within: (s_top_x.min(o_top_x), s_top_y.min(o_top_y)),
-            bottom_right_within: (s_bott_x.max(o_bott_x), s_bott_y.max(o_bott_y)),
+    /// Returns the part of the screen the window covers with fully opaque pixels, nothing below it has to be drawn there
+    fn opaque_region(&self) -> Region {
+        let mut region = Region::new();
+        if self.minimized {
+            return region;
         }
-    }
-}
-
-impl Sum for IntersectionPoint {
-    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
-        let mut results = IntersectionPoint::none();
 
-        for i in iter {
-            if results == IntersectionPoint::none() {
-                results = i;
-            } else {
-                results = results + i;
+        // The decorations are always opaque
+        if let Some(decorations) = &self.decorations {
+            for part in decorations.frame_parts() {
+                region.union_rect(Rect::new(
+                    self.pos_x + part.x,
+                    self.pos_y + part.y,
+                    part.width,
+                    part.height,
+                ));
             }
         }
 
-        results
-    }
-}
-
-#[derive(Debug, Clone, Copy)]
-struct DamageRegion {
-    pos_x: usize,
-    pos_y: usize,
-    width: usize,
-    height: usize,
-}
+        let client_rect = Rect::new(0, 0, self.width, self.height);
+        let opaque_client_rect = match &self.opaque_hint {
+            Some(hint) => hint.intersection(&client_rect),
+            None => self.pixels_opaque.then_some(client_rect),
+        };
 
-impl DamageRegion {
-    #[inline]
-    /// Checks if self overlaps with `win` returning the point which is covered from the window
-    pub fn overlaps_with(&self, win: &Window) -> Option<IntersectionPoint> {
-        let d_x0 = self.pos_x;
-        let d_x1 = self.pos_x + self.width;
-        let d_y0 = self.pos_y;
-        let d_y1 = self.pos_y + self.height;
-
-        let w_x0 = win.pos_x;
-        let w_x1 = win.pos_x + win.width;
-        let w_y0 = win.pos_y;
-        let w_y1 = win.pos_y + win.height;
-
-        if (d_x0 < w_x1 && d_x1 > w_x0) && (d_y0 < w_y1 && d_y1 > w_y0) {
-            let i_x0 = d_x0.max(w_x0) - w_x0;
-            let i_x1 = d_x1.min(w_x1) - w_x0;
-            let i_y0 = d_y0.max(w_y0) - w_y0;
-            let i_y1 = d_y1.min(w_y1) - w_y0;
-
-            Some(IntersectionPoint {
-                top_left_within: (i_x0, i_y0),
-                bottom_right_within: (i_x1, i_y1),
-            })
-        } else {
-            None
+        if let Some(rect) = opaque_client_rect {
+            let (client_x, client_y) = self.client_offset();
+            region.union_rect(Rect::new(
+                self.pos_x + client_x + rect.x,
+                self.pos_y + client_y + rect.y,
+                rect.width,
+                rect.height,
+            ));
         }
+        region
+    }
+
+    /// Returns the damage a window may have caused on the framebuffer, if it's position or dimensions changed
+    /// There is 2 damages: The damage before the operation, The damage after the operation
+    fn damage(&self) -> Rect {
+        Rect::new(
+            self.pos_x,
+            self.pos_y,
+            self.outer_width(),
+            self.outer_height(),
+        )
     }
 }
 
-const MAX_WINDOW_ID: usize = 1024 /* TODO: more windows? */;
+/// The maximum amount of windows that can exist at once unless configured otherwise, see [`Windows::set_max_windows`]
+pub const DEFAULT_MAX_WINDOWS: usize = 1024;
+/// The most windows that can exist at once, at which point every [`WinID`] is in use
+pub const MAX_WINDOWS_LIMIT: usize = WinID::MAX as usize + 1;
+/// The minimum width of a window's pixels the user can resize a window to
+pub const MIN_WINDOW_WIDTH: usize = 80;
+/// The minimum height of a window's pixels the user can resize a window to
+pub const MIN_WINDOW_HEIGHT: usize = 32;
 /// A window ID
 pub type WinID = u16;
 
@@ -357,11 +505,19 @@ pub struct Windows {
     normal_windows: IndexSet<WinID, FxBuildHasher>,
 
     /// A list of window IDs
-    /// currently stored using a Bitmap and the max is 1024
-    window_ids: [u128; 8],
+    /// currently stored using a Bitmap that grows as needed up to `max_windows` bits
+    window_ids: Vec<u128>,
+    max_windows: usize,
     focused_window: Option<WinID>,
-
-    damaged_regions: Vec<DamageRegion>,
+    /// The minimized windows, the most recently minimized window comes last
+    minimized_windows: Vec<WinID>,
+
+    /// The parts of the screen that need to be redrawn
+    damage: Region,
+    /// The windows drawn since the last frame was presented, see [`Self::frame_done`]
+    presented_windows: Vec<WinID>,
+    /// The layer behind every window
+    wallpaper: Wallpaper,
 }
 
 impl Windows {
@@ -370,51 +526,70 @@ impl Windows {
             overlay_windows: IndexSet::with_hasher(FxBuildHasher),
             normal_windows: IndexSet::with_hasher(FxBuildHasher),
             focused_window: None,
+            minimized_windows: Vec::new(),
 
-            damaged_regions: Vec::new(),
+            damage: Region::new(),
+            presented_windows: Vec::new(),
+            wallpaper: Wallpaper::DEFAULT,
             windows: HashMap::with_hasher(FxBuildHasher),
-            window_ids: [0; 8],
+            window_ids: Vec::new(),
+            max_windows: DEFAULT_MAX_WINDOWS,
         }
     }
 
     #[inline]
-    fn insert_damage(&mut self, regions: &[DamageRegion]) {
-        // Faster than extend_from_slice for some reason (I checked the code they aren't reserving the additional elements)
-        self.damaged_regions.reserve(regions.len());
-        self.damaged_regions.extend_from_slice(regions);
+    fn insert_damage(&mut self, rects: &[Rect]) {
+        for rect in rects {
+            self.damage.union_rect(*rect);
+        }
 
         SHOULD_REDRAW.store(true, Ordering::Release);
     }
 
+    /// Sets the maximum amount of windows that can exist at once, clamped to [`MAX_WINDOWS_LIMIT`].
+    ///
+    /// Windows that already exist are kept even if there are more than `max_windows` of them.
+    pub fn set_max_windows(&mut self, max_windows: usize) {
+        self.max_windows = max_windows.min(MAX_WINDOWS_LIMIT);
+    }
+
     /// Allocates a new Window ID
     fn add_id(&mut self) -> Option<WinID> {
-        for (row, byte) in self.window_ids.iter_mut().enumerate() {
-            let width = size_of_val(byte) * 8;
+        const WIDTH: usize = u128::BITS as usize;
 
-            for col in 0..width {
-                let bit = ((*byte >> col) & 1) == 1;
-                if !bit {
-                    *byte |= 1 << col;
-                    return Some((col + (row * width)) as WinID);
+        for (row, byte) in self.window_ids.iter_mut().enumerate() {
+            let col = byte.trailing_ones() as usize;
+            if col < WIDTH {
+                let id = col + (row * WIDTH);
+                if id >= self.max_windows {
+                    return None;
                 }
+
+                *byte |= 1 << col;
+                return Some(id as WinID);
             }
         }
 
-        None
+        // Every allocated row is full
+        let id = self.window_ids.len() * WIDTH;
+        if id >= self.max_windows {
+            return None;
+        }
+
+        self.window_ids.push(1);
+        Some(id as WinID)
     }
 
     /// Deallocate an existing Window ID
     /// returns true if successful, false if the ID is invalid
     fn remove_id(&mut self, id: WinID) -> bool {
-        if id as usize >= MAX_WINDOW_ID {
-            return false;
-        }
-
-        let width = size_of_val(&self.window_ids[0]);
-        let row = (id / width as u16) as usize;
-        let col = (id % width as u16) as usize;
+        const WIDTH: usize = u128::BITS as usize;
+        let row = id as usize / WIDTH;
+        let col = id as usize % WIDTH;
 
-        let byte = &mut self.window_ids[row];
+        let Some(byte) = self.window_ids.get_mut(row) else {
+            return false;
+        };
         let bit = ((*byte >> col) & 1) == 1;
         let will_succeed = bit;
 
@@ -427,112 +602,142 @@ impl Windows {
 
     /// Redraw the damage caused by (and apply the results of) playing around with the windows using `self`
     pub fn damage_redraw(&mut self) {
-        if self.damaged_regions.is_empty() {
+        if self.damage.is_empty() {
             return;
         }
 
         let mut fb = framebuffer::framebuffer();
+        self.damage_redraw_to(&mut **fb);
+    }
 
-        let damage = core::mem::take(&mut self.damaged_regions);
-
-        for region in &damage {
-            // Clear the damaged region
-            fb.draw_rect_filled_with(
-                region.pos_x,
-                region.pos_y,
-                region.width,
-                region.height,
-                BG_PIXEL,
-            );
-        }
+    /// Same as [`Self::damage_redraw`] but draws to the given display backend `fb` instead of the global one.
+    pub fn damage_redraw_to(&mut self, fb: &mut dyn DisplayBackend) {
+        // Windows may hang off the screen, only what is on the screen can be redrawn
+        let screen = Rect::new(0, 0, fb.width(), fb.height());
+        let damage = core::mem::take(&mut self.damage).intersect_rect(&screen);
+
+        // Walk the windows from the top-most to the bottom-most (overlay windows come on top of other windows),
+        // each window only has to fix the damage that isn't covered by opaque windows above it
+        let mut covered = Region::new();
+        let mut visible = Vec::new();
+
+        let z_ordering = self
+            .overlay_windows
+            .iter()
+            .rev()
+            .chain(self.normal_windows.iter().rev());
+        for win_id in z_ordering {
+            let (win, _) = self
+                .windows
+                .get(win_id)
+                .expect("Window wasn't removed from the Z-Ordering when it was removed");
+            if win.minimized {
+                continue;
+            }
 
-        // Fixes all the damages caused on a window if any
-        macro_rules! fix_window {
-            ($win: expr) => {{
-                let win = $win;
-                let intersection: IntersectionPoint =
-                    damage.iter().filter_map(|d| d.overlaps_with(&win)).sum();
+            let mut win_damage = damage.intersect_rect(&win.damage());
+            win_damage.subtract(&covered);
+            if win_damage.is_empty() {
+                continue;
+            }
 
-                if intersection != IntersectionPoint::none() {
-                    win.draw_at(&mut fb, intersection);
-                }
-            }};
+            covered.union(&win.opaque_region().intersect(&damage));
+            visible.push((*win_id, win, win_damage));
         }
 
-        for win_id in &self.normal_windows {
-            let (window, _) = self
-                .windows
-                .get_mut(win_id)
-                .expect("Window wasn't removed from the Z-Ordering when it was removed");
-            fix_window!(window);
+        // Repair the damage no opaque window covers from the wallpaper
+        let mut background = damage.clone();
+        background.subtract(&covered);
+        for rect in background.rects() {
+            self.wallpaper.draw(fb, *rect);
         }
 
-        // Overlay on top of other windows
-        for win_id in &self.overlay_windows {
-            let (window, _) = self
-                .windows
-                .get_mut(win_id)
-                .expect("Overlay window wasn't removed from the Z-Ordering when it was removed");
-            fix_window!(window);
+        // Fixes all the damages caused on the windows back-to-front,
+        // the damaged rectangles never overlap so each damaged pixel of a window is drawn at most once
+        let mut drawn = Vec::with_capacity(visible.len());
+        for (win_id, win, win_damage) in visible.into_iter().rev() {
+            for rect in win_damage.rects() {
+                win.draw_at(
+                    fb,
+                    rect.relative_to(win.pos_x, win.pos_y),
+                    win.pos_x,
+                    win.pos_y,
+                );
+            }
+            drawn.push(win_id);
         }
+        self.presented_windows.extend(drawn);
 
-        for r in damage {
-            fb.sync_pixels_rect(r.pos_x, r.pos_y, r.width, r.height);
+        for r in damage.rects() {
+            fb.sync_pixels_rect(r.x, r.y, r.width, r.height);
         }
 
         SHOULD_REDRAW.store(false, Ordering::Release);
     }
 
+    /// Replaces the layer behind every window with `wallpaper`, redrawing the whole screen
+    pub fn set_wallpaper(&mut self, wallpaper: Wallpaper) {
+        self.wallpaper = wallpaper;
+
+        let (width, height) = framebuffer::screen_size();
+        self.insert_damage(&[Rect::new(0, 0, width, height)]);
+    }
+
     /// Adds `x` to window with the ID  `win_id` x position and `y` to the window with the ID `win_id`'s Y position
     ///
     /// Returns the new position if the Window ID exist
     pub fn add_cord(&mut self, win_id: WinID, x: i32, y: i32) -> Option<(usize, usize)> {
-        let (win, _) = self.windows.get_mut(&win_id)?;
+        let (win, _) = self.windows.get(&win_id)?;
 
-        /* The guarantee that this will be successful, is that we have a mutable reference on Self and that all access on the Window will be performed from Self */
         if x == 0 && y == 0 {
             return Some((win.pos_x, win.pos_y));
         }
 
+        let new_x = win.pos_x.saturating_add_signed(x as isize);
+        let new_y = win.pos_y.saturating_add_signed(y as isize);
+        self.move_window(win_id, new_x, new_y)
+    }
+
+    /// Moves the window with the ID `win_id` to the position (`x`, `y`), the position is clamped so the window stays within the screen.
+    ///
+    /// Returns the new position if the Window ID exist
+    pub fn move_window(&mut self, win_id: WinID, x: usize, y: usize) -> Option<(usize, usize)> {
+        let (win, _) = self.windows.get_mut(&win_id)?;
+
+        /* The guarantee that this will be successful, is that we have a mutable reference on Self and that all access on the Window will be performed from Self */
         let damage0 = win.damage();
 
-        let max_x = FB_INFO.width;
-        let max_y = FB_INFO.height;
+        let (max_x, max_y) = framebuffer::screen_size();
 
-        win.pos_x = std::cmp::min(
-            win.pos_x.saturating_add_signed(x as isize),
-            max_x - win.width,
-        );
-        win.pos_y = std::cmp::min(
-            win.pos_y.saturating_add_signed(y as isize),
-            max_y - win.height,
-        );
+        win.pos_x = std::cmp::min(x, max_x.saturating_sub(win.outer_width()));
+        win.pos_y = std::cmp::min(y, max_y.saturating_sub(win.outer_height()));
 
-        if win.pos_x == damage0.pos_x && win.pos_y == damage0.pos_y {
+        if win.pos_x == damage0.x && win.pos_y == damage0.y {
             return Some((win.pos_x, win.pos_y));
         }
 
         let damage1 = win.damage();
 
-        if REALLY_VERBOSE {
+        if logging::log_enabled(LogLevel::Verbose) {
             dlog!(
                 "window changed from x: {}, y: {} to x: {}, y: {} as per: {x}, {y}",
-                damage0.pos_x,
-                damage0.pos_y,
-                damage1.pos_x,
-                damage1.pos_y
+                damage0.x,
+                damage0.y,
+                damage1.x,
+                damage1.y
             );
         }
 
         self.insert_damage(&[damage0, damage1]);
-        Some((damage1.pos_x, damage1.pos_y))
+        Some((damage1.x, damage1.y))
     }
 
     /// Adds a window and organizes it depending on `kind` (see [`WindowKind`])
-    pub fn add_window(&mut self, window: Window, kind: WindowKind) -> Option<WinID> {
+    pub fn add_window(&mut self, mut window: Window, kind: WindowKind) -> Option<WinID> {
         let damage = window.damage();
 
         let id = self.add_id()?;
+        window.id = id;
         self.windows.insert(id, (window, kind));
 
         match kind {
@@ -549,18 +754,25 @@ impl Windows {
     /// Set the window with the id `win_id` as focused,
     /// handles everything including sending events and damage, and reordering the Z-list.
     pub fn set_focused(&mut self, win_id: WinID) -> bool {
-        let Some((window, window_kind)) = self.windows.get(&win_id) else {
+        let Some((window, window_kind)) = self.windows.get_mut(&win_id) else {
             return false;
         };
 
+        let window_kind = *window_kind;
         let old_value = self.focused_window.replace(win_id);
         window.send_event(Event::WindowFocused);
+        window.set_decorations_focused(true);
         let damage0 = window.damage();
 
         if let Some(old_id) = old_value
-            && let Some((win, _)) = self.windows.get(&old_id)
+            && old_id != win_id
+            && let Some((win, _)) = self.windows.get_mut(&old_id)
         {
             win.send_event(Event::WindowUnfocused);
+            if win.set_decorations_focused(false) {
+                let damage = win.damage();
+                self.insert_damage(&[damage]);
+            }
         }
 
         match window_kind {
@@ -581,37 +793,293 @@ impl Windows {
     /// Unfocus the currently focused window.
     pub fn unfocus_current(&mut self) {
         if let Some(win_id) = self.focused_window.take() {
-            if let Some((win, _)) = self.windows.get(&win_id) {
+            if let Some((win, _)) = self.windows.get_mut(&win_id) {
                 win.send_event(Event::WindowUnfocused);
-                self.insert_damage(&[win.damage()]);
+                win.set_decorations_focused(false);
+                let damage = win.damage();
+                self.insert_damage(&[damage]);
             }
         }
     }
 
-    /// Returns the ID of the top-most window that is in contact with the given position and size if any.
+    /// Returns the ID of the top-most window that is in contact with the given position and size if any,
+    /// and the part of the window (relative to the whole window) that is in contact.
     pub fn window_in_contact(
         &self,
         pos_x: usize,
         pos_y: usize,
         width: usize,
         height: usize,
-    ) -> Option<(WinID, IntersectionPoint)> {
-        let region = DamageRegion {
-            pos_x,
-            pos_y,
-            width,
-            height,
-        };
+    ) -> Option<(WinID, Rect)> {
+        let contact = Rect::new(pos_x, pos_y, width, height);
 
         self.normal_windows.iter().rev().find_map(|win_id| {
             let (win, _) = self
                 .windows
                 .get(win_id)
                 .expect("Window wasn't removed from the Z-ordering when it's ID was deallocated");
-            region.overlaps_with(win).map(|point| (*win_id, point))
+            if win.minimized {
+                return None;
+            }
+
+            contact
+                .intersection(&win.damage())
+                .map(|rect| (*win_id, rect.relative_to(win.pos_x, win.pos_y)))
         })
     }
 
+    /// Returns the part of the window with the ID `win_id` the position (`x`, `y`) relative to the whole window is in,
+    /// and that position converted to be relative to the window's pixels.
+    pub fn hit_test(
+        &self,
+        win_id: WinID,
+        x: usize,
+        y: usize,
+    ) -> Option<(DecorationHit, usize, usize)> {
+        let (win, _) = self.windows.get(&win_id)?;
+        let (client_x, client_y) = win.to_client_coords(x, y);
+        Some((win.hit_test(x, y), client_x, client_y))
+    }
+
+    /// Minimizes the window with the ID `win_id`, the window is no longer displayed until it is restored using [`Self::restore_last_minimized`]
+    pub fn minimize(&mut self, win_id: WinID) -> Result<(), ()> {
+        let (win, _) = self.windows.get_mut(&win_id).ok_or(())?;
+        if win.minimized {
+            return Ok(());
+        }
+
+        win.minimized = true;
+        win.send_event(Event::WindowMinimized);
+        let damage = win.damage();
+
+        if self.focused_window == Some(win_id) {
+            self.unfocus_current();
+        }
+
+        self.minimized_windows.push(win_id);
+        self.insert_damage(&[damage]);
+        Ok(())
+    }
+
+    /// Restores the most recently minimized window that still exists and focuses it, returns its ID if any
+    pub fn restore_last_minimized(&mut self) -> Option<WinID> {
+        while let Some(win_id) = self.minimized_windows.pop() {
+            let Some((win, _)) = self.windows.get_mut(&win_id) else {
+                continue;
+            };
+
+            // The ID might have been reused by a window that was never minimized
+            if !win.minimized {
+                continue;
+            }
+
+            win.minimized = false;
+            win.send_event(Event::WindowRestored);
+            self.set_focused(win_id);
+            return Some(win_id);
+        }
+
+        None
+    }
+
+    /// Maximizes the window with the ID `win_id` to fill the whole screen, or restores it's old position and size if it is already maximized.
+    ///
+    /// The client is asked to resize the window like any other resize, see [`Self::configure_window`].
+    pub fn toggle_maximize(&mut self, win_id: WinID) -> Result<(), ()> {
+        let (win, _) = self.windows.get_mut(&win_id).ok_or(())?;
+
+        let (x, y, width, height) = match win.restore_geometry.take() {
+            Some(geometry) => geometry,
+            None => {
+                win.restore_geometry = Some((win.pos_x, win.pos_y, win.width, win.height));
+
+                let (screen_width, screen_height) = framebuffer::screen_size();
+                let extra_width = win.outer_width() - win.width;
+                let extra_height = win.outer_height() - win.height;
+                (
+                    0,
+                    0,
+                    screen_width - extra_width,
+                    screen_height - extra_height,
+                )
+            }
+        };
+
+        self.configure_window(win_id, x, y, width, height)
+    }
+
+    /// Sets the shape of the cursor while it is over the pixels of the window with the ID `win_id`
+    pub fn set_window_cursor(&mut self, win_id: WinID, shape: CursorShape) -> Result<(), ()> {
+        let (win, _) = self.windows.get_mut(&win_id).ok_or(())?;
+        win.cursor = shape;
+        Ok(())
+    }
+
+    /// Returns the shape of the cursor while it is over the pixels of the window with the ID `win_id`
+    pub fn window_cursor(&self, win_id: WinID) -> Option<CursorShape> {
+        self.windows.get(&win_id).map(|(win, _)| win.cursor)
+    }
+
+    /// Returns the position of the whole window with the ID `win_id` and the size of it's pixels
+    pub fn geometry(&self, win_id: WinID) -> Option<(usize, usize, usize, usize)> {
+        let (win, _) = self.windows.get(&win_id)?;
+        Some((win.pos_x, win.pos_y, win.width, win.height))
+    }
+
+    /// Replaces the pixels of the window with the ID `win_id` with `pixels` that has the size `width`*`height`,
+    /// the window's pixels are only reallocated if they can't fit the new pixels.
+    ///
+    /// Only for windows without an owner, the buffer may end up larger than the window's pixels which clients don't expect.
+    pub fn set_window_pixels(
+        &mut self,
+        win_id: WinID,
+        width: usize,
+        height: usize,
+        pixels: &[Pixel],
+    ) -> Result<(), ()> {
+        let (win, _) = self.windows.get_mut(&win_id).ok_or(())?;
+        let damage0 = win.damage();
+
+        if width * height > win.pixels.as_slice().len() {
+            win.resize(width, height);
+        } else {
+            win.width = width;
+            win.height = height;
+            if let Some(decorations) = &mut win.decorations {
+                decorations.resize(width, height);
+            }
+        }
+
+        win.pixels.as_mut_slice()[..width * height].copy_from_slice(pixels);
+        win.pixels_opaque = win.pixels_are_opaque();
+
+        let damage1 = win.damage();
+        self.insert_damage(&[damage0, damage1]);
+        Ok(())
+    }
+
+    /// Asks the client that owns the window with the ID `win_id` to resize it to `width`*`height` pixels and move it to (`x`, `y`),
+    /// the window is resized once the client acknowledges using [`Self::ack_configure`].
+    ///
+    /// Windows without an owner are resized immediately.
+    pub fn configure_window(
+        &mut self,
+        win_id: WinID,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<(), ()> {
+        let (win, _) = self.windows.get_mut(&win_id).ok_or(())?;
+        let (max_x, max_y) = framebuffer::screen_size();
+
+        let extra_width = win.outer_width() - win.width;
+        let extra_height = win.outer_height() - win.height;
+
+        let width = width.max(MIN_WINDOW_WIDTH).min(max_x - extra_width);
+        let height = height.max(MIN_WINDOW_HEIGHT).min(max_y - extra_height);
+
+        if win.com_pipe.is_none() {
+            self.resize_window(win_id, width, height)?;
+            self.move_window(win_id, x, y);
+            return Ok(());
+        }
+
+        self.send_configure(win_id, x, y, width, height)
+    }
+
+    /// Sends a configure event asking the client to resize the window with the ID `win_id` to `width`*`height` pixels
+    /// and to move it to (`x`, `y`), unless the same resize is already pending, see [`Self::configure_window`]
+    fn send_configure(
+        &mut self,
+        win_id: WinID,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<(), ()> {
+        let (win, _) = self.windows.get_mut(&win_id).ok_or(())?;
+        match &mut win.pending_configure {
+            Some(pending) if pending.width == width && pending.height == height => {
+                pending.pos_x = x;
+                pending.pos_y = y;
+                return Ok(());
+            }
+            // Nothing for the client to acknowledge
+            None if win.width == width && win.height == height => {
+                self.move_window(win_id, x, y);
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        win.configure_serial = win.configure_serial.wrapping_add(1);
+        win.pending_configure = Some(PendingConfigure {
+            serial: win.configure_serial,
+            pos_x: x,
+            pos_y: y,
+            width,
+            height,
+        });
+
+        win.send_event(Event::Configure(ConfigureEvent::new(
+            win.configure_serial,
+            width as u32,
+            height as u32,
+        )));
+        Ok(())
+    }
+
+    /// Acknowledges the configure event with the serial `serial` sent to the window with the ID `win_id`,
+    /// if it is the most recent configure event the window is resized, see [`Self::resize_window`].
+    ///
+    /// Returns None if the configure event was an older one, even if the most recent one was already acknowledged.
+    pub fn ack_configure(
+        &mut self,
+        win_id: WinID,
+        serial: u32,
+    ) -> Result<Option<(usize, usize, usize)>, ResponseError> {
+        let (win, _) = self
+            .windows
+            .get_mut(&win_id)
+            .ok_or(ResponseError::UnknownWindow)?;
+
+        let pending = match win.pending_configure {
+            Some(pending) if pending.serial == serial => pending,
+            // An older configure event, possibly acknowledged after the most recent one was already applied
+            _ => {
+                // Serials wrap around, older serials are the ones behind the most recent one
+                let older = win.configure_serial.wrapping_sub(serial) < u32::MAX / 2;
+                return if older {
+                    Ok(None)
+                } else {
+                    Err(ResponseError::InvalidData)
+                };
+            }
+        };
+
+        win.pending_configure = None;
+        let resized = self
+            .resize_window(win_id, pending.width, pending.height)
+            .map_err(|()| ResponseError::UnknownWindow)?;
+        self.move_window(win_id, pending.pos_x, pending.pos_y);
+        Ok(Some(resized))
+    }
+
+    /// Checks whether or not the window with the ID `win_id` is owned by the client `client`
+    pub fn check_owner(&self, win_id: WinID, client: ClientID) -> Result<(), ResponseError> {
+        let (win, _) = self
+            .windows
+            .get(&win_id)
+            .ok_or(ResponseError::UnknownWindow)?;
+
+        if win.owner() != Some(client) {
+            return Err(ResponseError::PermissionDenied);
+        }
+
+        Ok(())
+    }
+
     /// Returns the ID of the focused Window
     pub const fn focused_window(&self) -> Option<WinID> {
         self.focused_window
@@ -630,20 +1098,128 @@ impl Windows {
         let x = x.min(win.width);
         let y = y.min(win.height);
 
-        let pos_x = (win.pos_x + x).min(win.pos_x + win.width);
-        let pos_y = (win.pos_y + y).min(win.pos_y + win.height);
+        let (client_x, client_y) = win.client_offset();
+        let client_pos_x = win.pos_x + client_x;
+        let client_pos_y = win.pos_y + client_y;
+
+        let pos_x = (client_pos_x + x).min(client_pos_x + win.width);
+        let pos_y = (client_pos_y + y).min(client_pos_y + win.height);
         let width = width.min(win.width - x);
         let height = height.min(win.height - y);
 
-        self.insert_damage(&[DamageRegion {
-            pos_x,
-            pos_y,
-            width,
-            height,
-        }]);
+        win.update_pixels_opaque(Rect::new(x, y, width, height));
+        self.insert_damage(&[Rect::new(pos_x, pos_y, width, height)]);
+        Ok(())
+    }
+
+    /// Resizes the window with the ID `win_id` to `width`*`height` pixels, the size is clamped so the whole window fits in the screen.
+    ///
+    /// Returns the new shared memory key of the window's pixels and the actual new width and height.
+    pub fn resize_window(
+        &mut self,
+        win_id: WinID,
+        width: usize,
+        height: usize,
+    ) -> Result<(usize, usize, usize), ()> {
+        let (win, _) = self.windows.get_mut(&win_id).ok_or(())?;
+        let (max_x, max_y) = framebuffer::screen_size();
+
+        let extra_width = win.outer_width() - win.width;
+        let extra_height = win.outer_height() - win.height;
+
+        let width = width.min(max_x - extra_width);
+        let height = height.min(max_y - extra_height);
+
+        let damage0 = win.damage();
+        let shm_key = win.resize(width, height);
+
+        // Keep the window within the screen
+        win.pos_x = win.pos_x.min(max_x - win.outer_width());
+        win.pos_y = win.pos_y.min(max_y - win.outer_height());
+
+        let damage1 = win.damage();
+        win.send_event(Event::WindowResized(WindowResizedEvent::new(
+            width as u32,
+            height as u32,
+        )));
+
+        self.insert_damage(&[damage0, damage1]);
+        Ok((shm_key, width, height))
+    }
+
+    /// Sets the title of the window with the ID `win_id`
+    pub fn set_window_title(&mut self, win_id: WinID, title: String) -> Result<(), ()> {
+        let (win, _) = self.windows.get_mut(&win_id).ok_or(())?;
+
+        if win.set_title(title) {
+            let damage = win.damage();
+            self.insert_damage(&[damage]);
+        }
         Ok(())
     }
 
+    /// Sets the application ID of the window with the ID `win_id`
+    pub fn set_app_id(&mut self, win_id: WinID, app_id: String) -> Result<(), ()> {
+        let (win, _) = self.windows.get_mut(&win_id).ok_or(())?;
+        win.app_id = app_id;
+        Ok(())
+    }
+
+    /// Sets the part of the window with the ID `win_id`'s pixels that is fully opaque, see [`Window::opaque_hint`],
+    /// if `hint` is None whether or not the window is opaque is derived from it's pixels instead.
+    pub fn set_opaque_region(&mut self, win_id: WinID, hint: Option<Rect>) -> Result<(), ()> {
+        let (win, _) = self.windows.get_mut(&win_id).ok_or(())?;
+        win.opaque_hint = hint;
+        if hint.is_none() {
+            win.pixels_opaque = win.pixels_are_opaque();
+        }
+
+        // What is behind the window may have to be drawn now
+        let damage = win.damage();
+        self.insert_damage(&[damage]);
+        Ok(())
+    }
+
+    /// Requests a [`Event::FrameDone`] event to be sent to the window with the ID `win_id` once the next frame is presented
+    pub fn request_frame_callback(&mut self, win_id: WinID) -> Result<(), ()> {
+        let (win, _) = self.windows.get_mut(&win_id).ok_or(())?;
+        win.frame_callback_requested = true;
+        Ok(())
+    }
+
+    /// Draws the window with the ID `win_id` (including the decorations) as if nothing else was on the screen,
+    /// returns the width and the height of the whole window and it's pixels with straight alpha.
+    pub fn capture_window(&self, win_id: WinID) -> Result<(usize, usize, Vec<Pixel>), ()> {
+        let (win, _) = self.windows.get(&win_id).ok_or(())?;
+        let width = win.outer_width();
+        let height = win.outer_height();
+
+        // Starts fully transparent
+        let mut surface = HeadlessBackend::new(width, height);
+        win.draw_at(&mut surface, Rect::new(0, 0, width, height), 0, 0);
+
+        let pixels = surface
+            .pixels()
+            .iter()
+            .map(Pixel::unpremultiplied)
+            .collect();
+        Ok((width, height, pixels))
+    }
+
+    /// Notifies the windows that requested a frame callback and were drawn since the last frame that a frame was presented at `timestamp`,
+    /// windows that weren't drawn (nothing changed in them, they are covered or minimized) keep waiting for a frame containing them.
+    pub fn frame_done(&mut self, timestamp: u64) {
+        for win_id in self.presented_windows.drain(..) {
+            // The window may have been removed after it was drawn
+            if let Some((win, _)) = self.windows.get_mut(&win_id)
+                && win.frame_callback_requested
+            {
+                win.frame_callback_requested = false;
+                win.send_event(Event::FrameDone(FrameDoneEvent::new(timestamp)));
+            }
+        }
+    }
+
     pub fn send_event(&mut self, win_id: WinID, event: Event) -> Result<(), ()> {
         let (win, _) = self.windows.get_mut(&win_id).ok_or(())?;
         win.send_event(event);
@@ -680,7 +1256,11 @@ impl Windows {
             self.remove_id(win_id),
             "Unexpected behavior, ID should have been removed successfully"
         );
-        dlog!("Window removed");
+        dlog!(
+            "Window {win_id} removed, title: {:?}, app ID: {:?}",
+            window.title,
+            window.app_id
+        );
         Ok(())
     }
 }
@@ -695,24 +1275,177 @@ pub fn add_window(window: Window, kind: WindowKind) -> Option<WinID> {
         .add_window(window, kind)
 }
 
+/// Checks whether or not a window is owned by the client `client`, see [`Windows::check_owner`]
+pub fn check_owner(client: ClientID, win_id: WinID) -> Result<(), ResponseError> {
+    WINDOWS
+        .lock()
+        .expect("Failed to acquire lock on Windows while checking a Window's owner")
+        .check_owner(win_id, client)
+}
+
+/// Damages a window owned by the client `client`, see [`Windows::damage_window`]
 pub fn damage_window(
+    client: ClientID,
     win_id: WinID,
     x: usize,
     y: usize,
     width: usize,
     height: usize,
-) -> Result<(), ()> {
-    WINDOWS
+) -> Result<(), ResponseError> {
+    let mut windows = WINDOWS
         .lock()
-        .expect("Failed to acquire lock on Windows while damaging a Window")
+        .expect("Failed to acquire lock on Windows while damaging a Window");
+    windows.check_owner(win_id, client)?;
+    windows
         .damage_window(win_id, x, y, width, height)
+        .map_err(|()| ResponseError::UnknownWindow)
+}
+
+/// Moves a window owned by the client `client`, see [`Windows::move_window`]
+pub fn move_window(
+    client: ClientID,
+    win_id: WinID,
+    x: usize,
+    y: usize,
+) -> Result<(usize, usize), ResponseError> {
+    let mut windows = WINDOWS
+        .lock()
+        .expect("Failed to acquire lock on Windows while moving a Window");
+    windows.check_owner(win_id, client)?;
+    windows
+        .move_window(win_id, x, y)
+        .ok_or(ResponseError::UnknownWindow)
+}
+
+/// Completely removes a window owned by the client `client`, see [`Windows::remove_window`]
+pub fn remove_window(client: ClientID, win_id: WinID) -> Result<(), ResponseError> {
+    let mut windows = WINDOWS
+        .lock()
+        .expect("Failed to acquire lock on Windows while removing a Window");
+    windows.check_owner(win_id, client)?;
+    windows
+        .remove_window(win_id)
+        .map_err(|()| ResponseError::UnknownWindow)
+}
+
+/// Resizes a window owned by the client `client`, see [`Windows::resize_window`]
+pub fn resize_window(
+    client: ClientID,
+    win_id: WinID,
+    width: usize,
+    height: usize,
+) -> Result<(usize, usize, usize), ResponseError> {
+    let mut windows = WINDOWS
+        .lock()
+        .expect("Failed to acquire lock on Windows while resizing a Window");
+    windows.check_owner(win_id, client)?;
+    windows
+        .resize_window(win_id, width, height)
+        .map_err(|()| ResponseError::UnknownWindow)
+}
+
+/// Acknowledges a configure event sent to a window owned by the client `client`, see [`Windows::ack_configure`]
+pub fn ack_configure(
+    client: ClientID,
+    win_id: WinID,
+    serial: u32,
+) -> Result<Option<(usize, usize, usize)>, ResponseError> {
+    let mut windows = WINDOWS
+        .lock()
+        .expect("Failed to acquire lock on Windows while acknowledging a configure event");
+    windows.check_owner(win_id, client)?;
+    windows.ack_configure(win_id, serial)
+}
+
+/// Sets the cursor over a window owned by the client `client`, see [`Windows::set_window_cursor`]
+pub fn set_window_cursor(
+    client: ClientID,
+    win_id: WinID,
+    shape: CursorShape,
+) -> Result<(), ResponseError> {
+    let mut windows = WINDOWS
+        .lock()
+        .expect("Failed to acquire lock on Windows while setting a Window's cursor");
+    windows.check_owner(win_id, client)?;
+    windows
+        .set_window_cursor(win_id, shape)
+        .map_err(|()| ResponseError::UnknownWindow)
+}
+
+/// Sets the opaque region of a window owned by the client `client`, see [`Windows::set_opaque_region`]
+pub fn set_opaque_region(
+    client: ClientID,
+    win_id: WinID,
+    hint: Option<Rect>,
+) -> Result<(), ResponseError> {
+    let mut windows = WINDOWS
+        .lock()
+        .expect("Failed to acquire lock on Windows while setting a Window's opaque region");
+    windows.check_owner(win_id, client)?;
+    windows
+        .set_opaque_region(win_id, hint)
+        .map_err(|()| ResponseError::UnknownWindow)
+}
+
+/// Replaces the layer behind every window, see [`Windows::set_wallpaper`]
+pub fn set_wallpaper(wallpaper: Wallpaper) {
+    WINDOWS
+        .lock()
+        .expect("Failed to acquire lock on Windows while setting the wallpaper")
+        .set_wallpaper(wallpaper);
+}
+
+/// Captures any window, see [`Windows::capture_window`]
+pub fn capture_window(win_id: WinID) -> Result<(usize, usize, Vec<Pixel>), ResponseError> {
+    WINDOWS
+        .lock()
+        .expect("Failed to acquire lock on Windows while capturing a Window")
+        .capture_window(win_id)
+        .map_err(|()| ResponseError::UnknownWindow)
+}
+
+/// Requests a frame callback for a window owned by the client `client`, see [`Windows::request_frame_callback`]
+pub fn request_frame_callback(client: ClientID, win_id: WinID) -> Result<(), ResponseError> {
+    let mut windows = WINDOWS
+        .lock()
+        .expect("Failed to acquire lock on Windows while requesting a frame callback");
+    windows.check_owner(win_id, client)?;
+    windows
+        .request_frame_callback(win_id)
+        .map_err(|()| ResponseError::UnknownWindow)
+}
+
+/// Sets the title of a window owned by the client `client`, see [`Windows::set_window_title`]
+pub fn set_window_title(
+    client: ClientID,
+    win_id: WinID,
+    title: String,
+) -> Result<(), ResponseError> {
+    let mut windows = WINDOWS
+        .lock()
+        .expect("Failed to acquire lock on Windows while setting a Window's title");
+    windows.check_owner(win_id, client)?;
+    windows
+        .set_window_title(win_id, title)
+        .map_err(|()| ResponseError::UnknownWindow)
+}
+
+/// Sets the application ID of a window owned by the client `client`, see [`Windows::set_app_id`]
+pub fn set_app_id(client: ClientID, win_id: WinID, app_id: String) -> Result<(), ResponseError> {
+    let mut windows = WINDOWS
+        .lock()
+        .expect("Failed to acquire lock on Windows while setting a Window's app ID");
+    windows.check_owner(win_id, client)?;
+    windows
+        .set_app_id(win_id, app_id)
+        .map_err(|()| ResponseError::UnknownWindow)
 }
 
 /// Whether we should redraw the screen
 static SHOULD_REDRAW: AtomicBool = AtomicBool::new(false);
 
 /// Returns true if you should call `redraw_screen`
-fn should_redraw() -> bool {
+pub fn should_redraw() -> bool {
     SHOULD_REDRAW.load(Ordering::Acquire)
 }
 
@@ -726,3 +1459,189 @@ pub fn redraw() {
             .damage_redraw();
     }
 }
+
+/// Notifies the windows waiting for a frame callback that a frame was presented at `timestamp`, see [`Windows::frame_done`]
+pub fn frame_done(timestamp: u64) {
+    WINDOWS
+        .lock()
+        .expect("Failed to acquire lock on Windows while finishing a frame")
+        .frame_done(timestamp);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GREEN: Pixel = Pixel::from_rgba(0, 0xFF, 0, 0xFF);
+    const HALF_RED: Pixel = Pixel::from_rgba(0xFF, 0, 0, 0x80);
+
+    /// Returns the total area of `rects`, asserting that none of them overlap
+    fn synced_area(fb: &mut HeadlessBackend) -> usize {
+        let rects: Vec<Rect> = fb
+            .take_synced_rects()
+            .into_iter()
+            .map(|rect| Rect::new(rect.off_x, rect.off_y, rect.width, rect.height))
+            .collect();
+
+        for (i, a) in rects.iter().enumerate() {
+            for b in &rects[i + 1..] {
+                assert_eq!(a.intersection(b), None, "{a:?} and {b:?} were both synced");
+            }
+        }
+        rects.iter().map(|rect| rect.width * rect.height).sum()
+    }
+
+    fn pixel_at(fb: &HeadlessBackend, x: usize, y: usize) -> Pixel {
+        fb.pixels()[y * fb.width() + x]
+    }
+
+    #[test]
+    fn damage_redraw_composites_and_syncs_the_damage_once() {
+        let mut windows = Windows::new();
+        let green = windows
+            .add_window(
+                Window::new_filled_with(10, 10, 20, 20, GREEN),
+                WindowKind::Normal,
+            )
+            .unwrap();
+        let red = windows
+            .add_window(
+                Window::new_filled_with(20, 20, 20, 20, HALF_RED),
+                WindowKind::Normal,
+            )
+            .unwrap();
+
+        let mut fb = HeadlessBackend::new(64, 64);
+        windows.damage_redraw_to(&mut fb);
+
+        // Both windows overlap on a 10*10 square
+        assert_eq!(synced_area(&mut fb), 20 * 20 * 2 - 10 * 10);
+        assert_eq!(pixel_at(&fb, 15, 15), GREEN);
+        assert_eq!(pixel_at(&fb, 25, 25), HALF_RED.premultiplied().over(&GREEN));
+        // Only the damage is drawn
+        assert_eq!(pixel_at(&fb, 5, 5), Pixel::from_hex(0));
+
+        // Nothing changed since
+        windows.damage_redraw_to(&mut fb);
+        assert_eq!(synced_area(&mut fb), 0);
+
+        // The old and the new position of the red window don't overlap
+        windows.move_window(red, 40, 40).unwrap();
+        windows.damage_redraw_to(&mut fb);
+        assert_eq!(synced_area(&mut fb), 20 * 20 * 2);
+        assert_eq!(pixel_at(&fb, 25, 25), GREEN);
+        assert_eq!(pixel_at(&fb, 35, 35), Pixel::from_hex(0xFF28_2828));
+        assert_eq!(
+            pixel_at(&fb, 45, 45),
+            HALF_RED.premultiplied().over(&Pixel::from_hex(0xFF28_2828))
+        );
+
+        windows.remove_window(green).unwrap();
+        windows.damage_redraw_to(&mut fb);
+        assert_eq!(synced_area(&mut fb), 20 * 20);
+        assert_eq!(pixel_at(&fb, 15, 15), Pixel::from_hex(0xFF28_2828));
+    }
+
+    #[test]
+    fn damage_redraw_clips_windows_to_the_screen() {
+        let mut windows = Windows::new();
+        windows
+            .add_window(
+                Window::new_filled_with(50, 50, 40, 40, HALF_RED),
+                WindowKind::Normal,
+            )
+            .unwrap();
+
+        let mut fb = HeadlessBackend::new(64, 64);
+        windows.damage_redraw_to(&mut fb);
+
+        assert_eq!(synced_area(&mut fb), 14 * 14);
+        assert_eq!(
+            pixel_at(&fb, 63, 63),
+            HALF_RED.premultiplied().over(&Pixel::from_hex(0xFF28_2828))
+        );
+    }
+
+    #[test]
+    fn ack_configure_ignores_configures_older_than_the_applied_one() {
+        let mut windows = Windows::new();
+        let id = windows
+            .add_window(
+                Window::new_filled_with(0, 0, 100, 100, GREEN),
+                WindowKind::Normal,
+            )
+            .unwrap();
+
+        windows.send_configure(id, 0, 0, 200, 200).unwrap();
+        windows.send_configure(id, 0, 0, 150, 150).unwrap();
+        assert_eq!(windows.geometry(id), Some((0, 0, 100, 100)));
+
+        // The most recent configure is applied
+        assert!(windows.ack_configure(id, 2).unwrap().is_some());
+        assert_eq!(windows.geometry(id), Some((0, 0, 150, 150)));
+
+        // The older one acknowledged late doesn't resize the window back
+        assert_eq!(windows.ack_configure(id, 1), Ok(None));
+        assert_eq!(windows.geometry(id), Some((0, 0, 150, 150)));
+
+        // A configure that was never sent
+        assert_eq!(
+            windows.ack_configure(id, 3),
+            Err(ResponseError::InvalidData)
+        );
+    }
+
+    #[test]
+    fn set_window_pixels_reuses_large_enough_buffers() {
+        let mut windows = Windows::new();
+        let id = windows
+            .add_window(
+                Window::new_filled_with(0, 0, 4, 4, GREEN),
+                WindowKind::Overlay,
+            )
+            .unwrap();
+        let buffer = |windows: &Windows| windows.windows[&id].0.pixels.as_slice().as_ptr();
+        let before = buffer(&windows);
+
+        windows.set_window_pixels(id, 2, 3, &[HALF_RED; 6]).unwrap();
+        assert_eq!(buffer(&windows), before);
+        assert_eq!(windows.geometry(id), Some((0, 0, 2, 3)));
+        assert_eq!(windows.windows[&id].0.client_pixels(), &[HALF_RED; 6]);
+
+        windows.set_window_pixels(id, 5, 5, &[GREEN; 25]).unwrap();
+        assert_eq!(windows.geometry(id), Some((0, 0, 5, 5)));
+        assert!(windows.windows[&id].0.pixels_opaque);
+    }
+
+    #[test]
+    fn frame_done_only_signals_the_windows_drawn_in_the_frame() {
+        let mut windows = Windows::new();
+        let drawn = windows
+            .add_window(
+                Window::new_filled_with(0, 0, 10, 10, GREEN),
+                WindowKind::Normal,
+            )
+            .unwrap();
+        let untouched = windows
+            .add_window(
+                Window::new_filled_with(30, 30, 10, 10, GREEN),
+                WindowKind::Normal,
+            )
+            .unwrap();
+        let requested = |windows: &Windows, id| windows.windows[&id].0.frame_callback_requested;
+
+        let mut fb = HeadlessBackend::new(64, 64);
+        windows.damage_redraw_to(&mut fb);
+        windows.frame_done(0);
+
+        windows.request_frame_callback(drawn).unwrap();
+        windows.request_frame_callback(untouched).unwrap();
+        let damage = windows.windows[&drawn].0.damage();
+        windows.insert_damage(&[damage]);
+        windows.damage_redraw_to(&mut fb);
+        windows.frame_done(1);
+
+        assert!(!requested(&windows, drawn));
+        assert!(requested(&windows, untouched));
+    }
+}